@@ -0,0 +1,88 @@
+use engine::error::Error;
+use engine::input::{Input, InputState};
+use engine::video::{Page, Polygon};
+use engine::{EngineOptions, Executor, Gfx};
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::directory::DirectoryIo;
+
+pub struct NullGfx;
+
+impl Gfx for NullGfx {
+    fn blit(&mut self, _page: Page) {}
+    fn draw_polygon(&mut self, _polygon: Polygon) {}
+    fn fill_page(&mut self, _page: Page, _color: u8) {}
+    fn select_page(&mut self, _page: Page) {}
+    fn copy_page(&mut self, _src: Page, _dest: Page, _scroll: i16) {}
+    fn set_palette(&mut self, _palette: [(u8, u8, u8); 16]) {}
+    fn draw_string(&mut self, _text: &'static str, _color: u8, _x: i16, _y: i16) {}
+}
+
+pub struct NullInput;
+
+impl Input for NullInput {
+    fn get_input(&self) -> InputState {
+        InputState::default()
+    }
+}
+
+pub fn run(path: PathBuf, frames: usize, options: &EngineOptions) -> Result<(), Error> {
+    let io = DirectoryIo::new(path);
+    let mut executor = Executor::with_options(io, NullGfx, NullInput, options)?;
+    executor.enable_timings();
+
+    let mut frame_times = Vec::with_capacity(frames);
+    let start = Instant::now();
+    for _ in 0..frames {
+        let frame_start = Instant::now();
+        executor.run();
+        frame_times.push(frame_start.elapsed());
+    }
+    let total = start.elapsed();
+
+    if frame_times.is_empty() {
+        println!("no frames run");
+        return Ok(());
+    }
+
+    frame_times.sort();
+    let average = total / frame_times.len() as u32;
+
+    println!("frames:  {}", frame_times.len());
+    println!("total:   {}", format_duration(total));
+    println!(
+        "fps:     {:.1}",
+        frame_times.len() as f64 / total.as_secs_f64()
+    );
+    println!("average: {}", format_duration(average));
+    for percentile in [50, 95, 99] {
+        println!(
+            "p{}:     {}",
+            percentile,
+            format_duration(percentile_of(&frame_times, percentile))
+        );
+    }
+    println!(
+        "max:     {}",
+        format_duration(frame_times[frame_times.len() - 1])
+    );
+
+    if let Some(timings) = executor.timings() {
+        println!("vm:        {}", format_duration(timings.vm));
+        println!("video:     {}", format_duration(timings.video));
+        println!("resources: {}", format_duration(timings.resources));
+    }
+
+    Ok(())
+}
+
+fn percentile_of(sorted: &[Duration], percentile: usize) -> Duration {
+    let index = (sorted.len() * percentile / 100).min(sorted.len() - 1);
+    sorted[index]
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,157 @@
+use engine::input::InputState;
+use engine::{EngineOptions, Executor, Input};
+
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::directory::DirectoryIo;
+use super::dump::{self, DUMP_HEIGHT, DUMP_WIDTH};
+use super::gfx::{GlGfx, LocalGlHandle};
+
+/// Boots the game with `options`, so `--part` picks the part, runs it for `frames`
+/// blits while following the input script at `script`, then renders the last blitted
+/// page through the palette and writes it to `out` as a 320x200 PNG.
+///
+/// Nothing depends on wall clock time, so the same arguments always produce the same
+/// image, which makes it suitable for golden images and for reproducing bug reports.
+pub fn capture<P: AsRef<Path>>(
+    data_path: PathBuf,
+    frames: u64,
+    script: Option<P>,
+    out: P,
+    gfx: GlGfx,
+    options: &EngineOptions,
+) -> Result<(), String> {
+    let segments = match script {
+        Some(path) => {
+            let path = path.as_ref();
+            let text =
+                std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            parse_script(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => Vec::new(),
+    };
+
+    let frame = Rc::new(Cell::new(0));
+    let input = ScriptInput {
+        segments,
+        frame: frame.clone(),
+    };
+    let gfx = Rc::new(RefCell::new(gfx));
+    let io = DirectoryIo::new(data_path);
+    let mut executor = Executor::with_options(io, LocalGlHandle::new(gfx.clone()), input, options)
+        .map_err(|e| e.to_string())?;
+
+    while frame.get() < frames {
+        executor.run();
+        // a run cut short by the load budget has not blitted yet
+        if !executor.is_loading() {
+            frame.set(frame.get() + 1);
+        }
+    }
+
+    let rgba = gfx.borrow_mut().capture_frame(DUMP_WIDTH, DUMP_HEIGHT);
+    dump::write_png(out.as_ref(), &rgba).map_err(|e| e.to_string())
+}
+
+/// Holds the buttons of every script segment covering the current frame, which the
+/// capture loop advances once per blit.
+struct ScriptInput {
+    segments: Vec<(Range<u64>, InputState)>,
+    frame: Rc<Cell<u64>>,
+}
+
+impl Input for ScriptInput {
+    fn get_input(&self) -> InputState {
+        let frame = self.frame.get();
+        let mut state = InputState::default();
+
+        for (_, input) in self.segments.iter().filter(|(r, _)| r.contains(&frame)) {
+            state.up |= input.up;
+            state.down |= input.down;
+            state.left |= input.left;
+            state.right |= input.right;
+            state.action |= input.action;
+        }
+
+        state
+    }
+}
+
+/// Parses an input script, one segment per line as a frame or inclusive range of frames
+/// followed by the buttons held, for example `10-50 right action`. Frames count blits
+/// from boot starting at 0. Blank lines and everything after a `#` are ignored.
+pub fn parse_script(text: &str) -> Result<Vec<(Range<u64>, InputState)>, String> {
+    let mut segments = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let frames = match words.next() {
+            Some(frames) => frames,
+            None => continue,
+        };
+
+        let error = |message: String| format!("line {}: {}", number + 1, message);
+        let parse_frame = |frame: &str| {
+            frame
+                .parse::<u64>()
+                .map_err(|_| error(format!("invalid frame '{}'", frame)))
+        };
+        let (start, end) = match frames.split_once('-') {
+            Some((start, end)) => (parse_frame(start)?, parse_frame(end)?),
+            None => (parse_frame(frames)?, parse_frame(frames)?),
+        };
+        if end < start {
+            return Err(error(format!("range '{}' ends before it starts", frames)));
+        }
+
+        let mut input = InputState::default();
+        for button in words {
+            match button {
+                "up" => input.up = true,
+                "down" => input.down = true,
+                "left" => input.left = true,
+                "right" => input.right = true,
+                "action" => input.action = true,
+                _ => return Err(error(format!("unknown button '{}'", button))),
+            }
+        }
+
+        segments.push((start..end + 1, input));
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_frames_ranges_and_buttons() {
+        let script = "# walk right then jump\n0-9 right\n\n10 up action # jump\n";
+        let segments = parse_script(script).unwrap();
+
+        let ranges: Vec<_> = segments.iter().map(|(range, _)| range.clone()).collect();
+        assert_eq!(ranges, [0..10, 10..11]);
+        let (walk, jump) = (segments[0].1, segments[1].1);
+        assert!(walk.right && !walk.up && !walk.action);
+        assert!(jump.up && jump.action && !jump.right);
+
+        assert_eq!(
+            parse_script("5 jump").unwrap_err(),
+            "line 1: unknown button 'jump'"
+        );
+        assert_eq!(
+            parse_script("\n9-3 left").unwrap_err(),
+            "line 2: range '9-3' ends before it starts"
+        );
+        assert_eq!(
+            parse_script("x right").unwrap_err(),
+            "line 1: invalid frame 'x'"
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use engine::demo::{InputRecording, PlaybackInput};
+use engine::desync::{self, CommandLog};
+use engine::{EngineOptions, Executor};
+
+use std::path::{Path, PathBuf};
+
+use super::benchmark::NullGfx;
+use super::directory::DirectoryIo;
+
+/// Runs the game headless for `frames` frames and writes the video commands it issued
+/// to `out`, replaying the input recording at `input` when given.
+pub fn record_commands<P: AsRef<Path>>(
+    data_path: PathBuf,
+    frames: usize,
+    input: Option<P>,
+    out: P,
+    options: &EngineOptions,
+) -> Result<(), String> {
+    let recording = match input {
+        Some(input) => {
+            let bytes = std::fs::read(input).map_err(|e| e.to_string())?;
+            InputRecording::from_bytes(&bytes)
+        }
+        None => InputRecording::new(),
+    };
+
+    let io = DirectoryIo::new(data_path);
+    let input = PlaybackInput::new(recording);
+    let mut executor =
+        Executor::with_options(io, NullGfx, input, options).map_err(|e| e.to_string())?;
+    let log = desync::record_commands(&mut executor, frames);
+
+    std::fs::write(out, log.to_text()).map_err(|e| e.to_string())
+}
+
+/// Compares two command logs, returning whether they match and printing the first
+/// difference when they do not.
+pub fn diff_run<P: AsRef<Path>>(a: P, b: P) -> Result<bool, String> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map(|text| CommandLog::from_text(&text))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    };
+    let a = read(a.as_ref())?;
+    let b = read(b.as_ref())?;
+
+    match a.first_divergence(&b) {
+        Some(divergence) => {
+            println!("runs diverge at {}", divergence);
+            Ok(false)
+        }
+        None => {
+            println!("runs match over {} frames", a.len());
+            Ok(true)
+        }
+    }
+}
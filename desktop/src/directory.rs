@@ -1,26 +1,117 @@
 use engine::error::Error;
 use engine::resources::Io;
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Loads game files from a directory. Names are matched case-insensitively, one path
+/// component at a time, so dumps with `memlist.bin` or `Bank0A` load on case-sensitive
+/// filesystems. Each directory is listed once and cached.
 pub struct DirectoryIo {
-    base_path: std::path::PathBuf,
+    base_path: PathBuf,
+    listings: RefCell<HashMap<PathBuf, Vec<String>>>,
 }
 
 impl DirectoryIo {
-    pub fn new<P: Into<std::path::PathBuf>>(base_path: P) -> Self {
+    pub fn new<P: Into<PathBuf>>(base_path: P) -> Self {
         Self {
             base_path: base_path.into(),
+            listings: RefCell::new(HashMap::new()),
         }
     }
+
+    fn resolve(&self, name: &str) -> Result<PathBuf, std::io::Error> {
+        let mut path = self.base_path.clone();
+
+        for component in name.split('/') {
+            let exact = path.join(component);
+            if exact.exists() {
+                path = exact;
+                continue;
+            }
+
+            let mut listings = self.listings.borrow_mut();
+            let entries = listings
+                .entry(path.clone())
+                .or_insert_with(|| list_directory(&path));
+
+            match entries.iter().find(|e| e.eq_ignore_ascii_case(component)) {
+                Some(entry) => path = path.join(entry),
+                None => {
+                    let message = format!(
+                        "{} not found in {}, found: {}",
+                        component,
+                        path.display(),
+                        entries.join(", ")
+                    );
+                    return Err(std::io::Error::new(std::io::ErrorKind::NotFound, message));
+                }
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+fn list_directory(path: &Path) -> Vec<String> {
+    let mut entries: Vec<String> = std::fs::read_dir(path)
+        .map(|dir| {
+            dir.filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
 }
 
 impl Io for DirectoryIo {
     type Reader = std::fs::File;
 
     fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
-        let path = self.base_path.join(name.as_ref());
+        let path = self.resolve(name.as_ref())?;
 
         eprintln!("load: {}", path.display());
 
         Ok(std::fs::File::open(path)?)
     }
+
+    fn exists<S: AsRef<str>>(&self, name: S) -> bool {
+        self.resolve(name.as_ref()).is_ok_and(|path| path.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_mixed_case_names() {
+        let dir = std::env::temp_dir().join(format!("mass-aw-directory-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("game").join("dat")).unwrap();
+        for name in ["memlist.bin", "Bank01", "bank0a", "game/dat/file021.dat"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+
+        let io = DirectoryIo::new(&dir);
+        for (name, expected) in [
+            ("MEMLIST.BIN", "memlist.bin"),
+            ("BANK01", "Bank01"),
+            ("BANK0A", "bank0a"),
+            ("Game/DAT/FILE021.DAT", "game/dat/file021.dat"),
+        ] {
+            assert!(io.exists(name));
+            let contents = std::io::read_to_string(io.load(name).unwrap()).unwrap();
+            assert_eq!(contents, expected);
+        }
+
+        assert!(!io.exists("BANK02"));
+        match io.load("BANK02") {
+            Err(err) => assert!(err.to_string().contains("found: Bank01, bank0a, game")),
+            Ok(_) => panic!("expected missing file error"),
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+/// Frames are captured at the game's native resolution regardless of the window size.
+pub const DUMP_WIDTH: u32 = 320;
+pub const DUMP_HEIGHT: u32 = 200;
+
+/// Default cap on the number of frames written, roughly 15 minutes of play.
+pub const DEFAULT_DUMP_LIMIT: u64 = 20_000;
+
+/// Writes every blitted frame to `dir` as `frame_NNNNNN.png`, for diffing a playthrough
+/// against other implementations. Stops once `limit` frames have been written.
+pub struct FrameDump {
+    dir: PathBuf,
+    frame: u64,
+    limit: u64,
+}
+
+impl FrameDump {
+    pub fn new<P: Into<PathBuf>>(dir: P, limit: u64) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            frame: 0,
+            limit,
+        })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frame >= self.limit
+    }
+
+    /// Writes one RGBA frame of `DUMP_WIDTH` by `DUMP_HEIGHT` pixels, top row first.
+    pub fn write(&mut self, rgba: &[u8]) {
+        if self.is_full() {
+            return;
+        }
+
+        let path = self.dir.join(format!("frame_{:06}.png", self.frame));
+        self.frame += 1;
+
+        if let Err(err) = write_png(&path, rgba) {
+            eprintln!("unable to write {}: {}", path.display(), err);
+        } else if self.is_full() {
+            eprintln!("frame dump limit of {} reached", self.limit);
+        }
+    }
+}
+
+/// Writes one RGBA frame of `DUMP_WIDTH` by `DUMP_HEIGHT` pixels to `path`.
+pub fn write_png(path: &std::path::Path, rgba: &[u8]) -> Result<(), png::EncodingError> {
+    let file = std::fs::File::create(path)?;
+    let writer = std::io::BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(writer, DUMP_WIDTH, DUMP_HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)
+}
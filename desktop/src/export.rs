@@ -0,0 +1,87 @@
+use engine::audio::{Mixer, Sample, DEFAULT_SAMPLE_RATE, FREQUENCY_TABLE, MAX_VOLUME};
+use engine::Resources;
+
+use std::io::Write;
+use std::path::Path;
+
+use super::directory::DirectoryIo;
+
+/// Decodes sound resource `index`, renders it through the mixer as if played at
+/// `FREQUENCY_TABLE[freq]`, and writes the result as a 16-bit mono WAV.
+pub fn export_sound<P: AsRef<Path>>(
+    data_path: P,
+    index: u16,
+    freq: u8,
+    out: P,
+) -> Result<(), String> {
+    let io = DirectoryIo::new(data_path.as_ref());
+    let mut resources = Resources::load(io).map_err(|e| e.to_string())?;
+
+    let data = resources.load_sound(index).map_err(|e| e.to_string())?;
+    let sample =
+        Sample::decode(data).ok_or_else(|| format!("resource {:#x} is not a sound", index))?;
+    let frequency = *FREQUENCY_TABLE
+        .get(freq as usize)
+        .ok_or_else(|| format!("frequency index must be 0-{}", FREQUENCY_TABLE.len() - 1))?
+        as u64;
+
+    let rate = DEFAULT_SAMPLE_RATE as u64;
+    let frames = (sample.data().len() as u64 * rate / frequency) as usize;
+    let loop_start = sample
+        .loop_start()
+        .map(|start| (start as u64 * rate / frequency) as u32);
+
+    let mut mixer = Mixer::new(DEFAULT_SAMPLE_RATE);
+    mixer.play(0, sample, frequency as u32, MAX_VOLUME);
+    let mut buffer = vec![0.0; frames];
+    mixer.mix(&mut buffer);
+
+    let file = std::fs::File::create(out.as_ref()).map_err(|e| e.to_string())?;
+    let mut writer = std::io::BufWriter::new(file);
+    write_wav(&mut writer, &buffer, loop_start).map_err(|e| e.to_string())
+}
+
+fn write_wav<W: Write>(
+    out: &mut W,
+    samples: &[f32],
+    loop_start: Option<u32>,
+) -> std::io::Result<()> {
+    let data_len = samples.len() as u32 * 2;
+    let smpl_len = if loop_start.is_some() { 8 + 60 } else { 0 };
+
+    out.write_all(b"RIFF")?;
+    out.write_all(&(4 + 8 + 16 + 8 + data_len + smpl_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&DEFAULT_SAMPLE_RATE.to_le_bytes())?;
+    out.write_all(&(DEFAULT_SAMPLE_RATE * 2).to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        let value = (sample * i16::MAX as f32) as i16;
+        out.write_all(&value.to_le_bytes())?;
+    }
+
+    if let Some(loop_start) = loop_start {
+        let loop_end = (samples.len() as u32).saturating_sub(1);
+        let sample_period = 1_000_000_000 / DEFAULT_SAMPLE_RATE;
+
+        out.write_all(b"smpl")?;
+        out.write_all(&60u32.to_le_bytes())?;
+        for value in [0, 0, sample_period, 60, 0, 0, 0, 1, 0] {
+            out.write_all(&u32::to_le_bytes(value))?;
+        }
+        for value in [0, 0, loop_start, loop_end, 0, 0] {
+            out.write_all(&u32::to_le_bytes(value))?;
+        }
+    }
+
+    out.flush()
+}
@@ -5,19 +5,23 @@ use glium::{
     uniforms::Sampler,
     DrawParameters, IndexBuffer, Rect, Surface, Texture2d, VertexBuffer,
 };
-use lyon::{
-    lyon_tessellation::{BuffersBuilder, FillOptions, FillVertex, VertexBuffers},
-    path::traits::PathBuilder,
-    tessellation::FillTessellator,
-};
 use winit::event_loop::{EventLoop, EventLoopProxy};
 
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::{Arc, Condvar, Mutex};
-
-use engine::gfx::Gfx;
-use engine::video::{BlendMode, Page, Polygon};
-
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use engine::font::{self, FontBitmap};
+use engine::gfx::{Gfx, PageSnapshot, PAGE_HEIGHT, PAGE_WIDTH};
+use engine::input::InputState;
+use engine::tessellate::{Geometry, Tessellator};
+use engine::video::{self, BlendMode, Page, PaletteMode, Polygon};
+use engine::vm::ThreadActivity;
+
+use super::dump::{FrameDump, DUMP_HEIGHT, DUMP_WIDTH};
+use super::lock::lock;
 use super::shaders::*;
 use super::UserEvent;
 
@@ -27,15 +31,13 @@ struct RenderPage {
 }
 
 impl RenderPage {
-    fn new(display: &glium::Display) -> Self {
-        let (width, height) = display.get_framebuffer_dimensions();
-
-        let texture = UnsignedTexture2d::empty_with_format(
+    /// Creates a page with every pixel set to the color index `clear`.
+    fn new(display: &glium::Display, width: u32, height: u32, clear: u8) -> Self {
+        let texture = UnsignedTexture2d::with_format(
             display,
+            blank_page(width, height, clear),
             UncompressedUintFormat::U8,
             MipmapsOption::NoMipmap,
-            width,
-            height,
         )
         .unwrap();
 
@@ -58,6 +60,15 @@ impl RenderPage {
     }
 }
 
+fn blank_page(width: u32, height: u32, clear: u8) -> RawImage2d<'static, u8> {
+    RawImage2d {
+        data: vec![clear; width as usize * height as usize].into(),
+        width,
+        height,
+        format: glium::texture::ClientFormat::U8,
+    }
+}
+
 struct RenderPalette {
     colors: [(u8, u8, u8); 16],
     texture: Texture2d,
@@ -127,36 +138,174 @@ const SCREEN_QUAD: [QuadPoint; 6] = [
     },
 ];
 
-struct Sync {
-    lock: Mutex<bool>,
+const SYNC_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+struct SyncState {
+    requested: u64,
+    completed: u64,
+}
+
+/// Ticketed handshake that lets the game thread wait until the render thread has
+/// processed each event sent through the proxy.
+pub struct Sync {
+    lock: Mutex<SyncState>,
     condvar: Condvar,
 }
 
 impl Sync {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
-            lock: Mutex::new(false),
+            lock: Mutex::new(SyncState::default()),
             condvar: Condvar::new(),
         }
     }
-    fn notify(&self) {
-        let mut synced = self.lock.lock().unwrap();
-        *synced = true;
+
+    pub fn request(&self) -> u64 {
+        let mut state = lock(&self.lock);
+        state.requested += 1;
+        state.requested
+    }
+
+    pub fn notify(&self) {
+        let mut state = lock(&self.lock);
+        state.completed += 1;
         self.condvar.notify_one()
     }
 
-    fn wait(&self) {
-        let mut synced = self.lock.lock().unwrap();
-        while !*synced {
-            synced = self.condvar.wait(synced).unwrap();
+    pub fn wait(&self, ticket: u64) {
+        let state = lock(&self.lock);
+        let (state, result) = self
+            .condvar
+            .wait_timeout_while(state, SYNC_TIMEOUT, |state| state.completed < ticket)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if result.timed_out() {
+            log::warn!(
+                "render thread unresponsive after {}ms, {} events pending",
+                SYNC_TIMEOUT.as_millis(),
+                ticket - state.completed
+            );
+        }
+    }
+}
+
+/// Weight of the newest frame the moment it is blitted. Starting above zero keeps frame
+/// blending from delaying new frames by a full frame, the newest frame always
+/// dominates and the previous one fades out over the following frame interval.
+const MIN_BLEND_WEIGHT: f32 = 0.5;
+/// Longest gap between blits treated as a frame interval, longer pauses end the fade
+/// early rather than stretching it.
+const MAX_BLEND_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The last two output frames resolved through the palette, for blending between game
+/// frames on displays that refresh faster than the game draws.
+struct FrameBlend {
+    previous: Texture2d,
+    current: Texture2d,
+    blit_time: Instant,
+    interval: Duration,
+}
+
+impl FrameBlend {
+    fn new(display: &glium::Display, width: u32, height: u32) -> Self {
+        Self {
+            previous: Texture2d::empty(display, width, height).unwrap(),
+            current: Texture2d::empty(display, width, height).unwrap(),
+            blit_time: Instant::now(),
+            interval: MAX_BLEND_INTERVAL,
+        }
+    }
+
+    fn blit(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        let now = Instant::now();
+        self.interval = (now - self.blit_time).min(MAX_BLEND_INTERVAL);
+        self.blit_time = now;
+    }
+
+    fn weight(&self) -> f32 {
+        let phase = if self.interval.is_zero() {
+            1.0
+        } else {
+            (self.blit_time.elapsed().as_secs_f32() / self.interval.as_secs_f32()).min(1.0)
+        };
+
+        MIN_BLEND_WEIGHT + (1.0 - MIN_BLEND_WEIGHT) * phase
+    }
+}
+
+/// Time from each blit until the frame it produced is first presented, to compare the
+/// latency frame blending adds against presenting directly from `blit`. Blits that are
+/// replaced before being presented are not counted.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PresentLatency {
+    pending: Option<Instant>,
+    frames: u32,
+    total: Duration,
+    max: Duration,
+}
+
+impl PresentLatency {
+    fn blitted(&mut self, time: Instant) {
+        self.pending = Some(time);
+    }
+
+    fn presented(&mut self, time: Instant) {
+        if let Some(blit_time) = self.pending.take() {
+            let latency = time.saturating_duration_since(blit_time);
+            self.frames += 1;
+            self.total += latency;
+            self.max = self.max.max(latency);
         }
-        *synced = false;
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn average(&self) -> Duration {
+        self.total.checked_div(self.frames).unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
     }
 }
 
 struct GfxState {
     polygons: Vec<Polygon>,
     palette: Option<[(u8, u8, u8); 16]>,
+    font: Option<Box<FontBitmap>>,
+    thread_activity: [ThreadActivity; 64],
+    input: InputState,
+    cursor: Option<(i16, i16)>,
+    /// Pages read back for a `GlHandle` by `UserEvent::SavePages`
+    saved_pages: Option<PageSnapshot>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Pages are rendered at the window's framebuffer resolution
+    Smooth,
+    /// Pages are rendered at 320x200, one texel per game pixel, and upscaled on redraw
+    PixelAccurate,
+}
+
+impl RenderMode {
+    fn page_dimensions(&self, display: &glium::Display) -> (u32, u32) {
+        match self {
+            RenderMode::Smooth => display.get_framebuffer_dimensions(),
+            RenderMode::PixelAccurate => (320, 200),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PolygonMode {
+    Filled,
+    /// Polygons are stroked one game pixel wide instead of filled, to expose scene geometry
+    Wireframe,
 }
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -171,19 +320,34 @@ pub struct GlGfx {
     state: Arc<Mutex<GfxState>>,
     sync: Arc<Sync>,
     proxy: EventLoopProxy<UserEvent>,
-    tessellator: FillTessellator,
+    tessellator: Tessellator,
+    polygon_mode: PolygonMode,
+    depth_heatmap: bool,
     palette: RenderPalette,
     page_program: glium::Program,
     frame_program: glium::Program,
+    blend_program: glium::Program,
     copy_program: glium::Program,
+    readback_program: glium::Program,
     font_program: glium::Program,
+    overlay_program: glium::Program,
     pages: HashMap<GlPage, RenderPage>,
     output_page: Page,
     active_page: Page,
     screen_vertex_buffer: VertexBuffer<QuadPoint>,
-    tessellate_buffer: VertexBuffers<PolyPoint, u16>,
+    tessellate_buffer: Geometry<PolyPoint>,
     font_texture: UnsignedTexture2d,
     text_buffer: Vec<TextPoint>,
+    overlay_buffer: Vec<OverlayPoint>,
+    thread_overlay: bool,
+    input_overlay: bool,
+    mouse_mode: bool,
+    page_grid: bool,
+    frame_blend: Option<FrameBlend>,
+    present_latency: PresentLatency,
+    page_dimensions: (u32, u32),
+    frame_dump: Option<FrameDump>,
+    color_transform: [[f32; 3]; 3],
 }
 
 fn create_program(
@@ -192,9 +356,17 @@ fn create_program(
     fragment: &str,
     srgb: bool,
 ) -> glium::Program {
+    let version = if *display.get_opengl_version() >= glium::Version(glium::Api::Gl, 4, 2) {
+        "#version 420"
+    } else {
+        "#version 330"
+    };
+    let vertex = format!("{}\n{}", version, vertex);
+    let fragment = format!("{}\n{}", version, fragment);
+
     let program_input = glium::program::ProgramCreationInput::SourceCode {
-        vertex_shader: vertex,
-        fragment_shader: fragment,
+        vertex_shader: &vertex,
+        fragment_shader: &fragment,
         outputs_srgb: srgb,
         tessellation_control_shader: None,
         tessellation_evaluation_shader: None,
@@ -205,31 +377,11 @@ fn create_program(
     glium::Program::new(display, program_input).unwrap()
 }
 
-fn create_font(display: &glium::Display) -> UnsignedTexture2d {
-    let mut font_data = vec![0u8; 100 * 64];
-    for n in 0..96 {
-        let x_ind = (n % 10) * 8;
-        let y_ind = (n / 10) * 8;
-
-        for y in 0..8 {
-            let mut row = engine::font::FONT[(n * 8) + y];
-            for x in 0..8 {
-                let bit = row & 0x80 != 0;
-                row <<= 1;
-                let color = if bit { 0xff } else { 0x00 };
-
-                let x_off = x_ind + x;
-                let y_off = y_ind + y;
-
-                font_data[(y_off * 80) + x_off] = color;
-            }
-        }
-    }
-
+fn create_font(display: &glium::Display, font: &FontBitmap) -> UnsignedTexture2d {
     let data = RawImage2d {
-        data: font_data.into(),
-        width: 80,
-        height: 80,
+        data: font::build_atlas(font).into(),
+        width: font::ATLAS_SIZE as u32,
+        height: font::ATLAS_SIZE as u32,
         format: glium::texture::ClientFormat::U8,
     };
 
@@ -243,52 +395,82 @@ fn create_font(display: &glium::Display) -> UnsignedTexture2d {
 }
 
 impl GlGfx {
-    pub fn new(display: glium::Display, event_loop: &EventLoop<UserEvent>) -> Self {
+    pub fn new(
+        display: glium::Display,
+        event_loop: &EventLoop<UserEvent>,
+        render_mode: RenderMode,
+    ) -> Self {
         let proxy = event_loop.create_proxy();
 
         let page_program =
             create_program(&display, PAGE_VERTEX_SHADER, PAGE_FRAGMENT_SHADER, false);
+        // The palette texture is linear and the frame shader is flagged as already
+        // producing sRGB, so palette bytes reach the sRGB framebuffer unconverted, matching
+        // the web backend which does no color conversion at all.
         let frame_program =
             create_program(&display, FRAME_VERTEX_SHADER, FRAME_FRAGMENT_SHADER, true);
+        let blend_program =
+            create_program(&display, FRAME_VERTEX_SHADER, BLEND_FRAGMENT_SHADER, true);
         let copy_program =
             create_program(&display, COPY_VERTEX_SHADER, COPY_FRAGMENT_SHADER, false);
+        let readback_program = create_program(
+            &display,
+            FRAME_VERTEX_SHADER,
+            READBACK_FRAGMENT_SHADER,
+            false,
+        );
         let font_program =
             create_program(&display, FONT_VERTEX_SHADER, FONT_FRAGMENT_SHADER, false);
-
+        let overlay_program = create_program(
+            &display,
+            OVERLAY_VERTEX_SHADER,
+            OVERLAY_FRAGMENT_SHADER,
+            true,
+        );
+
+        let (page_width, page_height) = render_mode.page_dimensions(&display);
         let mut pages = HashMap::new();
-        let page = RenderPage::new(&display);
-        pages.insert(GlPage::Game(Page::Zero), page);
-        let page = RenderPage::new(&display);
-        pages.insert(GlPage::Game(Page::One), page);
-        let page = RenderPage::new(&display);
-        pages.insert(GlPage::Game(Page::Two), page);
-        let page = RenderPage::new(&display);
-        pages.insert(GlPage::Game(Page::Three), page);
-        let page = RenderPage::new(&display);
-        pages.insert(GlPage::Zero, page);
-        let page = RenderPage::new(&display);
-        pages.insert(GlPage::Current, page);
+        for page in [
+            GlPage::Game(Page::Zero),
+            GlPage::Game(Page::One),
+            GlPage::Game(Page::Two),
+            GlPage::Game(Page::Three),
+            GlPage::Zero,
+            GlPage::Current,
+        ] {
+            pages.insert(page, RenderPage::new(&display, page_width, page_height, 0));
+        }
 
         let palette = RenderPalette::new(&display);
 
         let screen_vertex_buffer = VertexBuffer::new(&display, SCREEN_QUAD.as_slice()).unwrap();
-        let tessellate_buffer: VertexBuffers<PolyPoint, u16> = VertexBuffers::new();
+        let tessellate_buffer: Geometry<PolyPoint> = Geometry::new();
 
-        let font_texture = create_font(&display);
+        let font_texture = create_font(&display, &font::FONT);
 
-        Self {
+        let gfx = Self {
             display,
             proxy,
             state: Arc::new(Mutex::new(GfxState {
                 polygons: Vec::new(),
                 palette: Some([(0, 0, 0); 16]),
+                font: None,
+                thread_activity: [ThreadActivity::Inactive; 64],
+                input: InputState::default(),
+                cursor: None,
+                saved_pages: None,
             })),
-            tessellator: FillTessellator::new(),
+            tessellator: Tessellator::new(),
+            polygon_mode: PolygonMode::Filled,
+            depth_heatmap: false,
             palette,
             page_program,
             frame_program,
+            blend_program,
             copy_program,
+            readback_program,
             font_program,
+            overlay_program,
             pages,
             output_page: Page::Zero,
             active_page: Page::Zero,
@@ -297,6 +479,233 @@ impl GlGfx {
             sync: Arc::new(Sync::new()),
             font_texture,
             text_buffer: Vec::new(),
+            overlay_buffer: Vec::new(),
+            thread_overlay: false,
+            input_overlay: false,
+            mouse_mode: false,
+            page_grid: false,
+            frame_blend: None,
+            present_latency: PresentLatency::default(),
+            page_dimensions: (page_width, page_height),
+            frame_dump: None,
+            color_transform: PaletteMode::Normal.color_transform(),
+        };
+        gfx.warm_up();
+        gfx
+    }
+
+    /// Draws a degenerate triangle with every program into scratch targets and waits for
+    /// the GPU, so drivers that compile shaders lazily do it now rather than on the first
+    /// frames that use them. Called by `new`.
+    pub fn warm_up(&self) {
+        let start = Instant::now();
+        let scratch_page = RenderPage::new(&self.display, 1, 1, 0);
+        let scratch_frame = Texture2d::empty(&self.display, 1, 1).unwrap();
+        let gpu_index_buffer = glium::index::NoIndices(PrimitiveType::TrianglesList);
+        let params = DrawParameters::default();
+
+        let page_zero = self.pages.get(&GlPage::Zero).unwrap();
+        let page_self = self.pages.get(&GlPage::Current).unwrap();
+
+        let point = PolyPoint {
+            position: (0.0, 0.0),
+            color: 0,
+            depth: 0,
+            mask: 0,
+        };
+        let vertex_buffer = VertexBuffer::new(&self.display, &[point; 3]).unwrap();
+        let uniforms = glium::uniform! {
+            u_max_depth: 1u32,
+            u_depth_heatmap: false,
+            u_page_zero: page_zero.sampled(),
+            u_page_self: page_self.sampled(),
+        };
+        scratch_page
+            .frame(&self.display)
+            .draw(
+                &vertex_buffer,
+                gpu_index_buffer,
+                &self.page_program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+
+        let uniforms = glium::uniform! {
+            u_page: page_zero.sampled(),
+            u_fill: 0u32,
+            u_scroll: 0i32
+        };
+        scratch_page
+            .frame(&self.display)
+            .draw(
+                &self.screen_vertex_buffer,
+                gpu_index_buffer,
+                &self.copy_program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+
+        let point = TextPoint {
+            position: (0.0, 0.0),
+            uv: (0.0, 0.0),
+        };
+        let vertex_buffer = VertexBuffer::new(&self.display, &[point; 3]).unwrap();
+        let uniforms = glium::uniform! {
+            u_font_atlas: self.font_texture.sampled(),
+            u_color: 0u32,
+        };
+        scratch_page
+            .frame(&self.display)
+            .draw(
+                &vertex_buffer,
+                gpu_index_buffer,
+                &self.font_program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+
+        let uniforms = glium::uniform! {
+            u_palette: self.palette.sampled(),
+            u_page: page_zero.sampled(),
+            u_color_transform: self.color_transform
+        };
+        scratch_frame
+            .as_surface()
+            .draw(
+                &self.screen_vertex_buffer,
+                gpu_index_buffer,
+                &self.frame_program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+
+        let uniforms = glium::uniform! {
+            u_previous: self.palette.sampled(),
+            u_current: self.palette.sampled(),
+            u_weight: 0.0f32,
+        };
+        scratch_frame
+            .as_surface()
+            .draw(
+                &self.screen_vertex_buffer,
+                gpu_index_buffer,
+                &self.blend_program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+
+        let point = OverlayPoint {
+            position: (0.0, 0.0),
+            color: (0.0, 0.0, 0.0),
+        };
+        let vertex_buffer = VertexBuffer::new(&self.display, &[point; 3]).unwrap();
+        scratch_frame
+            .as_surface()
+            .draw(
+                &vertex_buffer,
+                gpu_index_buffer,
+                &self.overlay_program,
+                &glium::uniforms::EmptyUniforms,
+                &params,
+            )
+            .unwrap();
+
+        self.display.finish();
+        log::debug!("shader warm up took {:?}", start.elapsed());
+    }
+
+    pub fn set_palette_mode(&mut self, mode: PaletteMode) {
+        self.color_transform = mode.color_transform();
+    }
+
+    pub fn toggle_wireframe(&mut self) {
+        self.polygon_mode = match self.polygon_mode {
+            PolygonMode::Filled => PolygonMode::Wireframe,
+            PolygonMode::Wireframe => PolygonMode::Filled,
+        };
+    }
+
+    /// Colors polygons by their position in the draw order instead of the palette, from
+    /// blue for the first polygon drawn since the last flush to red for the last. Only
+    /// affects polygons drawn after toggling.
+    pub fn toggle_depth_heatmap(&mut self) {
+        self.depth_heatmap = !self.depth_heatmap;
+    }
+
+    pub fn toggle_thread_overlay(&mut self) {
+        self.thread_overlay = !self.thread_overlay;
+        self.request_redraw();
+    }
+
+    /// Shows the directions and action button the game saw on its last frame in the
+    /// bottom left corner, lit while held.
+    pub fn toggle_input_overlay(&mut self) {
+        self.input_overlay = !self.input_overlay;
+        self.request_redraw();
+    }
+
+    /// Shows all four game pages as quadrants of the window, page 0 top left through
+    /// page 3 bottom right, instead of only the output page.
+    pub fn toggle_page_grid(&mut self) {
+        self.page_grid = !self.page_grid;
+        self.request_redraw();
+    }
+
+    /// Blends each redraw between the previous and current game frames by how far the
+    /// display is into the current frame, smoothing 50Hz content on faster displays.
+    /// Redraws are then driven by the event loop instead of by blits, which delays each
+    /// new frame until the next redraw, see `present_latency`.
+    pub fn set_frame_blend(&mut self, enabled: bool) {
+        self.frame_blend = if enabled {
+            let (width, height) = self.page_dimensions;
+            Some(FrameBlend::new(&self.display, width, height))
+        } else {
+            None
+        };
+    }
+
+    /// Swaps the system cursor for a game resolution cursor sprite drawn over the frame.
+    pub fn set_mouse_mode(&mut self, enabled: bool) {
+        self.mouse_mode = enabled;
+        self.display
+            .gl_window()
+            .window()
+            .set_cursor_visible(!enabled);
+    }
+
+    /// Maps a position in window pixels to game pixels.
+    pub fn game_position(&self, x: f64, y: f64) -> (i16, i16) {
+        let (width, height) = self.display.get_framebuffer_dimensions();
+        let x = x * 320.0 / width as f64;
+        let y = y * 200.0 / height as f64;
+        (x.floor() as i16, y.floor() as i16)
+    }
+
+    /// See `Gfx::draw_cursor`, only shown in mouse mode.
+    pub fn set_cursor(&mut self, cursor: Option<(i16, i16)>) {
+        let mut state = lock(&self.state);
+        state.cursor = cursor;
+    }
+
+    /// Refills every page with `video::DEBUG_CLEAR_COLOR` so pages read before the game
+    /// fills them show as magenta, or with color 0 when disabled. Meant to be set before
+    /// the game starts, as it discards whatever the pages hold.
+    pub fn set_debug_clear(&mut self, enabled: bool) {
+        let clear = if enabled { video::DEBUG_CLEAR_COLOR } else { 0 };
+        let (width, height) = self.page_dimensions;
+        for page in self.pages.values_mut() {
+            *page = RenderPage::new(&self.display, width, height, clear);
+        }
+    }
+
+    pub fn overlay_handle(&self) -> OverlayHandle {
+        OverlayHandle {
+            state: self.state.clone(),
         }
     }
 
@@ -335,13 +744,11 @@ impl GlGfx {
                 &DrawParameters::default(),
             )
             .unwrap();
-        self.sync.notify();
     }
 
     pub fn copy(&mut self, src: Page, dest: Page, scroll: i16) {
         self.flush_draws();
         self.do_copy(GlPage::Game(src), GlPage::Game(dest), scroll);
-        self.sync.notify();
     }
 
     fn do_copy(&self, src: GlPage, dest: GlPage, scroll: i16) {
@@ -369,75 +776,259 @@ impl GlGfx {
             .unwrap();
     }
 
-    pub fn blit(&mut self, page: Page) {
+    /// Reads every page back at game resolution, see `Gfx::save_pages`.
+    pub fn save_pages(&mut self) -> PageSnapshot {
         self.flush_draws();
-        self.output_page = page;
-        self.redraw();
-        self.sync.notify();
+
+        let (width, height) = (PAGE_WIDTH as u32, PAGE_HEIGHT as u32);
+        let target = Texture2d::empty(&self.display, width, height).unwrap();
+        let gpu_index_buffer = glium::index::NoIndices(PrimitiveType::TrianglesList);
+        let mut snapshot = PageSnapshot::filled(0);
+
+        for page in Page::ALL {
+            let render_page = self.pages.get(&GlPage::Game(page)).unwrap();
+            let uniforms = glium::uniform! {
+                u_page: render_page.sampled(),
+            };
+            target
+                .as_surface()
+                .draw(
+                    &self.screen_vertex_buffer,
+                    gpu_index_buffer,
+                    &self.readback_program,
+                    &uniforms,
+                    &DrawParameters::default(),
+                )
+                .unwrap();
+
+            let image: RawImage2d<u8> = target.read();
+            // OpenGL reads bottom row first
+            let rows = image.data.chunks_exact(PAGE_WIDTH * 4).rev();
+            let pixels = rows.flat_map(|row| row.chunks_exact(4).map(|rgba| rgba[0]));
+            for (index, color) in snapshot.page_mut(page).iter_mut().zip(pixels) {
+                *index = color;
+            }
+        }
+
+        snapshot
     }
 
-    pub fn select(&mut self, page: Page) {
+    /// Replaces every page with a snapshot from `save_pages`, scaled up to the page size.
+    pub fn restore_pages(&mut self, snapshot: &PageSnapshot) {
         self.flush_draws();
-        self.active_page = page;
-        self.sync.notify();
+
+        let gpu_index_buffer = glium::index::NoIndices(PrimitiveType::TrianglesList);
+        for page in Page::ALL {
+            // OpenGL expects the bottom row first
+            let data: Vec<u8> = snapshot
+                .page(page)
+                .chunks_exact(PAGE_WIDTH)
+                .rev()
+                .flatten()
+                .copied()
+                .collect();
+            let source = UnsignedTexture2d::with_format(
+                &self.display,
+                RawImage2d {
+                    data: data.into(),
+                    width: PAGE_WIDTH as u32,
+                    height: PAGE_HEIGHT as u32,
+                    format: glium::texture::ClientFormat::U8,
+                },
+                UncompressedUintFormat::U8,
+                MipmapsOption::NoMipmap,
+            )
+            .unwrap();
+
+            let uniforms = glium::uniform! {
+                u_page: source
+                    .sampled()
+                    .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+                    .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
+                u_fill: 255u32,
+                u_scroll: 0i32
+            };
+
+            let dest_page = self.pages.get(&GlPage::Game(page)).unwrap();
+            let mut frame = dest_page.frame(&self.display);
+            frame.clear_depth(-1.0);
+            frame
+                .draw(
+                    &self.screen_vertex_buffer,
+                    gpu_index_buffer,
+                    &self.copy_program,
+                    &uniforms,
+                    &DrawParameters::default(),
+                )
+                .unwrap();
+        }
+    }
+
+    /// Reads the pages back into the shared state for `GlHandle::save_pages` to collect.
+    pub fn stash_pages(&mut self) {
+        let pages = self.save_pages();
+        lock(&self.state).saved_pages = Some(pages);
     }
 
-    pub fn string(&mut self, text: &'static str, color: u8, mut x: i16, mut y: i16) {
+    pub fn blit(&mut self, page: Page) {
         self.flush_draws();
-        self.text_buffer.clear();
+        self.output_page = page;
+        self.present_latency.blitted(Instant::now());
+        if self.frame_blend.is_some() {
+            self.resolve_blend_frame();
+        } else {
+            self.redraw();
+        }
 
-        let x_origin = x;
-        for c in text.bytes() {
-            if c == b'\n' {
-                x = x_origin;
-                y += 8;
-                continue;
+        if self.frame_dump.as_ref().is_some_and(|dump| !dump.is_full()) {
+            let rgba = self.capture_frame(DUMP_WIDTH, DUMP_HEIGHT);
+            if let Some(dump) = self.frame_dump.as_mut() {
+                dump.write(&rgba);
             }
+        }
+    }
 
-            let c = c - b' ';
+    /// Resolves the output page through the palette into the newest blend frame.
+    fn resolve_blend_frame(&mut self) {
+        let mut state = lock(&self.state);
+        self.palette.update(&mut state.palette);
 
-            let x_ind = (c % 10) * 8;
-            let y_ind = (c / 10) * 8;
+        let Some(blend) = self.frame_blend.as_mut() else {
+            return;
+        };
+        blend.blit();
+
+        let gpu_index_buffer = glium::index::NoIndices(PrimitiveType::TrianglesList);
+        let output_page = self.pages.get(&GlPage::Game(self.output_page)).unwrap();
+        let uniforms = glium::uniform! {
+            u_palette: self.palette.sampled(),
+            u_page: output_page.sampled(),
+            u_color_transform: self.color_transform
+        };
+
+        blend
+            .current
+            .as_surface()
+            .draw(
+                &self.screen_vertex_buffer,
+                gpu_index_buffer,
+                &self.frame_program,
+                &uniforms,
+                &DrawParameters::default(),
+            )
+            .unwrap();
+    }
+
+    pub fn set_frame_dump(&mut self, dump: Option<FrameDump>) {
+        self.frame_dump = dump;
+    }
+
+    /// Renders the output page through the palette at `width` by `height`, without
+    /// overlays, and returns it as RGBA rows from top to bottom.
+    pub fn capture_frame(&mut self, width: u32, height: u32) -> Vec<u8> {
+        self.flush_draws();
 
-            let x_ind = x_ind as f32 / 80.0;
-            let y_ind = y_ind as f32 / 80.0;
+        let mut state = lock(&self.state);
+        self.palette.update(&mut state.palette);
+
+        let target = Texture2d::empty(&self.display, width, height).unwrap();
+        let mut frame = target.as_surface();
 
-            let step = 8.0 / 80.0;
+        let gpu_index_buffer = glium::index::NoIndices(PrimitiveType::TrianglesList);
 
-            let x_pos = x as f32;
-            let y_pos = y as f32;
+        let output_page = self.pages.get(&GlPage::Game(self.output_page)).unwrap();
+        let uniforms = glium::uniform! {
+            u_palette: self.palette.sampled(),
+            u_page: output_page.sampled(),
+            u_font_atlas: self.font_texture.sampled(),
+            u_color_transform: self.color_transform
+        };
 
-            x += 8;
+        frame
+            .draw(
+                &self.screen_vertex_buffer,
+                gpu_index_buffer,
+                &self.frame_program,
+                &uniforms,
+                &DrawParameters::default(),
+            )
+            .unwrap();
 
-            self.text_buffer.push(TextPoint {
-                position: (x_pos, y_pos),
-                uv: (x_ind, y_ind),
-            });
+        let image: RawImage2d<u8> = target.read();
+        let row = width as usize * 4;
+        // OpenGL reads bottom row first
+        image
+            .data
+            .chunks_exact(row)
+            .rev()
+            .flatten()
+            .copied()
+            .collect()
+    }
 
-            self.text_buffer.push(TextPoint {
-                position: (x_pos, y_pos + 8.0),
-                uv: (x_ind, y_ind + step),
-            });
+    pub fn select(&mut self, page: Page) {
+        self.flush_draws();
+        self.active_page = page;
+    }
 
-            self.text_buffer.push(TextPoint {
-                position: (x_pos + 8.0, y_pos),
-                uv: (x_ind + step, y_ind),
-            });
+    pub fn set_palette(&mut self, palette: [(u8, u8, u8); 16]) {
+        let mut state = lock(&self.state);
+        state.palette = Some(palette);
+    }
 
-            self.text_buffer.push(TextPoint {
-                position: (x_pos + 8.0, y_pos + 8.0),
-                uv: (x_ind + step, y_ind + step),
-            });
+    /// Replaces the font atlas, the texture is rebuilt before the next string is drawn.
+    pub fn set_font(&mut self, font: &FontBitmap) {
+        let mut state = lock(&self.state);
+        state.font = Some(Box::new(*font));
+    }
 
-            self.text_buffer.push(TextPoint {
-                position: (x_pos, y_pos + 8.0),
-                uv: (x_ind, y_ind + step),
-            });
+    pub fn sync(&self) {
+        self.sync.notify();
+    }
 
-            self.text_buffer.push(TextPoint {
-                position: (x_pos + 8.0, y_pos),
-                uv: (x_ind + step, y_ind),
-            });
+    /// Carries out an event sent by a `GlHandle`.
+    pub fn apply(&mut self, event: UserEvent) {
+        match event {
+            UserEvent::Blit(page) => {
+                self.blit(page);
+                self.request_redraw();
+            }
+            UserEvent::Fill(page, color) => self.fill(page, color),
+            UserEvent::Copy(src, dest, scroll) => self.copy(src, dest, scroll),
+            UserEvent::Select(page) => self.select(page),
+            UserEvent::String(text, color, x, y) => self.string(text, color, x, y),
+            UserEvent::SavePages => self.stash_pages(),
+            UserEvent::RestorePages(pages) => self.restore_pages(&pages),
+            UserEvent::Frame(frame) => {
+                let mut polygons = frame.polygons.into_iter();
+                for (queued, event) in frame.commands {
+                    lock(&self.state)
+                        .polygons
+                        .extend(polygons.by_ref().take(queued));
+                    self.apply(event);
+                }
+                lock(&self.state).polygons.extend(polygons);
+            }
+        }
+    }
+
+    /// Waits for the GPU to finish everything drawn so far.
+    pub fn finish(&self) {
+        self.display.finish();
+    }
+
+    pub fn string(&mut self, text: &str, color: u8, x: i16, y: i16) {
+        self.flush_draws();
+        self.text_buffer.clear();
+
+        if let Some(font) = lock(&self.state).font.take() {
+            self.font_texture = create_font(&self.display, &font);
+        }
+
+        for glyph in engine::font::layout_text(text, x, y) {
+            for (position, uv) in glyph.vertices() {
+                self.text_buffer.push(TextPoint { position, uv });
+            }
         }
 
         let gpu_vertex_buffer = VertexBuffer::new(&self.display, &self.text_buffer).unwrap();
@@ -459,68 +1050,34 @@ impl GlGfx {
                 &DrawParameters::default(),
             )
             .unwrap();
-        self.sync.notify();
     }
 
     fn flush_draws(&mut self) {
-        let mut state = self.state.lock().unwrap();
-
+        let mut state = lock(&self.state);
         let poly_count = state.polygons.len();
-        let mut current_poly = 0;
-        let mut pending_polys;
-        let mut special;
-
-        let fill_options = FillOptions::default();
-
-        while current_poly < poly_count {
-            pending_polys = 0;
-            special = false;
-            while current_poly < poly_count {
-                if let Some(poly) = state.polygons.get(current_poly) {
-                    let (color, mask) = match poly.blend {
-                        BlendMode::Solid(col) => (col & 0xf, 0),
-                        BlendMode::Mask(mask) if pending_polys == 0 => {
-                            special = true;
-                            (0, mask)
-                        }
-                        BlendMode::Mask(_) => break,
-                        BlendMode::Blend if pending_polys == 0 => {
-                            special = true;
-                            (0xff, 0)
-                        }
-                        BlendMode::Blend => break,
-                    };
-                    let mut points = poly
-                        .points()
-                        .map(|(x, y)| lyon::math::point(x as f32, y as f32));
-
-                    if let Some(first) = points.next() {
-                        let mut buffer_builder = BuffersBuilder::new(
-                            &mut self.tessellate_buffer,
-                            |vertex: FillVertex| PolyPoint {
-                                position: vertex.position().to_tuple(),
-                                color,
-                                depth: current_poly as u16,
-                                mask,
-                            },
-                        );
-
-                        let mut builder =
-                            self.tessellator.builder(&fill_options, &mut buffer_builder);
-
-                        builder.begin(first);
-                        for point in points {
-                            builder.line_to(point);
-                        }
-                        builder.close();
 
-                        let _ = builder.build().unwrap();
+        for (batch, snapshot) in video::draw_batches(&state.polygons) {
+            for (current_poly, poly) in batch.clone().zip(&state.polygons[batch]) {
+                let (color, mask) = match poly.blend {
+                    BlendMode::Solid(col) => (col & 0xf, 0),
+                    BlendMode::Mask(mask) => (0, mask),
+                    BlendMode::Blend => (0xff, 0),
+                };
+                let depth = current_poly as u16;
+                let vertex = |position| PolyPoint {
+                    position,
+                    color,
+                    depth,
+                    mask,
+                };
+                match self.polygon_mode {
+                    PolygonMode::Filled => {
+                        self.tessellator
+                            .fill(poly, &mut self.tessellate_buffer, vertex)
                     }
-                    pending_polys += 1;
-                    current_poly += 1;
-
-                    if special {
-                        break;
+                    PolygonMode::Wireframe => {
+                        self.tessellator
+                            .stroke(poly, &mut self.tessellate_buffer, vertex)
                     }
                 }
             }
@@ -541,13 +1098,16 @@ impl GlGfx {
             let page_self = self.pages.get(&GlPage::Current).unwrap();
             let page_zero = self.pages.get(&GlPage::Zero).unwrap();
 
-            if special {
+            if snapshot.active_page {
                 self.do_copy(GlPage::Game(self.active_page), GlPage::Current, 0);
+            }
+            if snapshot.page_zero {
                 self.do_copy(GlPage::Game(Page::Zero), GlPage::Zero, 0);
             }
 
             let uniforms = glium::uniform! {
                 u_max_depth: poly_count as u32 + 1,
+                u_depth_heatmap: self.depth_heatmap,
                 u_page_zero: page_zero.sampled(),
                 u_page_self: page_self.sampled(),
             };
@@ -581,7 +1141,7 @@ impl GlGfx {
     pub fn redraw(&mut self) {
         self.flush_draws();
 
-        let mut state = self.state.lock().unwrap();
+        let mut state = lock(&self.state);
         self.palette.update(&mut state.palette);
 
         let mut frame = self.display.draw();
@@ -589,24 +1149,188 @@ impl GlGfx {
 
         let gpu_index_buffer = glium::index::NoIndices(PrimitiveType::TrianglesList);
 
-        let output_page = self.pages.get(&GlPage::Game(self.output_page)).unwrap();
-        let uniforms = glium::uniform! {
-            u_palette: self.palette.sampled(),
-            u_page: output_page.sampled(),
-            u_font_atlas: self.font_texture.sampled()
+        let (width, height) = self.display.get_framebuffer_dimensions();
+        let views = if self.page_grid {
+            let (half_width, half_height) = (width / 2, height / 2);
+            let quadrant = |left, bottom| Rect {
+                left,
+                bottom,
+                width: half_width,
+                height: half_height,
+            };
+            vec![
+                (Page::Zero, Some(quadrant(0, half_height))),
+                (Page::One, Some(quadrant(half_width, half_height))),
+                (Page::Two, Some(quadrant(0, 0))),
+                (Page::Three, Some(quadrant(half_width, 0))),
+            ]
+        } else {
+            vec![(self.output_page, None)]
         };
 
-        frame
-            .draw(
-                &self.screen_vertex_buffer,
-                &gpu_index_buffer,
-                &self.frame_program,
-                &uniforms,
-                &DrawParameters::default(),
-            )
-            .unwrap();
+        if let (Some(blend), false) = (self.frame_blend.as_ref(), self.page_grid) {
+            let uniforms = glium::uniform! {
+                u_previous: blend.previous.sampled(),
+                u_current: blend.current.sampled(),
+                u_weight: blend.weight(),
+            };
+
+            frame
+                .draw(
+                    &self.screen_vertex_buffer,
+                    gpu_index_buffer,
+                    &self.blend_program,
+                    &uniforms,
+                    &DrawParameters::default(),
+                )
+                .unwrap();
+        } else {
+            for (page, viewport) in views {
+                let page = self.pages.get(&GlPage::Game(page)).unwrap();
+                let uniforms = glium::uniform! {
+                    u_palette: self.palette.sampled(),
+                    u_page: page.sampled(),
+                    u_font_atlas: self.font_texture.sampled(),
+                    u_color_transform: self.color_transform
+                };
+
+                frame
+                    .draw(
+                        &self.screen_vertex_buffer,
+                        gpu_index_buffer,
+                        &self.frame_program,
+                        &uniforms,
+                        &DrawParameters {
+                            viewport,
+                            ..Default::default()
+                        },
+                    )
+                    .unwrap();
+            }
+        }
+
+        self.overlay_buffer.clear();
+
+        if self.thread_overlay {
+            for (n, activity) in state.thread_activity.iter().enumerate() {
+                let color = match activity {
+                    ThreadActivity::Inactive => (0.1, 0.1, 0.1),
+                    ThreadActivity::Paused => (0.5, 0.5, 0.5),
+                    ThreadActivity::Ran => (0.0, 0.8, 0.0),
+                    ThreadActivity::Yielded => (0.9, 0.8, 0.0),
+                    ThreadActivity::Killed => (0.8, 0.0, 0.0),
+                };
+
+                let x = 4.0 + (n % 8) as f32 * 8.0;
+                let y = 4.0 + (n / 8) as f32 * 8.0;
+                push_overlay_rect(&mut self.overlay_buffer, (width, height), x, y, 7.0, color);
+            }
+        }
+
+        if self.input_overlay {
+            let input = state.input;
+            // a d-pad with the action button to its right, in 8px cells from the bottom left
+            let buttons = [
+                (1.0, 0.0, input.up),
+                (0.0, 1.0, input.left),
+                (2.0, 1.0, input.right),
+                (1.0, 2.0, input.down),
+                (4.0, 1.0, input.action),
+            ];
+
+            for (column, row, held) in buttons {
+                let color = if held {
+                    (0.9, 0.9, 0.9)
+                } else {
+                    (0.2, 0.2, 0.2)
+                };
+                let x = 4.0 + column * 8.0;
+                let y = height as f32 - 28.0 + row * 8.0;
+                push_overlay_rect(&mut self.overlay_buffer, (width, height), x, y, 7.0, color);
+            }
+        }
+
+        if let (true, Some(cursor)) = (self.mouse_mode, state.cursor) {
+            let (x, y, pixel) = cursor_origin(cursor, (width, height));
+            // a drop shadow one game pixel down and right, then the arrow itself
+            for (offset, color) in [(pixel, (0.0, 0.0, 0.0)), (0.0, (1.0, 1.0, 1.0))] {
+                for (row, bits) in CURSOR.iter().enumerate() {
+                    for column in (0..8).filter(|column| bits & (0x80 >> column) != 0) {
+                        let x = x + offset + column as f32 * pixel;
+                        let y = y + offset + row as f32 * pixel;
+                        push_overlay_rect(
+                            &mut self.overlay_buffer,
+                            (width, height),
+                            x,
+                            y,
+                            pixel,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+
+        if !self.overlay_buffer.is_empty() {
+            let gpu_vertex_buffer = VertexBuffer::new(&self.display, &self.overlay_buffer).unwrap();
+            frame
+                .draw(
+                    &gpu_vertex_buffer,
+                    gpu_index_buffer,
+                    &self.overlay_program,
+                    &glium::uniforms::EmptyUniforms,
+                    &DrawParameters::default(),
+                )
+                .unwrap();
+        }
 
         frame.finish().unwrap();
+        self.present_latency.presented(Instant::now());
+    }
+
+    /// See `PresentLatency`, measured up to when the finished frame is handed to the
+    /// swap chain.
+    pub fn present_latency(&self) -> PresentLatency {
+        self.present_latency
+    }
+}
+
+/// An arrow pointing up and left from its top left pixel, one byte per row.
+const CURSOR: [u8; 8] = [0x80, 0xc0, 0xe0, 0xf0, 0xf8, 0xe0, 0xb0, 0x18];
+
+/// The framebuffer position of the cursor's top left pixel and the size of one game
+/// pixel, for a cursor at `cursor` in game pixels.
+fn cursor_origin(cursor: (i16, i16), (width, height): (u32, u32)) -> (f32, f32, f32) {
+    let scale_x = width as f32 / 320.0;
+    let scale_y = height as f32 / 200.0;
+    let x = cursor.0 as f32 * scale_x;
+    let y = cursor.1 as f32 * scale_y;
+
+    (x, y, scale_x.min(scale_y))
+}
+
+fn push_overlay_rect(
+    buffer: &mut Vec<OverlayPoint>,
+    (width, height): (u32, u32),
+    x: f32,
+    y: f32,
+    size: f32,
+    color: (f32, f32, f32),
+) {
+    let left = x / width as f32 * 2.0 - 1.0;
+    let right = (x + size) / width as f32 * 2.0 - 1.0;
+    let top = 1.0 - y / height as f32 * 2.0;
+    let bottom = 1.0 - (y + size) / height as f32 * 2.0;
+
+    for position in [
+        (left, top),
+        (left, bottom),
+        (right, top),
+        (right, bottom),
+        (left, bottom),
+        (right, top),
+    ] {
+        buffer.push(OverlayPoint { position, color });
     }
 }
 
@@ -633,45 +1357,220 @@ struct TextPoint {
 }
 glium::implement_vertex!(TextPoint, position, uv);
 
+#[derive(Copy, Clone)]
+struct OverlayPoint {
+    position: (f32, f32),
+    color: (f32, f32, f32),
+}
+glium::implement_vertex!(OverlayPoint, position, color);
+
+/// Passes the executor's thread activity and input to the debug overlays.
+pub struct OverlayHandle {
+    state: Arc<Mutex<GfxState>>,
+}
+
+impl OverlayHandle {
+    pub fn update(&self, activity: &[ThreadActivity; 64], input: InputState) {
+        let mut state = lock(&self.state);
+        state.thread_activity = *activity;
+        state.input = input;
+    }
+}
+
 pub struct GlHandle {
     state: Arc<Mutex<GfxState>>,
     sync: Arc<Sync>,
     proxy: EventLoopProxy<UserEvent>,
 }
 
+impl GlHandle {
+    fn send(&self, event: UserEvent) {
+        let ticket = self.sync.request();
+        if self.proxy.send_event(event).is_ok() {
+            self.sync.wait(ticket);
+        }
+    }
+
+    /// Hands over a whole frame of drawing at once, only waiting while more than
+    /// `in_flight` earlier frames are still being drawn. This is the buffered hand-off
+    /// `syncbench` compares against sending each command on its own.
+    pub fn send_frame(&self, frame: Frame, in_flight: u64) {
+        let ticket = self.sync.request();
+        if self.proxy.send_event(UserEvent::Frame(frame)).is_ok() {
+            self.sync.wait(ticket.saturating_sub(in_flight));
+        }
+    }
+}
+
+/// Drawing recorded for `GlHandle::send_frame`. Each command notes how many of the
+/// polygons were drawn between it and the command before.
+#[derive(Default)]
+pub struct Frame {
+    pub polygons: Vec<Polygon>,
+    pub commands: Vec<(usize, UserEvent)>,
+}
+
 impl Gfx for GlHandle {
     fn blit(&mut self, page: Page) {
-        let _ = self.proxy.send_event(UserEvent::Blit(page));
-        self.sync.wait();
+        self.send(UserEvent::Blit(page));
     }
 
     fn draw_polygon(&mut self, polygon: Polygon) {
-        let mut state = self.state.lock().unwrap();
+        let mut state = lock(&self.state);
         state.polygons.push(polygon);
     }
 
     fn fill_page(&mut self, page: Page, color: u8) {
-        let _ = self.proxy.send_event(UserEvent::Fill(page, color));
-        self.sync.wait();
+        self.send(UserEvent::Fill(page, color));
     }
 
     fn copy_page(&mut self, src: Page, dest: Page, scroll: i16) {
-        let _ = self.proxy.send_event(UserEvent::Copy(src, dest, scroll));
-        self.sync.wait();
+        self.send(UserEvent::Copy(src, dest, scroll));
     }
 
     fn set_palette(&mut self, palette: [(u8, u8, u8); 16]) {
-        let mut state = self.state.lock().unwrap();
+        let mut state = lock(&self.state);
         state.palette = Some(palette);
     }
 
     fn select_page(&mut self, page: Page) {
-        let _ = self.proxy.send_event(UserEvent::Select(page));
-        self.sync.wait();
+        self.send(UserEvent::Select(page));
     }
 
     fn draw_string(&mut self, text: &'static str, color: u8, x: i16, y: i16) {
-        let _ = self.proxy.send_event(UserEvent::String(text, color, x, y));
-        self.sync.wait();
+        self.send(UserEvent::String(text, color, x, y));
+    }
+
+    fn set_font(&mut self, font: &FontBitmap) {
+        let mut state = lock(&self.state);
+        state.font = Some(Box::new(*font));
+    }
+
+    fn draw_cursor(&mut self, cursor: Option<(i16, i16)>) {
+        let mut state = lock(&self.state);
+        state.cursor = cursor;
+    }
+
+    fn save_pages(&mut self) -> Option<PageSnapshot> {
+        self.send(UserEvent::SavePages);
+        lock(&self.state).saved_pages.take()
+    }
+
+    fn restore_pages(&mut self, pages: &PageSnapshot) {
+        self.send(UserEvent::RestorePages(Box::new(pages.clone())));
+    }
+}
+
+/// Draws straight into a `GlGfx` owned by the event loop, for running the executor on the
+/// render thread without the proxy and sync handshake.
+pub struct LocalGlHandle {
+    gfx: Rc<RefCell<GlGfx>>,
+}
+
+impl LocalGlHandle {
+    pub fn new(gfx: Rc<RefCell<GlGfx>>) -> Self {
+        LocalGlHandle { gfx }
+    }
+}
+
+impl Gfx for LocalGlHandle {
+    fn blit(&mut self, page: Page) {
+        self.gfx.borrow_mut().blit(page);
+    }
+
+    fn draw_polygon(&mut self, polygon: Polygon) {
+        let gfx = self.gfx.borrow();
+        let mut state = lock(&gfx.state);
+        state.polygons.push(polygon);
+    }
+
+    fn fill_page(&mut self, page: Page, color: u8) {
+        self.gfx.borrow_mut().fill(page, color);
+    }
+
+    fn copy_page(&mut self, src: Page, dest: Page, scroll: i16) {
+        self.gfx.borrow_mut().copy(src, dest, scroll);
+    }
+
+    fn set_palette(&mut self, palette: [(u8, u8, u8); 16]) {
+        self.gfx.borrow_mut().set_palette(palette);
+    }
+
+    fn select_page(&mut self, page: Page) {
+        self.gfx.borrow_mut().select(page);
+    }
+
+    fn draw_string(&mut self, text: &'static str, color: u8, x: i16, y: i16) {
+        self.gfx.borrow_mut().string(text, color, x, y);
+    }
+
+    fn set_font(&mut self, font: &FontBitmap) {
+        self.gfx.borrow_mut().set_font(font);
+    }
+
+    fn draw_cursor(&mut self, cursor: Option<(i16, i16)>) {
+        self.gfx.borrow_mut().set_cursor(cursor);
+    }
+
+    fn save_pages(&mut self) -> Option<PageSnapshot> {
+        Some(self.gfx.borrow_mut().save_pages())
+    }
+
+    fn restore_pages(&mut self, pages: &PageSnapshot) {
+        self.gfx.borrow_mut().restore_pages(pages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_clear_fills_blank_pages() {
+        let page = blank_page(4, 2, video::DEBUG_CLEAR_COLOR);
+        assert_eq!((page.width, page.height), (4, 2));
+        assert_eq!(&page.data[..], &[0xff; 8]);
+
+        let page = blank_page(320, 200, 0);
+        assert!(page.data.iter().all(|&index| index == 0));
+    }
+
+    #[test]
+    fn present_latency_counts_each_blit_once() {
+        let start = Instant::now();
+        let mut latency = PresentLatency::default();
+
+        latency.presented(start);
+        latency.blitted(start);
+        latency.blitted(start + Duration::from_millis(2));
+        latency.presented(start + Duration::from_millis(6));
+        latency.presented(start + Duration::from_millis(20));
+        latency.blitted(start + Duration::from_millis(20));
+        latency.presented(start + Duration::from_millis(22));
+
+        assert_eq!(latency.frames(), 2);
+        assert_eq!(latency.average(), Duration::from_millis(3));
+        assert_eq!(latency.max(), Duration::from_millis(4));
+    }
+
+    #[test]
+    fn cursor_follows_window_scale() {
+        assert_eq!(cursor_origin((0, 0), (320, 200)), (0.0, 0.0, 1.0));
+        assert_eq!(cursor_origin((10, 20), (960, 600)), (30.0, 60.0, 3.0));
+        assert_eq!(cursor_origin((319, 199), (640, 400)), (638.0, 398.0, 2.0));
+
+        let mut buffer = Vec::new();
+        let (x, y, pixel) = cursor_origin((100, 50), (960, 600));
+        push_overlay_rect(&mut buffer, (960, 600), x, y, pixel, (1.0, 1.0, 1.0));
+        let (left, top) = buffer[0].position;
+        let (right, bottom) = buffer[3].position;
+        assert_eq!(
+            (left, top),
+            (300.0 / 960.0 * 2.0 - 1.0, 1.0 - 150.0 / 600.0 * 2.0)
+        );
+        assert_eq!(
+            (right, bottom),
+            (303.0 / 960.0 * 2.0 - 1.0, 1.0 - 153.0 / 600.0 * 2.0)
+        );
     }
 }
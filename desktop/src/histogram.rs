@@ -0,0 +1,93 @@
+use engine::resources::GamePart;
+
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// Exclusive upper bound in ms of each bucket, slower frames go in a final overflow bucket.
+const BUCKET_BOUNDS_MS: [u64; 8] = [1, 2, 4, 8, 16, 33, 50, 100];
+const BUCKET_COUNT: usize = BUCKET_BOUNDS_MS.len() + 1;
+
+/// Where `F6` writes the histogram when `--frame-histogram` is not given.
+pub const DEFAULT_HISTOGRAM_PATH: &str = "frame-histogram.csv";
+
+/// Counts of frame execution times in latency buckets, kept separately for each part so
+/// consistently heavy scenes stand out.
+#[derive(Debug, Default, Clone)]
+pub struct FrameHistogram {
+    parts: Vec<(Option<GamePart>, [u64; BUCKET_COUNT])>,
+}
+
+impl FrameHistogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, part: Option<GamePart>, duration: Duration) {
+        let ms = duration.as_millis();
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|&bound| ms < bound as u128)
+            .unwrap_or(BUCKET_COUNT - 1);
+
+        let counts = match self.parts.iter().position(|(p, _)| *p == part) {
+            Some(idx) => &mut self.parts[idx].1,
+            None => {
+                self.parts.push((part, [0; BUCKET_COUNT]));
+                &mut self.parts.last_mut().unwrap().1
+            }
+        };
+        counts[bucket] += 1;
+    }
+
+    /// One row per part in the order they were first seen, with a column per bucket.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("part");
+        let mut lower = 0;
+        for bound in BUCKET_BOUNDS_MS {
+            let _ = write!(csv, ",{}-{}ms", lower, bound);
+            lower = bound;
+        }
+        let _ = writeln!(csv, ",{}ms+", lower);
+
+        for (part, counts) in &self.parts {
+            match part {
+                Some(part) => {
+                    let _ = write!(csv, "{:?}", part);
+                }
+                None => csv.push_str("none"),
+            }
+            for count in counts {
+                let _ = write!(csv, ",{}", count);
+            }
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_csv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_frame_times_by_part() {
+        let mut histogram = FrameHistogram::new();
+        for ms in [0, 1, 3, 16, 20, 20, 99, 100, 250] {
+            histogram.record(Some(GamePart::Two), Duration::from_millis(ms));
+        }
+        histogram.record(Some(GamePart::Three), Duration::from_micros(500));
+
+        assert_eq!(
+            histogram.to_csv(),
+            "part,0-1ms,1-2ms,2-4ms,4-8ms,8-16ms,16-33ms,33-50ms,50-100ms,100ms+\n\
+             Two,1,1,1,0,0,3,0,1,2\n\
+             Three,1,0,0,0,0,0,0,0,0\n"
+        );
+    }
+}
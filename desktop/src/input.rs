@@ -1,27 +1,57 @@
 use winit::event::{ElementState, VirtualKeyCode};
 
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use engine::input::{Input, InputState};
+use engine::input::{Input, InputBuffer, InputState};
+
+use super::lock::lock;
+
+/// Rows the free camera pans per PageUp/PageDown press or key repeat.
+const CAMERA_STEP: i16 = 8;
+
+#[derive(Debug, Clone)]
+struct KeyState {
+    held: InputState,
+    /// Whether P is down, so key repeats don't toggle pause again.
+    pause_key: bool,
+    buffer: InputBuffer,
+    free_camera: bool,
+    camera: Option<i16>,
+}
 
 pub struct WinitInput {
-    state: Arc<Mutex<InputState>>,
+    state: Arc<Mutex<KeyState>>,
 }
 
 impl WinitInput {
     pub fn new() -> Self {
         WinitInput {
-            state: Arc::new(Mutex::new(InputState {
-                up: false,
-                left: false,
-                right: false,
-                down: false,
-                action: false,
-                turbo: false,
+            state: Arc::new(Mutex::new(KeyState {
+                held: InputState::default(),
+                pause_key: false,
+                buffer: InputBuffer::new(Duration::ZERO),
+                free_camera: false,
+                camera: None,
             })),
         }
     }
 
+    /// How long a key tapped between frames is held for the next frame, zero disables.
+    pub fn set_buffer_window(&self, window: Duration) {
+        lock(&self.state).buffer.set_window(window);
+    }
+
+    /// Lets PageUp and PageDown pan scrolling scenes, starting from no scroll, and Home
+    /// hand the scroll back to the scripts. Disabling also hands it back.
+    pub fn set_free_camera(&self, enabled: bool) {
+        let mut state = lock(&self.state);
+        state.free_camera = enabled;
+        if !enabled {
+            state.camera = None;
+        }
+    }
+
     pub fn handle(&self) -> WinitInputHandle {
         WinitInputHandle {
             state: self.state.clone(),
@@ -30,7 +60,14 @@ impl WinitInput {
 
     pub fn process_event(&self, event: winit::event::KeyboardInput) {
         if let Some(key) = event.virtual_keycode {
-            let mut state = self.state.lock().unwrap();
+            let mut guard = lock(&self.state);
+            let KeyState {
+                held: state,
+                pause_key,
+                buffer,
+                free_camera,
+                camera,
+            } = &mut *guard;
             let pressed = event.state == ElementState::Pressed;
             match key {
                 VirtualKeyCode::Up | VirtualKeyCode::W => state.up = pressed,
@@ -39,19 +76,96 @@ impl WinitInput {
                 VirtualKeyCode::Right | VirtualKeyCode::D => state.right = pressed,
                 VirtualKeyCode::Space | VirtualKeyCode::Return => state.action = pressed,
                 VirtualKeyCode::LShift | VirtualKeyCode::RShift => state.turbo = pressed,
+                VirtualKeyCode::Back => state.rewind = pressed,
+                VirtualKeyCode::P => {
+                    if pressed && !*pause_key {
+                        state.pause = !state.pause;
+                    }
+                    *pause_key = pressed;
+                }
+                VirtualKeyCode::PageUp if *free_camera && pressed => pan(camera, -CAMERA_STEP),
+                VirtualKeyCode::PageDown if *free_camera && pressed => pan(camera, CAMERA_STEP),
+                VirtualKeyCode::Home if *free_camera && pressed => *camera = None,
                 _ => (),
             }
+
+            if pressed {
+                buffer.press(*state, Instant::now());
+            }
         }
     }
 }
 
 pub struct WinitInputHandle {
-    state: Arc<Mutex<InputState>>,
+    state: Arc<Mutex<KeyState>>,
+}
+
+impl WinitInputHandle {
+    /// The keys held right now, without taking taps buffered for the next frame the way
+    /// `get_input` does.
+    pub fn held(&self) -> InputState {
+        lock(&self.state).held
+    }
+
+    /// The free camera's scroll, `None` while the scripts control it.
+    pub fn scroll_override(&self) -> Option<i16> {
+        lock(&self.state).camera
+    }
 }
 
 impl Input for WinitInputHandle {
     fn get_input(&self) -> InputState {
-        let input = self.state.lock().unwrap();
-        *input
+        let mut state = lock(&self.state);
+        let held = state.held;
+        state.buffer.take(held, Instant::now())
+    }
+}
+
+fn pan(camera: &mut Option<i16>, step: i16) {
+    let scroll = camera.unwrap_or(0) + step;
+    *camera = Some(scroll.clamp(-199, 199));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(deprecated)]
+    fn key(key: VirtualKeyCode, state: ElementState) -> winit::event::KeyboardInput {
+        winit::event::KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn checking_held_keys_keeps_buffered_taps() {
+        let input = WinitInput::new();
+        input.set_buffer_window(Duration::from_secs(60));
+        let handle = input.handle();
+
+        input.process_event(key(VirtualKeyCode::Space, ElementState::Pressed));
+        input.process_event(key(VirtualKeyCode::Space, ElementState::Released));
+
+        assert!(!handle.held().action);
+        assert!(!handle.held().rewind);
+        assert!(handle.get_input().action);
+        assert!(!handle.get_input().action);
+    }
+
+    #[test]
+    fn pause_toggles_once_per_press() {
+        let input = WinitInput::new();
+        let handle = input.handle();
+
+        input.process_event(key(VirtualKeyCode::P, ElementState::Pressed));
+        input.process_event(key(VirtualKeyCode::P, ElementState::Pressed));
+        assert!(handle.held().pause);
+
+        input.process_event(key(VirtualKeyCode::P, ElementState::Released));
+        input.process_event(key(VirtualKeyCode::P, ElementState::Pressed));
+        assert!(!handle.held().pause);
     }
 }
@@ -0,0 +1,34 @@
+//! Locking that keeps going after a panic on another thread.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Locks `mutex`, using the state as it was left when a thread panicked while holding it.
+/// The state shared between the game, render and input threads is always left usable, so
+/// one thread's panic shouldn't also take down the thread trying to shut down cleanly.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn poisoned_lock_is_still_usable() {
+        let mutex = Arc::new(Mutex::new(1));
+        let poisoner = mutex.clone();
+        let result = std::thread::spawn(move || {
+            let mut value = poisoner.lock().unwrap();
+            *value = 2;
+            panic!("poisoning the lock");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+
+        *lock(&mutex) += 1;
+        assert_eq!(*lock(&mutex), 3);
+    }
+}
@@ -3,24 +3,55 @@ use glium::{
     glutin::{Api, GlRequest},
 };
 use winit::{
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
 };
 
+use engine::error::Error;
+use engine::font::FontBitmap;
+use engine::gfx::PageSnapshot;
+use engine::input::CompositeInput;
 use engine::video::Page;
+use engine::EngineOptions;
 use engine::Executor;
-use engine::Input;
+use engine::Gfx;
 
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod benchmark;
+mod capture;
+mod desync;
 mod directory;
+mod dump;
+mod export;
 mod gfx;
+mod histogram;
 mod input;
+mod lock;
+mod menu;
+mod scene;
+mod selftest;
 mod shaders;
+mod swatch;
+mod syncbench;
 
 use directory::DirectoryIo;
-use gfx::GlGfx;
-use input::WinitInput;
+use gfx::{Frame, GlGfx, LocalGlHandle, OverlayHandle, RenderMode};
+use histogram::FrameHistogram;
+use input::{WinitInput, WinitInputHandle};
+use lock::lock;
+use menu::Menu;
 
-const BYPASS_COPY_PROTECTION: bool = true;
+/// OpenGL versions to request, in order of preference.
+const GL_VERSIONS: [(u8, u8); 2] = [(4, 2), (3, 3)];
+const REWIND_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+const REWIND_STEP_INTERVAL: Duration = Duration::from_millis(100);
+/// How often a paused game checks whether it has been unpaused.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub enum UserEvent {
     Blit(Page),
@@ -28,103 +59,612 @@ pub enum UserEvent {
     Fill(Page, u8),
     Select(Page),
     String(&'static str, u8, i16, i16),
+    SavePages,
+    RestorePages(Box<PageSnapshot>),
+    Frame(Frame),
 }
 
 fn main() {
-    let mut args = std::env::args();
-    let _ = args.next();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let (options, args) = EngineOptions::from_args(std::env::args().skip(1));
 
-    let mut game_path = None;
-    let mut scale = None;
+    let mut render_mode = RenderMode::Smooth;
+    let mut benchmark_frames = None;
+    let mut sync_benchmark_frames = None;
+    let mut selftest = false;
+    let mut export_sound = false;
+    let mut sound_index = None;
+    let mut sound_freq = None;
+    let mut out_path = None;
+    let mut dump_dir = None;
+    let mut dump_limit = dump::DEFAULT_DUMP_LIMIT;
+    let mut record_commands = false;
+    let mut record_frames = None;
+    let mut input_path = None;
+    let mut diff_run = None;
+    let mut export_scene = None;
+    let mut export_palette = None;
+    let mut capture = false;
+    let mut capture_frames = None;
+    let mut frame_blend = false;
+    let mut histogram_path = None;
+    let mut font_path = None;
+    let mut mouse = false;
+    let mut frontend = FrontendOptions::default();
+    let mut args = args.into_iter();
     while let Some(arg) = args.next() {
         match arg.as_str() {
-            "-d" | "--data-path" => game_path = args.next(),
-            "-s" | "--scale" => scale = args.next().and_then(|s| s.parse().ok()),
+            "-p" | "--pixel-accurate" => render_mode = RenderMode::PixelAccurate,
+            "-v" | "--verbose" => frontend.verbose = true,
+            "--single-thread" => frontend.single_threaded = true,
+            "--free-camera" => frontend.free_camera = true,
+            "--selftest" => selftest = true,
+            "--benchmark" => benchmark_frames = args.next().and_then(|s| s.parse().ok()),
+            "--sync-benchmark" => sync_benchmark_frames = args.next().and_then(|s| s.parse().ok()),
+            "export-sound" => export_sound = true,
+            "--index" => sound_index = args.next().and_then(|s| parse_index(&s)),
+            "--freq" => sound_freq = args.next().and_then(|s| s.parse().ok()),
+            "--out" => out_path = args.next(),
+            "record-commands" => record_commands = true,
+            "--frames" => record_frames = args.next().and_then(|s| s.parse().ok()),
+            "--input" => input_path = args.next(),
+            "capture" => capture = true,
+            "--frame" => capture_frames = args.next().and_then(|s| s.parse().ok()),
+            "--frame-blend" => frame_blend = true,
+            "--frame-histogram" => histogram_path = args.next(),
+            "--font" => font_path = args.next(),
+            "--mouse" => mouse = true,
+            "--export-scene" => export_scene = args.next().and_then(|s| s.parse().ok()),
+            "--export-palette" => export_palette = args.next().and_then(|s| s.parse().ok()),
+            "diff-run" => diff_run = args.next().zip(args.next()),
+            "--dump-frames" => dump_dir = args.next(),
+            "--dump-limit" => {
+                dump_limit = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(dump_limit)
+            }
             _ => (),
         }
     }
 
-    let event_loop: EventLoop<UserEvent> = EventLoop::with_user_event();
-    let window_builder = winit::window::WindowBuilder::new()
-        .with_title("Another World")
-        .with_inner_size(winit::dpi::PhysicalSize {
-            width: 320 * scale.unwrap_or(1),
-            height: 200 * scale.unwrap_or(1),
-        });
-    let context_builder = glutin::glutin::ContextBuilder::new()
-        .with_srgb(true)
-        .with_depth_buffer(16)
-        .with_gl(GlRequest::Specific(Api::OpenGl, (4, 2)))
-        .with_vsync(false);
-    let display = glium::Display::new(window_builder, context_builder, &event_loop)
-        .expect("unable to create OpenGL window");
-
-    let io = DirectoryIo::new(game_path.expect("--data-path is required"));
-
-    let mut gfx = GlGfx::new(display, &event_loop);
-    let gfx_handle = gfx.handle();
+    if export_sound {
+        let data_path = options
+            .data_path
+            .expect("export-sound requires --data-path");
+        let index = sound_index.expect("export-sound requires --index");
+        let out_path = out_path.expect("export-sound requires --out");
+        if let Err(err) =
+            export::export_sound(data_path, index, sound_freq.unwrap_or(0x1c), out_path)
+        {
+            eprintln!("unable to export sound: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let input = WinitInput::new();
-    let input_handle = input.handle();
-    let turbo_handle = input.handle();
+    if record_commands {
+        let data_path = options
+            .data_path
+            .clone()
+            .expect("record-commands requires --data-path");
+        let frames = record_frames.expect("record-commands requires --frames");
+        let out_path = out_path.expect("record-commands requires --out");
+        if let Err(err) =
+            desync::record_commands(data_path.into(), frames, input_path, out_path, &options)
+        {
+            eprintln!("unable to record commands: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let mut executor = Executor::new(io, gfx_handle, input_handle, BYPASS_COPY_PROTECTION);
-    let mut last_timestamp = std::time::Instant::now();
+    if let Some((a, b)) = diff_run {
+        match desync::diff_run(a, b) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("unable to compare runs: {}", err);
+                std::process::exit(2);
+            }
+        }
+    }
 
-    std::thread::spawn(move || loop {
-        let input = turbo_handle;
-        loop {
-            let input = input.get_input();
-            let sleep_ms = executor.run();
-            if sleep_ms > 0 {
-                let ms = if input.turbo {
-                    sleep_ms.min(1)
-                } else {
-                    sleep_ms
-                };
-                let elapsed = last_timestamp.elapsed();
-                let duration = std::time::Duration::from_millis(ms);
-                if duration > elapsed {
-                    std::thread::sleep(duration - elapsed);
-                } else if !input.turbo {
-                    eprintln!(
-                        "slow frame: {}ms {}ms",
-                        elapsed.as_millis(),
-                        duration.as_millis()
-                    )
-                }
-                last_timestamp = std::time::Instant::now();
+    if let Some(frames) = export_scene {
+        let data_path = options
+            .data_path
+            .clone()
+            .expect("--export-scene requires --data-path");
+        let out_path = out_path.expect("--export-scene requires --out");
+        if let Err(err) = scene::export_scene(data_path.into(), frames, out_path, &options) {
+            eprintln!("unable to export scene: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(frames) = export_palette {
+        let data_path = options
+            .data_path
+            .clone()
+            .expect("--export-palette requires --data-path");
+        let out_path = out_path.expect("--export-palette requires --out");
+        if let Err(err) = swatch::export_palette(data_path.into(), frames, out_path, &options) {
+            eprintln!("unable to export palette: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if capture {
+        let data_path = options
+            .data_path
+            .clone()
+            .expect("capture requires --data-path");
+        let frames = capture_frames.expect("capture requires --frame");
+        let out_path = out_path.expect("capture requires --out");
+
+        let event_loop: EventLoop<UserEvent> = EventLoop::with_user_event();
+        let display = match create_display(&event_loop, 1, false) {
+            Ok(display) => display,
+            Err(err) => {
+                eprintln!("unable to create OpenGL window: {}", err);
+                std::process::exit(1);
             }
+        };
+        display.gl_window().window().set_visible(false);
+
+        let mut gfx = GlGfx::new(display, &event_loop, render_mode);
+        gfx.set_palette_mode(options.palette_mode);
+        if let Err(err) = capture::capture(
+            data_path.into(),
+            frames,
+            input_path,
+            out_path,
+            gfx,
+            &options,
+        ) {
+            eprintln!("unable to capture frame: {}", err);
+            std::process::exit(1);
         }
-    });
+        return;
+    }
 
-    event_loop.run(move |event, _window, control_flow| match event {
-        Event::UserEvent(UserEvent::Blit(page)) => {
-            gfx.blit(page);
-            gfx.request_redraw();
+    if let Some(frames) = sync_benchmark_frames {
+        let mut event_loop: EventLoop<UserEvent> = EventLoop::with_user_event();
+        let display = match create_display(&event_loop, 1, false) {
+            Ok(display) => display,
+            Err(err) => {
+                eprintln!("unable to create OpenGL window: {}", err);
+                std::process::exit(1);
+            }
+        };
+        display.gl_window().window().set_visible(false);
+
+        let mut gfx = GlGfx::new(display, &event_loop, render_mode);
+        syncbench::run(frames, &mut event_loop, &mut gfx);
+        return;
+    }
+
+    if let Some(frames) = benchmark_frames {
+        let path = options
+            .data_path
+            .clone()
+            .expect("--benchmark requires --data-path");
+        benchmark::run(path.into(), frames, &options).expect("unable to load game data");
+        return;
+    }
+
+    if selftest {
+        let path = options
+            .data_path
+            .clone()
+            .expect("--selftest requires --data-path");
+        match selftest::run(path.into(), &options) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("unable to load game data: {}", err);
+                std::process::exit(1);
+            }
         }
-        Event::UserEvent(UserEvent::Fill(page, color)) => {
-            gfx.fill(page, color);
+    }
+
+    let scale = options.scale;
+
+    let event_loop: EventLoop<UserEvent> = EventLoop::with_user_event();
+    let display = match create_display(&event_loop, scale.unwrap_or(1), frame_blend) {
+        Ok(display) => display,
+        Err(err) => {
+            eprintln!("unable to create OpenGL window: {}", err);
+            eprintln!(
+                "an OpenGL 3.3 or newer context is required, check that your graphics drivers are up to date"
+            );
+            std::process::exit(1);
         }
-        Event::UserEvent(UserEvent::Copy(src, dest, scroll)) => {
-            gfx.copy(src, dest, scroll);
+    };
+
+    let mut gfx = GlGfx::new(display, &event_loop, render_mode);
+    gfx.set_palette_mode(options.palette_mode);
+    gfx.set_frame_blend(frame_blend);
+    gfx.set_mouse_mode(mouse);
+    gfx.set_debug_clear(options.debug_clear);
+    if let Some(path) = font_path {
+        match load_font(&path) {
+            Ok(font) => gfx.set_font(&font),
+            Err(err) => eprintln!("unable to load font {}: {}", path, err),
         }
-        Event::UserEvent(UserEvent::Select(page)) => {
-            gfx.select(page);
+    }
+    if let Some(dir) = dump_dir {
+        match dump::FrameDump::new(dir, dump_limit) {
+            Ok(dump) => gfx.set_frame_dump(Some(dump)),
+            Err(err) => eprintln!("unable to create frame dump directory: {}", err),
         }
-        Event::UserEvent(UserEvent::String(text, color, x, y)) => {
-            gfx.string(text, color, x, y);
+    }
+
+    let input = WinitInput::new();
+    input.set_buffer_window(Duration::from_millis(options.input_buffer_ms.unwrap_or(0)));
+    input.set_free_camera(frontend.free_camera);
+    let gfx = Rc::new(RefCell::new(gfx));
+    let histogram = Arc::new(Mutex::new(FrameHistogram::new()));
+
+    let mut game = None;
+    let mut menu = match options.data_path.clone() {
+        Some(path) => {
+            game = start_game(path.into(), &gfx, &input, &options, &frontend, &histogram)
+                .expect("unable to load game data");
+            None
+        }
+        None => {
+            let menu = Menu::new();
+            menu.draw(&mut gfx.borrow_mut());
+            Some(menu)
+        }
+    };
+    let mut next_step = Instant::now();
+
+    event_loop.run(move |event, _window, control_flow| match event {
+        Event::UserEvent(event) => {
+            let mut gfx = gfx.borrow_mut();
+            gfx.apply(event);
+            gfx.sync();
         }
+        Event::MainEventsCleared => {
+            if let Some(game) = game.as_mut() {
+                if Instant::now() >= next_step {
+                    next_step = Instant::now() + game.step();
+                }
+                *control_flow = ControlFlow::WaitUntil(next_step);
+            }
+            // Blended frames change on every display refresh, so redraw continuously and
+            // let vsync pace the loop. This is independent of `game`, which is only set
+            // when the game runs on this thread.
+            if frame_blend && menu.is_none() {
+                gfx.borrow().request_redraw();
+                *control_flow = ControlFlow::Poll;
+            }
+        }
+        Event::RedrawRequested(_) if frame_blend && menu.is_none() => gfx.borrow_mut().redraw(),
         Event::WindowEvent {
             event: WindowEvent::CloseRequested,
             ..
-        } => *control_flow = ControlFlow::Exit,
+        } => {
+            if let Some(path) = histogram_path.as_deref() {
+                export_histogram(&histogram, path);
+            }
+            let latency = gfx.borrow().present_latency();
+            log::info!(
+                "{} frames presented {:.2}ms after their blit on average, {:.2}ms at most",
+                latency.frames(),
+                latency.average().as_secs_f64() * 1000.0,
+                latency.max().as_secs_f64() * 1000.0
+            );
+            *control_flow = ControlFlow::Exit
+        }
+        Event::WindowEvent {
+            event: WindowEvent::CursorMoved { position, .. },
+            ..
+        } if mouse => {
+            let mut gfx = gfx.borrow_mut();
+            let cursor = gfx.game_position(position.x, position.y);
+            gfx.set_cursor(Some(cursor));
+        }
+        Event::WindowEvent {
+            event: WindowEvent::CursorLeft { .. },
+            ..
+        } if mouse => gfx.borrow_mut().set_cursor(None),
+        Event::WindowEvent {
+            event: WindowEvent::DroppedFile(path),
+            ..
+        } => {
+            if let Some(menu_state) = menu.as_mut() {
+                let path = if path.is_file() {
+                    path.parent().map(|p| p.to_path_buf()).unwrap_or(path)
+                } else {
+                    path
+                };
+
+                match start_game(path, &gfx, &input, &options, &frontend, &histogram) {
+                    Ok(started) => {
+                        game = started;
+                        menu = None;
+                    }
+                    Err(err) => {
+                        menu_state.set_message(format!("{}", err));
+                        menu_state.draw(&mut gfx.borrow_mut());
+                    }
+                }
+            }
+        }
         Event::WindowEvent {
             event: WindowEvent::KeyboardInput { input: event, .. },
             ..
         } => {
+            if let Some(menu_state) = menu.as_mut() {
+                if let Some(path) = menu_state.process_event(event) {
+                    match start_game(path, &gfx, &input, &options, &frontend, &histogram) {
+                        Ok(started) => {
+                            game = started;
+                            menu = None;
+                        }
+                        Err(err) => menu_state.set_message(format!("{}", err)),
+                    }
+                }
+
+                if let Some(menu_state) = menu.as_ref() {
+                    menu_state.draw(&mut gfx.borrow_mut());
+                }
+                return;
+            }
+
+            if event.virtual_keycode == Some(VirtualKeyCode::F1)
+                && event.state == ElementState::Pressed
+            {
+                gfx.borrow_mut().toggle_thread_overlay();
+            }
+            if event.virtual_keycode == Some(VirtualKeyCode::F2)
+                && event.state == ElementState::Pressed
+            {
+                gfx.borrow_mut().toggle_wireframe();
+            }
+            if event.virtual_keycode == Some(VirtualKeyCode::F3)
+                && event.state == ElementState::Pressed
+            {
+                gfx.borrow_mut().toggle_page_grid();
+            }
+            if event.virtual_keycode == Some(VirtualKeyCode::F4)
+                && event.state == ElementState::Pressed
+            {
+                gfx.borrow_mut().toggle_input_overlay();
+            }
+            if event.virtual_keycode == Some(VirtualKeyCode::F5)
+                && event.state == ElementState::Pressed
+            {
+                gfx.borrow_mut().toggle_depth_heatmap();
+            }
+            if event.virtual_keycode == Some(VirtualKeyCode::F6)
+                && event.state == ElementState::Pressed
+            {
+                let path = histogram_path
+                    .as_deref()
+                    .unwrap_or(histogram::DEFAULT_HISTOGRAM_PATH);
+                export_histogram(&histogram, path);
+            }
             input.process_event(event);
         }
         _ => (),
     });
 }
+
+/// Creates the window with the newest OpenGL context the shaders support, falling back
+/// to older versions when the driver refuses.
+fn create_display(
+    event_loop: &EventLoop<UserEvent>,
+    scale: u32,
+    vsync: bool,
+) -> Result<glium::Display, glium::backend::glutin::DisplayCreationError> {
+    let mut result = None;
+    for version in GL_VERSIONS {
+        let window_builder = winit::window::WindowBuilder::new()
+            .with_title("Another World")
+            .with_inner_size(winit::dpi::PhysicalSize {
+                width: 320 * scale,
+                height: 200 * scale,
+            });
+        let context_builder = glutin::glutin::ContextBuilder::new()
+            .with_srgb(true)
+            .with_depth_buffer(16)
+            .with_gl(GlRequest::Specific(Api::OpenGl, version))
+            .with_vsync(vsync);
+
+        match glium::Display::new(window_builder, context_builder, event_loop) {
+            Ok(display) => return Ok(display),
+            Err(err) => {
+                eprintln!(
+                    "unable to create OpenGL {}.{} context: {}",
+                    version.0, version.1, err
+                );
+                result = Some(err);
+            }
+        }
+    }
+
+    Err(result.expect("at least one OpenGL version"))
+}
+
+fn parse_index(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Reads a replacement for the built in font, 768 bytes of 8x8 glyphs from space onward.
+fn load_font(path: &str) -> Result<FontBitmap, Error> {
+    let data = std::fs::read(path)?;
+    engine::font::font_from_bytes(&data)
+}
+
+fn export_histogram(histogram: &Mutex<FrameHistogram>, path: &str) {
+    match lock(histogram).write_csv(path) {
+        Ok(()) => log::info!("wrote frame histogram to {}", path),
+        Err(err) => log::error!("unable to write frame histogram: {}", err),
+    }
+}
+
+/// Loads the game at `path` and starts it running. By default the executor gets its own
+/// thread and draws through the event loop proxy; in single threaded mode the returned
+/// `GameLoop` must instead be stepped by the event loop itself.
+fn start_game(
+    path: PathBuf,
+    gfx: &Rc<RefCell<GlGfx>>,
+    input: &WinitInput,
+    options: &EngineOptions,
+    frontend: &FrontendOptions,
+    histogram: &Arc<Mutex<FrameHistogram>>,
+) -> Result<Option<GameLoop<LocalGlHandle>>, Error> {
+    let overlay = gfx.borrow().overlay_handle();
+
+    if frontend.single_threaded {
+        let gfx_handle = LocalGlHandle::new(gfx.clone());
+        let game = GameLoop::new(path, gfx_handle, overlay, input, options, frontend)?
+            .with_histogram(histogram.clone());
+        return Ok(Some(game));
+    }
+
+    let gfx_handle = gfx.borrow().handle();
+    let mut game = GameLoop::new(path, gfx_handle, overlay, input, options, frontend)?
+        .with_histogram(histogram.clone());
+
+    std::thread::spawn(move || loop {
+        let wait = game.step();
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    });
+
+    Ok(None)
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct FrontendOptions {
+    verbose: bool,
+    single_threaded: bool,
+    free_camera: bool,
+}
+
+struct GameLoop<G: Gfx> {
+    executor: Executor<DirectoryIo, G, CompositeInput>,
+    input: WinitInputHandle,
+    overlay: OverlayHandle,
+    histogram: Option<Arc<Mutex<FrameHistogram>>>,
+    frame_time: Duration,
+    last_timestamp: Instant,
+    last_snapshot: Instant,
+}
+
+impl<G: Gfx> GameLoop<G> {
+    fn new(
+        path: PathBuf,
+        gfx: G,
+        overlay: OverlayHandle,
+        input: &WinitInput,
+        options: &EngineOptions,
+        frontend: &FrontendOptions,
+    ) -> Result<Self, Error> {
+        let io = DirectoryIo::new(path);
+        let sources = CompositeInput::new().with(input.handle());
+        let mut executor = Executor::with_options(io, gfx, sources, options)?;
+        if frontend.verbose {
+            executor.enable_timings();
+        }
+
+        Ok(GameLoop {
+            executor,
+            input: input.handle(),
+            overlay,
+            histogram: None,
+            frame_time: Duration::ZERO,
+            last_timestamp: Instant::now(),
+            last_snapshot: Instant::now(),
+        })
+    }
+
+    /// Records the time spent running each frame, tagged with the part it ran in.
+    fn with_histogram(mut self, histogram: Arc<Mutex<FrameHistogram>>) -> Self {
+        self.histogram = Some(histogram);
+        self
+    }
+
+    /// Runs the executor up to its next yield and returns how long to wait before stepping
+    /// again.
+    fn step(&mut self) -> Duration {
+        // only peek at the keys, the executor takes any buffered taps when it runs
+        let input = self.input.held();
+        if input.pause {
+            self.last_timestamp = Instant::now() + PAUSE_POLL_INTERVAL;
+            self.last_snapshot = self.last_timestamp;
+            return PAUSE_POLL_INTERVAL;
+        }
+
+        if input.rewind {
+            self.executor.rewind_step();
+            self.last_timestamp = Instant::now() + REWIND_STEP_INTERVAL;
+            self.last_snapshot = self.last_timestamp;
+            return REWIND_STEP_INTERVAL;
+        }
+
+        if self.last_snapshot.elapsed() >= REWIND_SNAPSHOT_INTERVAL {
+            self.executor.push_rewind_snapshot();
+            self.last_snapshot = Instant::now();
+        }
+
+        self.executor
+            .set_scroll_override(self.input.scroll_override());
+        let run_start = Instant::now();
+        let sleep_ms = self.executor.run();
+        self.frame_time += run_start.elapsed();
+        self.overlay.update(
+            self.executor.last_thread_activity(),
+            self.executor.last_input(),
+        );
+        if sleep_ms == 0 {
+            return Duration::ZERO;
+        }
+
+        // runs cut short by the load or blit budget are added up into the frame they belong to
+        let frame_time = std::mem::take(&mut self.frame_time);
+        if let Some(histogram) = self.histogram.as_ref() {
+            let part = self.executor.loaded_part();
+            lock(histogram).record(part, frame_time);
+        }
+
+        let ms = if input.turbo {
+            sleep_ms.min(1)
+        } else {
+            sleep_ms
+        };
+        let elapsed = self.last_timestamp.elapsed();
+        let duration = Duration::from_millis(ms);
+        if duration <= elapsed && !input.turbo {
+            match self.executor.frame_stats() {
+                Some(stats) => log::warn!(
+                    "slow frame: {}ms {}ms ({})",
+                    elapsed.as_millis(),
+                    duration.as_millis(),
+                    stats
+                ),
+                None => log::warn!(
+                    "slow frame: {}ms {}ms",
+                    elapsed.as_millis(),
+                    duration.as_millis()
+                ),
+            }
+        }
+
+        let wait = duration.saturating_sub(elapsed);
+        self.last_timestamp = Instant::now() + wait;
+        wait
+    }
+}
@@ -0,0 +1,156 @@
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use engine::video::Page;
+use engine::Resources;
+
+use super::directory::DirectoryIo;
+use super::gfx::GlGfx;
+
+const SEARCH_PATHS: [&str; 3] = [".", "games", "data"];
+const VISIBLE_ENTRIES: usize = 16;
+const MAX_LINE_LEN: usize = 38;
+
+const MENU_PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xff, 0xcc, 0x00),
+    (0x88, 0x88, 0x88),
+    (0xcc, 0x22, 0x22),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0x00, 0x00, 0x00),
+    (0xff, 0xff, 0xff),
+];
+
+pub struct Menu {
+    entries: Vec<PathBuf>,
+    selected: usize,
+    message: Option<String>,
+}
+
+impl Menu {
+    pub fn new() -> Self {
+        let mut entries = Vec::new();
+
+        for base in SEARCH_PATHS.iter().map(Path::new) {
+            if is_game_data(base) {
+                entries.push(base.to_path_buf());
+            }
+
+            if let Ok(dir) = std::fs::read_dir(base) {
+                let mut found: Vec<_> = dir
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir() && is_game_data(p))
+                    .collect();
+                found.sort();
+                entries.extend(found);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        entries.retain(|entry| seen.insert(entry.clone()));
+
+        Self {
+            entries,
+            selected: 0,
+            message: None,
+        }
+    }
+
+    pub fn set_message<S: Into<String>>(&mut self, message: S) {
+        self.message = Some(message.into());
+    }
+
+    pub fn process_event(&mut self, event: KeyboardInput) -> Option<PathBuf> {
+        if event.state != ElementState::Pressed {
+            return None;
+        }
+
+        match event.virtual_keycode? {
+            VirtualKeyCode::Up | VirtualKeyCode::W => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            VirtualKeyCode::Down | VirtualKeyCode::S => {
+                self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1));
+            }
+            VirtualKeyCode::Space | VirtualKeyCode::Return => {
+                return self.entries.get(self.selected).cloned();
+            }
+            _ => (),
+        }
+
+        None
+    }
+
+    pub fn draw(&self, gfx: &mut GlGfx) {
+        gfx.set_palette(MENU_PALETTE);
+        gfx.select(Page::One);
+        gfx.fill(Page::One, 0);
+        gfx.string("ANOTHER WORLD", 15, 8, 8);
+
+        if self.entries.is_empty() {
+            gfx.string("No game data found.", 2, 8, 32);
+            gfx.string("Drop a data directory on", 2, 8, 48);
+            gfx.string("this window or use --data-path", 2, 8, 56);
+        } else {
+            gfx.string("Select game data:", 2, 8, 24);
+
+            let first = self.selected.saturating_sub(VISIBLE_ENTRIES - 1);
+            for (n, entry) in self
+                .entries
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(VISIBLE_ENTRIES)
+            {
+                let (prefix, color) = if n == self.selected {
+                    ("> ", 1)
+                } else {
+                    ("  ", 15)
+                };
+                let line = format!("{}{}", prefix, display_path(entry));
+                let y = 40 + (n - first) as i16 * 8;
+                gfx.string(&line, color, 0, y);
+            }
+        }
+
+        if let Some(message) = self.message.as_ref() {
+            let message: String = sanitize(message).chars().take(MAX_LINE_LEN).collect();
+            gfx.string(&message, 3, 8, 184);
+        }
+
+        gfx.blit(Page::One);
+    }
+}
+
+pub fn is_game_data(path: &Path) -> bool {
+    Resources::detect_version(&DirectoryIo::new(path)).is_some()
+}
+
+fn display_path(path: &Path) -> String {
+    let text = sanitize(&path.display().to_string());
+    let len = text.chars().count();
+    if len > MAX_LINE_LEN {
+        let tail: String = text.chars().skip(len - (MAX_LINE_LEN - 3)).collect();
+        format!("...{}", tail)
+    } else {
+        text
+    }
+}
+
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if (' '..='~').contains(&c) { c } else { '?' })
+        .collect()
+}
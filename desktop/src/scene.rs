@@ -0,0 +1,200 @@
+use engine::error::Error;
+use engine::video::{BlendMode, Page, Polygon};
+use engine::{EngineOptions, Executor, Gfx};
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use super::benchmark::NullInput;
+use super::directory::DirectoryIo;
+
+/// Opacity used for polygons whose color depends on what is already on the page.
+const TRANSLUCENT_OPACITY: f32 = 0.5;
+
+type Palette = [(u8, u8, u8); 16];
+
+#[derive(Debug, Clone)]
+enum Layer {
+    Fill(u8),
+    Polygon(Box<Polygon>),
+    Text {
+        text: &'static str,
+        color: u8,
+        x: i16,
+        y: i16,
+    },
+    /// Another page's layers as they were when copied, shifted down by the scroll
+    Copy {
+        layers: Vec<Layer>,
+        scroll: i16,
+    },
+}
+
+/// A `Gfx` that keeps every draw command applied to each page instead of rasterizing,
+/// so the page shown by a blit can be reconstructed polygon by polygon.
+#[derive(Default)]
+pub struct SceneGfx {
+    pages: HashMap<Page, Vec<Layer>>,
+    active_page: Option<Page>,
+    palette: Palette,
+    scene: Option<(Vec<Layer>, Palette)>,
+}
+
+impl SceneGfx {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renders the most recently blitted page as an SVG with one element per draw
+    /// command in draw order. Polygon groups are flattened into their child polygons.
+    pub fn last_scene_svg(&self) -> Option<String> {
+        let (layers, palette) = self.scene.as_ref()?;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="320" height="200" viewBox="0 0 320 200">"#
+        );
+        write_layers(&mut svg, layers, palette, 1);
+        svg.push_str("</svg>\n");
+
+        Some(svg)
+    }
+
+    fn layers(&mut self, page: Page) -> &mut Vec<Layer> {
+        self.pages.entry(page).or_default()
+    }
+}
+
+impl Gfx for SceneGfx {
+    fn blit(&mut self, page: Page) {
+        let layers = self.layers(page).clone();
+        self.scene = Some((layers, self.palette));
+    }
+
+    fn draw_polygon(&mut self, polygon: Polygon) {
+        let page = self.active_page.unwrap_or(Page::Zero);
+        self.layers(page).push(Layer::Polygon(Box::new(polygon)));
+    }
+
+    fn fill_page(&mut self, page: Page, color: u8) {
+        let layers = self.layers(page);
+        layers.clear();
+        layers.push(Layer::Fill(color & 0xf));
+    }
+
+    fn select_page(&mut self, page: Page) {
+        self.active_page = Some(page);
+    }
+
+    fn copy_page(&mut self, src: Page, dest: Page, scroll: i16) {
+        if src == dest {
+            return;
+        }
+
+        let layers = self.layers(src).clone();
+        let dest = self.layers(dest);
+        dest.clear();
+        dest.push(Layer::Copy { layers, scroll });
+    }
+
+    fn set_palette(&mut self, palette: [(u8, u8, u8); 16]) {
+        self.palette = palette;
+    }
+
+    fn draw_string(&mut self, text: &'static str, color: u8, x: i16, y: i16) {
+        let page = self.active_page.unwrap_or(Page::Zero);
+        self.layers(page).push(Layer::Text { text, color, x, y });
+    }
+}
+
+fn hex_color(palette: &Palette, color: u8) -> String {
+    let (r, g, b) = palette[color as usize & 0xf];
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn write_layers(svg: &mut String, layers: &[Layer], palette: &Palette, depth: usize) {
+    let indent = "  ".repeat(depth);
+
+    for layer in layers {
+        match layer {
+            Layer::Fill(color) => {
+                let _ = writeln!(
+                    svg,
+                    r#"{}<rect class="fill" width="320" height="200" fill="{}"/>"#,
+                    indent,
+                    hex_color(palette, *color)
+                );
+            }
+            Layer::Polygon(polygon) => {
+                let points = polygon
+                    .points()
+                    .map(|(x, y)| format!("{},{}", x, y))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                // Masked polygons brighten what is under them by moving it to the upper
+                // half of the palette, blended ones reveal page zero. Neither can be
+                // expressed as a flat color, so both are drawn translucent.
+                let (class, fill, opacity) = match polygon.blend {
+                    BlendMode::Solid(color) => ("solid", hex_color(palette, color), 1.0),
+                    BlendMode::Mask(_) => ("mask", String::from("#ffffff"), TRANSLUCENT_OPACITY),
+                    BlendMode::Blend => ("blend", String::from("#000000"), TRANSLUCENT_OPACITY),
+                };
+                let _ = writeln!(
+                    svg,
+                    r#"{}<polygon class="{}" points="{}" fill="{}" fill-opacity="{}"/>"#,
+                    indent, class, points, fill, opacity
+                );
+            }
+            Layer::Text { text, color, x, y } => {
+                let _ = writeln!(
+                    svg,
+                    r#"{}<text class="string" x="{}" y="{}" fill="{}" font-family="monospace" font-size="8" dominant-baseline="hanging">{}</text>"#,
+                    indent,
+                    x,
+                    y,
+                    hex_color(palette, *color),
+                    escape_text(text)
+                );
+            }
+            Layer::Copy { layers, scroll } => {
+                let _ = writeln!(
+                    svg,
+                    r#"{}<g class="copy" transform="translate(0,{})">"#,
+                    indent, scroll
+                );
+                write_layers(svg, layers, palette, depth + 1);
+                let _ = writeln!(svg, "{}</g>", indent);
+            }
+        }
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Runs the game headless for `frames` frames and writes the last blitted scene to `out`
+/// as a layered SVG.
+pub fn export_scene<P: AsRef<Path>>(
+    data_path: PathBuf,
+    frames: usize,
+    out: P,
+    options: &EngineOptions,
+) -> Result<(), Error> {
+    let io = DirectoryIo::new(data_path);
+    let mut executor = Executor::with_options(io, SceneGfx::new(), NullInput, options)?;
+    for _ in 0..frames {
+        executor.run();
+    }
+
+    let svg = executor.gfx().last_scene_svg().unwrap_or_else(|| {
+        String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="320" height="200"/>"#)
+    });
+    std::fs::write(out, svg)?;
+
+    Ok(())
+}
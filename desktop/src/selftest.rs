@@ -0,0 +1,29 @@
+use engine::error::Error;
+use engine::resources::GamePart;
+use engine::{EngineOptions, Executor};
+
+use std::path::PathBuf;
+
+use super::benchmark::{NullGfx, NullInput};
+use super::directory::DirectoryIo;
+
+/// Boots every game part up to its first blit and prints a pass/fail line for each,
+/// returning whether all of them booted.
+pub fn run(path: PathBuf, options: &EngineOptions) -> Result<bool, Error> {
+    let mut passed = true;
+
+    for part in GamePart::ALL {
+        let io = DirectoryIo::new(path.clone());
+        let mut executor = Executor::with_options(io, NullGfx, NullInput, options)?;
+
+        match executor.boot_part(part) {
+            Ok(()) => println!("part {:>2}: ok", part.number()),
+            Err(err) => {
+                println!("part {:>2}: FAILED {}", part.number(), err);
+                passed = false;
+            }
+        }
+    }
+
+    Ok(passed)
+}
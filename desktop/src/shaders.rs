@@ -1,14 +1,16 @@
-pub const PAGE_VERTEX_SHADER: &'static str = "
-#version 420
+//! Shader sources without a `#version` line, `gfx::create_program` prepends the
+//! newest version the context supports. They are kept valid GLSL 3.30 so that
+//! OpenGL 3.3 contexts work as a fallback.
 
+pub const PAGE_VERTEX_SHADER: &'static str = "
 in vec2 position;
 in uint color;
 in uint depth;
 in uint mask;
 
-out flat uint v_color;
-out flat uint v_depth;
-out flat uint v_mask;
+flat out uint v_color;
+flat out uint v_depth;
+flat out uint v_mask;
 out vec2 v_position;
 
 void main () {
@@ -21,23 +23,24 @@ void main () {
 ";
 
 pub const PAGE_FRAGMENT_SHADER: &'static str = "
-#version 420
-
-in flat uint v_color;
-in flat uint v_depth;
-in flat uint v_mask;
+flat in uint v_color;
+flat in uint v_depth;
+flat in uint v_mask;
 in vec2 v_position;
 
 uniform uint u_max_depth;
+uniform bool u_depth_heatmap;
 uniform usampler2D u_page_zero;
 uniform usampler2D u_page_self;
 
 out uint f_color;
 
 void main () {
-  if (v_mask != 0) {
+  if (u_depth_heatmap) {
+    f_color = 16u + min(15u, v_depth * 16u / u_max_depth);
+  } else if (v_mask != 0u) {
     f_color = texture(u_page_self, v_position).r | v_mask;
-  } else if (v_color > 15) {
+  } else if (v_color > 15u) {
     f_color = texture(u_page_zero, v_position).r;
   } else {
     f_color = v_color;
@@ -47,8 +50,6 @@ void main () {
 ";
 
 pub const FRAME_VERTEX_SHADER: &'static str = "
-#version 420
-
 in vec2 position;
 
 out vec2 v_position;
@@ -60,24 +61,63 @@ void main () {
 ";
 
 pub const FRAME_FRAGMENT_SHADER: &'static str = "
-#version 420
-
 in vec2 v_position;
 
 uniform sampler2D u_palette;
 uniform usampler2D u_page;
+uniform mat3 u_color_transform;
 
 out vec4 f_color;
 
 void main () {
   uint color_index = texture(u_page, v_position).r;
-  f_color = vec4(texelFetch(u_palette, ivec2(color_index, 0), 0).rgb, 1.0);
+  vec3 color;
+  if (color_index == 255u) {
+    // debug clear, a page read before it was ever filled
+    f_color = vec4(1.0, 0.0, 1.0, 1.0);
+    return;
+  } else if (color_index > 15u) {
+    // depth heatmap, blue for the first polygons of a batch through green to red
+    float heat = float(color_index - 16u) / 15.0;
+    color = vec3(heat, 1.0 - abs(2.0 * heat - 1.0), 1.0 - heat);
+  } else {
+    color = texelFetch(u_palette, ivec2(color_index, 0), 0).rgb;
+  }
+  f_color = vec4(clamp(u_color_transform * color, 0.0, 1.0), 1.0);
 }
 ";
 
-pub const COPY_VERTEX_SHADER: &'static str = "
-#version 420
+/// Writes each page index to the red channel as `index / 255`, so a page can be read
+/// back through a normalized RGBA texture.
+pub const READBACK_FRAGMENT_SHADER: &str = "
+in vec2 v_position;
 
+uniform usampler2D u_page;
+
+out vec4 f_color;
+
+void main () {
+  f_color = vec4(float(texture(u_page, v_position).r) / 255.0, 0.0, 0.0, 1.0);
+}
+";
+
+pub const BLEND_FRAGMENT_SHADER: &str = "
+in vec2 v_position;
+
+uniform sampler2D u_previous;
+uniform sampler2D u_current;
+uniform float u_weight;
+
+out vec4 f_color;
+
+void main () {
+  vec3 previous = texture(u_previous, v_position).rgb;
+  vec3 current = texture(u_current, v_position).rgb;
+  f_color = vec4(mix(previous, current, u_weight), 1.0);
+}
+";
+
+pub const COPY_VERTEX_SHADER: &'static str = "
 in vec2 position;
 
 out vec2 v_position;
@@ -89,8 +129,6 @@ void main () {
 ";
 
 pub const COPY_FRAGMENT_SHADER: &'static str = "
-#version 420
-
 in vec2 v_position;
 
 uniform usampler2D u_page;
@@ -100,7 +138,7 @@ uniform int u_scroll;
 out uint f_color;
 
 void main () {
-  if (u_fill > 15) {
+  if (u_fill > 15u) {
     float scroll = float(u_scroll) / 200.0;
     f_color = texture(u_page, v_position.xy + vec2(0.0, scroll)).r;
   } else {
@@ -110,8 +148,6 @@ void main () {
 ";
 
 pub const FONT_VERTEX_SHADER: &'static str = "
-#version 420
-
 in vec2 position;
 in vec2 uv;
 
@@ -126,8 +162,6 @@ void main () {
 ";
 
 pub const FONT_FRAGMENT_SHADER: &'static str = "
-#version 420
-
 in vec2 v_position;
 in vec2 v_uv;
 
@@ -138,10 +172,32 @@ out uint f_color;
 
 void main () {
   uint pixel = texture(u_font_atlas, v_uv.xy).r;
-  if (pixel > 0) {
+  if (pixel > 0u) {
     f_color = u_color;
   } else {
     discard;
   }
 }
 ";
+
+pub const OVERLAY_VERTEX_SHADER: &str = "
+in vec2 position;
+in vec3 color;
+
+out vec3 v_color;
+
+void main () {
+  v_color = color;
+  gl_Position = vec4(position, 0.0, 1.0);
+}
+";
+
+pub const OVERLAY_FRAGMENT_SHADER: &str = "
+in vec3 v_color;
+
+out vec4 f_color;
+
+void main () {
+  f_color = vec4(v_color, 1.0);
+}
+";
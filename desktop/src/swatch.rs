@@ -0,0 +1,88 @@
+use engine::{EngineOptions, Executor};
+
+use std::path::{Path, PathBuf};
+
+use super::benchmark::{NullGfx, NullInput};
+use super::directory::DirectoryIo;
+
+type Palette = [(u8, u8, u8); 16];
+
+/// Width and height in pixels of each color in the swatch.
+pub const SWATCH_SIZE: u32 = 16;
+
+/// Runs the game headless for `frames` frames and writes the palette shown at that
+/// point to `out` as a PNG swatch, see `write_swatch`.
+pub fn export_palette<P: AsRef<Path>>(
+    data_path: PathBuf,
+    frames: usize,
+    out: P,
+    options: &EngineOptions,
+) -> Result<(), String> {
+    let io = DirectoryIo::new(data_path);
+    let mut executor =
+        Executor::with_options(io, NullGfx, NullInput, options).map_err(|e| e.to_string())?;
+    for _ in 0..frames {
+        executor.run();
+    }
+
+    let palette = executor
+        .current_palette()
+        .ok_or_else(|| format!("no palette applied after {} frames", frames))?;
+
+    let file = std::fs::File::create(out).map_err(|e| e.to_string())?;
+    write_swatch(std::io::BufWriter::new(file), &palette).map_err(|e| e.to_string())
+}
+
+/// Encodes `palette` as a 16 color wide strip of `SWATCH_SIZE` squares, in palette
+/// index order from the left.
+pub fn write_swatch<W: std::io::Write>(
+    writer: W,
+    palette: &Palette,
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(writer, SWATCH_SIZE * 16, SWATCH_SIZE);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let row: Vec<u8> = palette
+        .iter()
+        .flat_map(|&(r, g, b)| std::iter::repeat_n([r, g, b], SWATCH_SIZE as usize))
+        .flatten()
+        .collect();
+    let pixels = row.repeat(SWATCH_SIZE as usize);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swatch_holds_each_palette_color() {
+        let mut palette = [(0, 0, 0); 16];
+        for (n, color) in palette.iter_mut().enumerate() {
+            let n = n as u8;
+            *color = (n * 16, 255 - n * 16, n);
+        }
+
+        let mut png_data = Vec::new();
+        write_swatch(&mut png_data, &palette).unwrap();
+
+        let decoder = png::Decoder::new(png_data.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((info.width, info.height), (256, 16));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        for y in [0, 15] {
+            for (n, &(r, g, b)) in palette.iter().enumerate() {
+                for x in [n * 16, n * 16 + 15] {
+                    let offset = (y * 256 + x) * 3;
+                    assert_eq!(pixels[offset..offset + 3], [r, g, b]);
+                }
+            }
+        }
+    }
+}
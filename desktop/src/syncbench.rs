@@ -0,0 +1,324 @@
+//! Compares how long the game thread stalls handing drawing to the render thread under
+//! the per command handshake `GlHandle` uses today and under a triple buffered hand-off
+//! of whole frames of commands.
+//!
+//! Both strategies go through the frontend's own path: a game thread draws a synthetic
+//! scene through a `GlHandle`, the commands reach the event loop through its proxy and
+//! `GlGfx::apply` renders them with OpenGL into the pages of a hidden window. Only the
+//! hand-off differs. For each strategy this reports the time the game thread spent
+//! inside drawing calls per frame, and throughput as frames per second from the first
+//! command until the GPU has finished the last frame. The context still needs a window,
+//! so on a machine without a display run this under a virtual one, such as `xvfb-run`.
+
+use winit::event::Event;
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::platform::run_return::EventLoopExtRunReturn;
+
+use std::time::{Duration, Instant};
+
+use engine::gfx::{Gfx, PageSnapshot};
+use engine::video::{BlendMode, Page, Polygon};
+
+use super::gfx::{Frame, GlGfx, GlHandle};
+use super::UserEvent;
+
+/// Frames in flight for the buffered strategy: one being filled, one queued and one
+/// being drawn.
+const BUFFER_COUNT: u64 = 3;
+/// How often the render thread checks whether the game thread is done once it has
+/// nothing left to draw.
+const FINISH_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Commands issued per frame, roughly a busy gameplay scene.
+#[derive(Debug, Copy, Clone)]
+pub struct Scene {
+    pub fills: usize,
+    pub copies: usize,
+    pub draws: usize,
+    pub strings: usize,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            fills: 2,
+            copies: 2,
+            draws: 300,
+            strings: 4,
+        }
+    }
+}
+
+impl Scene {
+    fn commands(&self) -> usize {
+        // a select and a blit around the scene itself
+        2 + self.fills + self.copies + self.draws + self.strings
+    }
+
+    /// Small quads spread over the screen in every color, with the occasional blended
+    /// one so the draws are split into batches as real scenes are.
+    fn polygons(&self) -> Vec<Polygon> {
+        (0..self.draws as i32)
+            .filter_map(|n| {
+                let (x, y) = ((n * 37) % 300, (n * 53) % 180);
+                let (w, h) = (8 + n % 16, 8 + n % 12);
+                let blend = if n % 50 == 49 {
+                    BlendMode::Blend
+                } else {
+                    BlendMode::Solid((n % 15 + 1) as u8)
+                };
+                Polygon::from_points(&[(x, y), (x + w, y), (x + w, y + h), (x, y + h)], blend)
+            })
+            .collect()
+    }
+
+    fn draw<G: Gfx>(&self, gfx: &mut G, polygons: &[Polygon]) {
+        gfx.select_page(Page::One);
+        for n in 0..self.fills {
+            gfx.fill_page(Page::One, n as u8);
+        }
+        for _ in 0..self.copies {
+            gfx.copy_page(Page::Zero, Page::One, 0);
+        }
+        for polygon in polygons {
+            gfx.draw_polygon(polygon.clone());
+        }
+        for n in 0..self.strings {
+            gfx.draw_string("Another World", 15, 8, 8 + n as i16 * 10);
+        }
+        gfx.blit(Page::One);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Report {
+    pub frames: usize,
+    pub total: Duration,
+    pub stall: Duration,
+    pub max_frame_stall: Duration,
+}
+
+impl Report {
+    fn new(frames: usize) -> Self {
+        Self {
+            frames,
+            total: Duration::ZERO,
+            stall: Duration::ZERO,
+            max_frame_stall: Duration::ZERO,
+        }
+    }
+
+    fn add_stall(&mut self, stall: Duration) {
+        self.stall += stall;
+        self.max_frame_stall = self.max_frame_stall.max(stall);
+    }
+
+    fn print(&self, name: &str) {
+        let frames = self.frames.max(1) as u32;
+        println!("{}:", name);
+        println!(
+            "  fps:         {:.1}",
+            self.frames as f64 / self.total.as_secs_f64()
+        );
+        println!("  stall/frame: {}", format_duration(self.stall / frames));
+        println!("  max stall:   {}", format_duration(self.max_frame_stall));
+    }
+}
+
+/// Runs `frames` of the default scene through both strategies, rendering with `gfx` on
+/// this thread, and prints the results.
+pub fn run(frames: usize, event_loop: &mut EventLoop<UserEvent>, gfx: &mut GlGfx) {
+    let scene = Scene::default();
+    println!("{} frames of {} commands", frames, scene.commands());
+
+    render(event_loop, gfx, move |handle| {
+        handshake(frames, scene, handle)
+    })
+    .print("handshake");
+    render(event_loop, gfx, move |handle| {
+        buffered(frames, scene, handle)
+    })
+    .print("buffered");
+}
+
+/// Runs `game` on its own thread with a handle to `gfx`, and renders what it sends the
+/// way the frontend's event loop does until it returns.
+fn render<F>(event_loop: &mut EventLoop<UserEvent>, gfx: &mut GlGfx, game: F) -> Report
+where
+    F: FnOnce(GlHandle) -> Report + Send + 'static,
+{
+    let handle = gfx.handle();
+    let start = Instant::now();
+    let game = std::thread::spawn(move || game(handle));
+
+    event_loop.run_return(|event, _, control_flow| match event {
+        Event::UserEvent(event) => {
+            gfx.apply(event);
+            gfx.sync();
+        }
+        Event::MainEventsCleared => {
+            *control_flow = if game.is_finished() {
+                ControlFlow::Exit
+            } else {
+                ControlFlow::WaitUntil(Instant::now() + FINISH_POLL_INTERVAL)
+            };
+        }
+        _ => (),
+    });
+    gfx.finish();
+
+    let mut report = game.join().expect("sync benchmark game thread panicked");
+    report.total = start.elapsed();
+    report
+}
+
+/// `GlHandle`'s strategy: draws are queued in shared state and every other command is
+/// sent on its own, waiting for the render thread to flush the queued draws and process
+/// it before the game thread continues.
+fn handshake(frames: usize, scene: Scene, mut handle: GlHandle) -> Report {
+    let polygons = scene.polygons();
+    let mut report = Report::new(frames);
+
+    for _ in 0..frames {
+        let frame_start = Instant::now();
+        scene.draw(&mut handle, &polygons);
+        report.add_stall(frame_start.elapsed());
+    }
+
+    report
+}
+
+/// Commands are recorded into a frame that is handed over whole at each blit, the game
+/// thread only waits when all `BUFFER_COUNT` frames are in flight.
+fn buffered(frames: usize, scene: Scene, handle: GlHandle) -> Report {
+    let polygons = scene.polygons();
+    let mut recorder = FrameRecorder {
+        handle,
+        frame: Frame::default(),
+        queued: 0,
+    };
+    let mut report = Report::new(frames);
+
+    for _ in 0..frames {
+        let frame_start = Instant::now();
+        scene.draw(&mut recorder, &polygons);
+        report.add_stall(frame_start.elapsed());
+    }
+    // let the frames still in flight finish before the render thread is stopped
+    recorder.handle.send_frame(Frame::default(), 0);
+
+    report
+}
+
+/// Records drawing for `GlHandle::send_frame` until the frame is blitted.
+struct FrameRecorder {
+    handle: GlHandle,
+    frame: Frame,
+    /// Polygons drawn since the last command
+    queued: usize,
+}
+
+impl FrameRecorder {
+    fn record(&mut self, event: UserEvent) {
+        self.frame.commands.push((self.queued, event));
+        self.queued = 0;
+    }
+
+    fn send(&mut self, in_flight: u64) {
+        let frame = std::mem::take(&mut self.frame);
+        self.queued = 0;
+        self.handle.send_frame(frame, in_flight);
+    }
+}
+
+impl Gfx for FrameRecorder {
+    fn blit(&mut self, page: Page) {
+        self.record(UserEvent::Blit(page));
+        self.send(BUFFER_COUNT - 1);
+    }
+
+    fn draw_polygon(&mut self, polygon: Polygon) {
+        self.frame.polygons.push(polygon);
+        self.queued += 1;
+    }
+
+    fn fill_page(&mut self, page: Page, color: u8) {
+        self.record(UserEvent::Fill(page, color));
+    }
+
+    fn select_page(&mut self, page: Page) {
+        self.record(UserEvent::Select(page));
+    }
+
+    fn copy_page(&mut self, src: Page, dest: Page, scroll: i16) {
+        self.record(UserEvent::Copy(src, dest, scroll));
+    }
+
+    fn set_palette(&mut self, palette: [(u8, u8, u8); 16]) {
+        self.handle.set_palette(palette);
+    }
+
+    fn draw_string(&mut self, text: &'static str, color: u8, x: i16, y: i16) {
+        self.record(UserEvent::String(text, color, x, y));
+    }
+
+    fn save_pages(&mut self) -> Option<PageSnapshot> {
+        self.send(0);
+        self.handle.save_pages()
+    }
+
+    fn restore_pages(&mut self, pages: &PageSnapshot) {
+        self.send(0);
+        self.handle.restore_pages(pages);
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct CountingGfx {
+        commands: usize,
+        polygons: usize,
+    }
+
+    impl Gfx for CountingGfx {
+        fn blit(&mut self, _page: Page) {
+            self.commands += 1;
+        }
+        fn draw_polygon(&mut self, _polygon: Polygon) {
+            self.commands += 1;
+            self.polygons += 1;
+        }
+        fn fill_page(&mut self, _page: Page, _color: u8) {
+            self.commands += 1;
+        }
+        fn select_page(&mut self, _page: Page) {
+            self.commands += 1;
+        }
+        fn copy_page(&mut self, _src: Page, _dest: Page, _scroll: i16) {
+            self.commands += 1;
+        }
+        fn set_palette(&mut self, _palette: [(u8, u8, u8); 16]) {}
+        fn draw_string(&mut self, _text: &'static str, _color: u8, _x: i16, _y: i16) {
+            self.commands += 1;
+        }
+    }
+
+    #[test]
+    fn scene_draws_every_command_on_screen() {
+        let scene = Scene::default();
+        let polygons = scene.polygons();
+        assert_eq!(polygons.len(), scene.draws);
+
+        let mut gfx = CountingGfx::default();
+        scene.draw(&mut gfx, &polygons);
+        assert_eq!(gfx.commands, scene.commands());
+        assert_eq!(gfx.polygons, scene.draws);
+    }
+}
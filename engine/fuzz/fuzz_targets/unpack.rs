@@ -0,0 +1,21 @@
+//! Feeds arbitrary packed bank data into the resource decompressor.
+//!
+//! The first two bytes of the input are the big endian unpacked size, as it would
+//! appear in `MEMLIST.BIN`, the rest is the packed buffer. The decompressor reads
+//! its input back to front, so the trailing twelve bytes form the size/crc/check
+//! header. Any result is fine, only a panic is a failure.
+//!
+//! Run with `cargo fuzz run unpack` from the `engine` directory. Entries packed from
+//! a real `BANKxx` file make a good seed corpus, `corpus/unpack` is not checked in.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+
+    let size = u16::from_be_bytes([data[0], data[1]]);
+    let _ = engine::resources::unpack(data[2..].to_vec(), size);
+});
@@ -0,0 +1,29 @@
+//! Runs arbitrary bytes as part bytecode through the VM.
+//!
+//! Threads that never yield would hang the fuzzer, so every `execute_frame` call is
+//! capped at `STEP_LIMIT` instructions and `FRAME_LIMIT` frames are run in total.
+//! Errors are expected, only a panic is a failure. The VM is fed a fresh default
+//! input each frame and resource requests are ignored.
+//!
+//! Run with `cargo fuzz run vm` from the `engine` directory. The `BANKxx` bytecode
+//! entries of each part make a good seed corpus, `corpus/vm` is not checked in.
+#![no_main]
+
+use engine::input::InputState;
+use engine::resources::GameVersion;
+use engine::vm::Vm;
+use libfuzzer_sys::fuzz_target;
+
+const STEP_LIMIT: usize = 10_000;
+const FRAME_LIMIT: usize = 16;
+
+fuzz_target!(|data: &[u8]| {
+    let mut vm = Vm::new(GameVersion::Dos, false);
+    vm.set_step_limit(Some(STEP_LIMIT));
+
+    for _ in 0..FRAME_LIMIT {
+        let _ = vm.execute_frame(data, InputState::default());
+        vm.video_commands().for_each(drop);
+        vm.audio_commands().for_each(drop);
+    }
+});
@@ -0,0 +1,668 @@
+/// Sound effects play on one of four channels numbered 0-3, mirroring the Amiga's four
+/// hardware voices. Channel numbers from scripts are masked to that range.
+pub const CHANNEL_COUNT: usize = 4;
+
+/// Default output rate of the mixer in Hz.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Playback rate in Hz for each frequency index accepted by PlaySound, valid indices are 0-39.
+pub const FREQUENCY_TABLE: [u16; 40] = [
+    0x0cff, 0x0dc3, 0x0e91, 0x0f6f, 0x1056, 0x114e, 0x1259, 0x136c, 0x149f, 0x15d9, 0x1726, 0x1888,
+    0x19fd, 0x1b86, 0x1d21, 0x1ede, 0x20ab, 0x229c, 0x24b3, 0x26d7, 0x293f, 0x2bb2, 0x2e4c, 0x3110,
+    0x33fb, 0x370d, 0x3a43, 0x3ddf, 0x4157, 0x4538, 0x4998, 0x4dae, 0x5240, 0x5764, 0x5c9a, 0x61c8,
+    0x6793, 0x6e19, 0x7485, 0x7bbd,
+];
+
+pub const MAX_VOLUME: u8 = 0x3f;
+
+const SOUND_HEADER_LEN: usize = 8;
+
+#[derive(Debug, Copy, Clone)]
+pub struct PlaySoundCommand {
+    pub resource_id: u16,
+    /// Index into `FREQUENCY_TABLE`
+    pub frequency: u8,
+    /// 0-63, a volume of 0 stops the channel instead of playing
+    pub volume: u8,
+    pub channel: u8,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PlayMusicCommand {
+    /// Music module to load, 0 leaves the current module in place
+    pub resource_id: u16,
+    /// Overrides the module's tempo when nonzero, see `MusicSequencer::row_duration_ms`
+    pub delay: u16,
+    /// Order table position to start from
+    pub position: u8,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AudioCommand {
+    PlaySound(PlaySoundCommand),
+    PlayMusic(PlayMusicCommand),
+}
+
+const MUSIC_ORDER_COUNT_OFFSET: usize = 0x3e;
+const MUSIC_ORDER_TABLE_OFFSET: usize = 0x40;
+const MUSIC_ORDER_TABLE_LEN: usize = 0x80;
+const MUSIC_PATTERN_OFFSET: usize = 0xc0;
+const MUSIC_PATTERN_LEN: usize = 0x400;
+const MUSIC_ROW_LEN: usize = 16;
+const MUSIC_ROWS: u8 = 64;
+/// A channel whose first note word is this sets the music marker to its second word.
+const MUSIC_NOTE_MARKER: u16 = 0xfffd;
+
+/// A decoded music resource: a big endian default delay, 15 instrument slots, the
+/// order table length at 0x3e, a 128 entry order table at 0x40, then pattern data.
+#[derive(Debug, Clone)]
+pub struct MusicModule {
+    delay: u16,
+    order_count: u16,
+    order_table: [u8; MUSIC_ORDER_TABLE_LEN],
+    patterns: Vec<u8>,
+}
+
+impl MusicModule {
+    pub fn decode(resource: &[u8]) -> Option<Self> {
+        let delay = u16::from_be_bytes([*resource.first()?, *resource.get(1)?]);
+        let order_count = u16::from_be_bytes([
+            *resource.get(MUSIC_ORDER_COUNT_OFFSET)?,
+            *resource.get(MUSIC_ORDER_COUNT_OFFSET + 1)?,
+        ]);
+        let mut order_table = [0; MUSIC_ORDER_TABLE_LEN];
+        order_table.copy_from_slice(
+            resource
+                .get(MUSIC_ORDER_TABLE_OFFSET..MUSIC_ORDER_TABLE_OFFSET + MUSIC_ORDER_TABLE_LEN)?,
+        );
+        let patterns = resource.get(MUSIC_PATTERN_OFFSET..)?.to_vec();
+
+        Some(Self {
+            delay,
+            order_count,
+            order_table,
+            patterns,
+        })
+    }
+
+    pub fn delay(&self) -> u16 {
+        self.delay
+    }
+
+    pub fn order_count(&self) -> u16 {
+        self.order_count
+    }
+
+    /// Pattern played at order table `position`.
+    pub fn pattern(&self, position: u8) -> Option<u8> {
+        self.order_table.get(position as usize).copied()
+    }
+
+    /// Pattern data, 1024 bytes per pattern: 64 rows of 4 channels, 4 bytes each.
+    pub fn patterns(&self) -> &[u8] {
+        &self.patterns
+    }
+}
+
+/// Tracks which music module is playing, its tempo, and its place in the order table.
+#[derive(Debug, Clone, Default)]
+pub struct MusicSequencer {
+    module: Option<MusicModule>,
+    resource_id: u16,
+    delay: u16,
+    position: u8,
+    row: u8,
+    row_elapsed_ms: u32,
+    playing: bool,
+}
+
+impl MusicSequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles a PlayMusic instruction, `data` is the music resource if it is loaded.
+    /// A nonzero `resource_id` loads that module and starts it from `position`, using
+    /// `delay` as its tempo when nonzero. With no resource a nonzero `delay` retimes the
+    /// playing module without restarting it, and a zero `delay` stops the music.
+    pub fn play_music(&mut self, command: PlayMusicCommand, data: Option<&[u8]>) {
+        if command.resource_id == 0 {
+            if command.delay != 0 {
+                self.delay = command.delay;
+            } else {
+                self.stop();
+            }
+            return;
+        }
+
+        let module = match data.and_then(MusicModule::decode) {
+            Some(module) => module,
+            None => {
+                log::warn!("music {:#x} not loaded", command.resource_id);
+                return;
+            }
+        };
+
+        self.delay = if command.delay != 0 {
+            command.delay
+        } else {
+            module.delay()
+        };
+        self.resource_id = command.resource_id;
+        self.position = command.position;
+        self.row = 0;
+        self.row_elapsed_ms = 0;
+        self.module = Some(module);
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn resource_id(&self) -> Option<u16> {
+        self.module.as_ref().map(|_| self.resource_id)
+    }
+
+    /// Raw tempo value, either the module default or the last PlayMusic override.
+    pub fn delay(&self) -> u16 {
+        self.delay
+    }
+
+    /// Time spent on each pattern row, the original converts the delay with `* 60 / 7050`.
+    pub fn row_duration_ms(&self) -> u32 {
+        self.delay as u32 * 60 / 7050
+    }
+
+    /// Current position in the module's order table.
+    pub fn position(&self) -> u8 {
+        self.position
+    }
+
+    pub fn row(&self) -> u8 {
+        self.row
+    }
+
+    pub fn module(&self) -> Option<&MusicModule> {
+        self.module.as_ref()
+    }
+
+    /// Moves the music on by `ms` of game time, playing a row each `row_duration_ms`.
+    /// Returns the value of the last marker note among the rows played, for the
+    /// executor to store in `vars::MUSIC_MARKER` where scripts wait on it to keep
+    /// cutscenes in step with the music. The music stops after the last order.
+    pub fn advance(&mut self, ms: u32) -> Option<i16> {
+        if !self.playing {
+            return None;
+        }
+
+        let row_ms = self.row_duration_ms().max(1);
+        let mut marker = None;
+
+        self.row_elapsed_ms += ms;
+        while self.playing && self.row_elapsed_ms >= row_ms {
+            self.row_elapsed_ms -= row_ms;
+            if let Some(value) = self.play_row() {
+                marker = Some(value);
+            }
+        }
+
+        marker
+    }
+
+    fn play_row(&mut self) -> Option<i16> {
+        let module = self.module.as_ref()?;
+        let mut marker = None;
+
+        if let Some(pattern) = module.pattern(self.position) {
+            let offset = pattern as usize * MUSIC_PATTERN_LEN + self.row as usize * MUSIC_ROW_LEN;
+            let row = module.patterns().get(offset..offset + MUSIC_ROW_LEN);
+            for note in row.into_iter().flat_map(|row| row.chunks_exact(4)) {
+                let first = u16::from_be_bytes([note[0], note[1]]);
+                let second = u16::from_be_bytes([note[2], note[3]]);
+                if first == MUSIC_NOTE_MARKER {
+                    marker = Some(second as i16);
+                }
+            }
+        }
+
+        self.row += 1;
+        if self.row == MUSIC_ROWS {
+            self.row = 0;
+            self.position = self.position.wrapping_add(1);
+            if self.position as u16 >= module.order_count() {
+                self.stop();
+            }
+        }
+
+        marker
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sample {
+    data: Vec<i8>,
+    loop_start: Option<usize>,
+}
+
+impl Sample {
+    pub fn new(data: Vec<i8>, loop_start: Option<usize>) -> Self {
+        let loop_start = loop_start.filter(|&start| start < data.len());
+        Self { data, loop_start }
+    }
+
+    /// Decodes a sound resource: a big endian length and loop length, both in
+    /// words, followed by signed 8-bit samples after an 8 byte header.
+    pub fn decode(resource: &[u8]) -> Option<Self> {
+        let len = u16::from_be_bytes([*resource.first()?, *resource.get(1)?]) as usize * 2;
+        let loop_len = u16::from_be_bytes([*resource.get(2)?, *resource.get(3)?]) as usize * 2;
+
+        let end = SOUND_HEADER_LEN + len + loop_len;
+        let data = resource.get(SOUND_HEADER_LEN..end)?;
+        let data = data.iter().map(|&b| b as i8).collect();
+        let loop_start = if loop_len > 0 { Some(len) } else { None };
+
+        Some(Self::new(data, loop_start))
+    }
+
+    pub fn data(&self) -> &[i8] {
+        &self.data
+    }
+
+    pub fn loop_start(&self) -> Option<usize> {
+        self.loop_start
+    }
+}
+
+/// A post-mix processing stage, applied to the summed output of all channels.
+pub trait AudioEffect: Send {
+    fn process(&mut self, buffer: &mut [f32]);
+}
+
+#[derive(Debug, Clone)]
+struct Channel {
+    sample: Sample,
+    volume: u8,
+    position: f64,
+    step: f64,
+}
+
+impl Channel {
+    fn next(&mut self) -> Option<f32> {
+        let mut index = self.position as usize;
+        if index >= self.sample.data.len() {
+            let loop_start = self.sample.loop_start?;
+            let loop_len = (self.sample.data.len() - loop_start) as f64;
+            self.position = loop_start as f64 + (self.position - loop_start as f64) % loop_len;
+            index = self.position as usize;
+        }
+
+        let value = self.sample.data[index] as f32 / 128.0;
+        self.position += self.step;
+
+        Some(value * self.volume as f32 / MAX_VOLUME as f32)
+    }
+}
+
+/// The sound channels and music position at a point in time, see `Mixer::state`.
+#[derive(Debug, Clone)]
+pub struct AudioState {
+    channels: [Option<Channel>; CHANNEL_COUNT],
+    music: MusicSequencer,
+}
+
+pub struct Mixer {
+    sample_rate: u32,
+    channels: [Option<Channel>; CHANNEL_COUNT],
+    effect: Option<Box<dyn AudioEffect>>,
+    music: MusicSequencer,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            channels: [None, None, None, None],
+            effect: None,
+            music: MusicSequencer::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn play(&mut self, channel: usize, sample: Sample, frequency: u32, volume: u8) {
+        if let Some(slot) = self.channels.get_mut(channel) {
+            *slot = Some(Channel {
+                sample,
+                volume: volume.min(MAX_VOLUME),
+                position: 0.0,
+                step: frequency as f64 / self.sample_rate as f64,
+            });
+        }
+    }
+
+    /// Handles a PlaySound instruction, `data` is the sound resource if it is loaded. A
+    /// volume of 0 silences the channel, otherwise the channel's current sound is cut and
+    /// replaced.
+    pub fn play_sound(&mut self, command: PlaySoundCommand, data: Option<&[u8]>) {
+        let channel = (command.channel & 3) as usize;
+
+        if command.volume == 0 {
+            self.stop(channel);
+            return;
+        }
+
+        let frequency = match FREQUENCY_TABLE.get(command.frequency as usize) {
+            Some(&frequency) => frequency as u32,
+            None => {
+                log::warn!("invalid sound frequency index: {}", command.frequency);
+                return;
+            }
+        };
+
+        match data.and_then(Sample::decode) {
+            Some(sample) => self.play(channel, sample, frequency, command.volume),
+            None => log::warn!("sound {:#x} not loaded", command.resource_id),
+        }
+    }
+
+    /// Handles a PlayMusic instruction, see `MusicSequencer::play_music`.
+    pub fn play_music(&mut self, command: PlayMusicCommand, data: Option<&[u8]>) {
+        self.music.play_music(command, data);
+    }
+
+    pub fn music(&self) -> &MusicSequencer {
+        &self.music
+    }
+
+    /// See `MusicSequencer::advance`.
+    pub fn advance_music(&mut self, ms: u32) -> Option<i16> {
+        self.music.advance(ms)
+    }
+
+    pub fn stop(&mut self, channel: usize) {
+        if let Some(slot) = self.channels.get_mut(channel) {
+            *slot = None;
+        }
+    }
+
+    pub fn is_playing(&self, channel: usize) -> bool {
+        matches!(self.channels.get(channel), Some(Some(_)))
+    }
+
+    /// Captures the playing sounds, including how far through their samples they are,
+    /// and the music module and position, for save states.
+    pub fn state(&self) -> AudioState {
+        AudioState {
+            channels: self.channels.clone(),
+            music: self.music.clone(),
+        }
+    }
+
+    /// Resumes the sounds and music captured by `state`, replacing whatever is playing.
+    /// The sample rate and effect are left as they are.
+    pub fn restore(&mut self, state: AudioState) {
+        self.channels = state.channels;
+        self.music = state.music;
+    }
+
+    /// Installs an effect on the output stage, `None` leaves the mix dry.
+    pub fn set_effect(&mut self, effect: Option<Box<dyn AudioEffect>>) {
+        self.effect = effect;
+    }
+
+    /// Fills `buffer` with mono samples in the range -1.0 to 1.0.
+    pub fn mix(&mut self, buffer: &mut [f32]) {
+        for out in buffer.iter_mut() {
+            let mut value = 0.0;
+            for slot in self.channels.iter_mut() {
+                if let Some(channel) = slot {
+                    match channel.next() {
+                        Some(sample) => value += sample,
+                        None => *slot = None,
+                    }
+                }
+            }
+            *out = value;
+        }
+
+        if let Some(effect) = self.effect.as_mut() {
+            effect.process(buffer);
+        }
+
+        for out in buffer.iter_mut() {
+            *out = out.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// A feedback delay line, enough to give a sense of space to the cave and
+/// arena scenes.
+pub struct Reverb {
+    delay: Vec<f32>,
+    position: usize,
+    feedback: f32,
+    mix: f32,
+}
+
+impl Reverb {
+    pub fn new(sample_rate: u32, delay_ms: u32, feedback: f32) -> Self {
+        let len = (sample_rate as u64 * delay_ms as u64 / 1000).max(1) as usize;
+        Self {
+            delay: vec![0.0; len],
+            position: 0,
+            feedback: feedback.clamp(0.0, 0.95),
+            mix: 0.3,
+        }
+    }
+
+    /// Sets the dry/wet balance, 0.0 is fully dry and 1.0 is fully wet.
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+}
+
+impl AudioEffect for Reverb {
+    fn process(&mut self, buffer: &mut [f32]) {
+        for sample in buffer.iter_mut() {
+            let delayed = self.delay[self.position];
+            self.delay[self.position] = *sample + delayed * self.feedback;
+            self.position = (self.position + 1) % self.delay.len();
+
+            *sample = *sample * (1.0 - self.mix) + delayed * self.mix;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn impulse() -> Sample {
+        let mut data = vec![0; 64];
+        data[0] = 127;
+        Sample::new(data, None)
+    }
+
+    fn render(mixer: &mut Mixer) -> Vec<f32> {
+        let mut buffer = vec![0.0; 64];
+        mixer.mix(&mut buffer);
+        buffer
+    }
+
+    fn sound_resource() -> Vec<u8> {
+        let mut data = vec![0x00, 0x20, 0x00, 0x00, 0, 0, 0, 0];
+        data.extend([0x40; 0x40]);
+        data
+    }
+
+    fn play(channel: u8, volume: u8) -> PlaySoundCommand {
+        PlaySoundCommand {
+            resource_id: 0x10,
+            frequency: 39,
+            volume,
+            channel,
+        }
+    }
+
+    #[test]
+    fn zero_volume_stops_channel() {
+        let data = sound_resource();
+        let mut mixer = Mixer::new(DEFAULT_SAMPLE_RATE);
+
+        mixer.play_sound(play(1, MAX_VOLUME), Some(&data));
+        assert!(mixer.is_playing(1));
+        assert!(render(&mut mixer).iter().any(|&s| s != 0.0));
+
+        mixer.play_sound(play(1, 0), Some(&data));
+        assert!(!mixer.is_playing(1));
+        assert!(render(&mut mixer).iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn retrigger_replaces_channel() {
+        let data = sound_resource();
+        let mut mixer = Mixer::new(DEFAULT_SAMPLE_RATE);
+
+        mixer.play_sound(play(2, MAX_VOLUME), Some(&data));
+        render(&mut mixer);
+        mixer.play_sound(play(6, 0x10), Some(&data));
+        let quiet = render(&mut mixer);
+
+        assert!(mixer.is_playing(2));
+        assert!(quiet.iter().all(|&s| (s - 0.5 * 16.0 / 63.0).abs() < 1e-6));
+        mixer.play_sound(play(2, MAX_VOLUME), None);
+        assert!(mixer.is_playing(2));
+    }
+
+    fn music_resource(delay: u16) -> Vec<u8> {
+        let mut data = vec![0; MUSIC_PATTERN_OFFSET + 0x400];
+        data[0..2].copy_from_slice(&delay.to_be_bytes());
+        data[MUSIC_ORDER_COUNT_OFFSET..MUSIC_ORDER_COUNT_OFFSET + 2]
+            .copy_from_slice(&3u16.to_be_bytes());
+        data
+    }
+
+    fn play_music(resource_id: u16, delay: u16, position: u8) -> PlayMusicCommand {
+        PlayMusicCommand {
+            resource_id,
+            delay,
+            position,
+        }
+    }
+
+    #[test]
+    fn play_music_uses_module_delay_unless_overridden() {
+        let data = music_resource(0x3a98);
+        let mut music = MusicSequencer::new();
+
+        music.play_music(play_music(0x07, 0, 0), Some(&data));
+        assert!(music.is_playing());
+        assert_eq!(music.resource_id(), Some(0x07));
+        assert_eq!(music.delay(), 0x3a98);
+        assert_eq!(music.row_duration_ms(), 127);
+
+        music.play_music(play_music(0x07, 0x1d4c, 0), Some(&data));
+        assert_eq!(music.delay(), 0x1d4c);
+        assert_eq!(music.row_duration_ms(), 63);
+    }
+
+    #[test]
+    fn play_music_starts_at_position() {
+        let data = music_resource(0x3a98);
+        let mut music = MusicSequencer::new();
+
+        music.play_music(play_music(0x07, 0, 2), Some(&data));
+        assert_eq!(music.position(), 2);
+        assert_eq!(music.row(), 0);
+        assert_eq!(music.module().map(|m| m.order_count()), Some(3));
+    }
+
+    #[test]
+    fn play_music_without_resource_retimes_or_stops() {
+        let data = music_resource(0x3a98);
+        let mut music = MusicSequencer::new();
+        music.play_music(play_music(0x07, 0, 1), Some(&data));
+
+        music.play_music(play_music(0, 0x1d4c, 0), None);
+        assert!(music.is_playing());
+        assert_eq!(music.resource_id(), Some(0x07));
+        assert_eq!(music.position(), 1);
+        assert_eq!(music.delay(), 0x1d4c);
+
+        music.play_music(play_music(0, 0, 0), None);
+        assert!(!music.is_playing());
+
+        music.play_music(play_music(0x08, 0, 0), None);
+        assert_eq!(music.resource_id(), Some(0x07));
+    }
+
+    #[test]
+    fn music_rows_advance_with_time_and_report_markers() {
+        let mut data = music_resource(2350);
+        // pattern 0, row 2, channel 3 holds marker 7
+        let note = MUSIC_PATTERN_OFFSET + 2 * MUSIC_ROW_LEN + 12;
+        data[note..note + 4].copy_from_slice(&[0xff, 0xfd, 0x00, 0x07]);
+        let mut music = MusicSequencer::new();
+        music.play_music(play_music(0x07, 0, 0), Some(&data));
+        assert_eq!(music.row_duration_ms(), 20);
+
+        assert_eq!(music.advance(19), None);
+        assert_eq!(music.row(), 0);
+        assert_eq!(music.advance(21), None);
+        assert_eq!(music.row(), 2);
+        assert_eq!(music.advance(20), Some(7));
+        assert_eq!(music.row(), 3);
+
+        // three orders of 64 rows, the first two rows of the last order are left
+        music.advance(20 * (64 * 3 - 5));
+        assert!(music.is_playing());
+        assert_eq!((music.position(), music.row()), (2, 62));
+        music.advance(40);
+        assert!(!music.is_playing());
+        assert_eq!(music.advance(20), None);
+    }
+
+    #[test]
+    fn reverb_changes_output() {
+        let mut dry = Mixer::new(1000);
+        dry.play(0, impulse(), 1000, MAX_VOLUME);
+        let dry = render(&mut dry);
+
+        let mut wet = Mixer::new(1000);
+        wet.set_effect(Some(Box::new(Reverb::new(1000, 10, 0.5))));
+        wet.play(0, impulse(), 1000, MAX_VOLUME);
+        let wet = render(&mut wet);
+
+        assert_ne!(dry, wet);
+        assert_eq!(dry[10], 0.0);
+        assert!(wet[10] > 0.0);
+    }
+
+    #[test]
+    fn reverb_mix_controls_dry_wet_balance() {
+        let mut dry_reverb = Reverb::new(1000, 10, 0.5);
+        dry_reverb.set_mix(0.0);
+        let mut mixer = Mixer::new(1000);
+        mixer.set_effect(Some(Box::new(dry_reverb)));
+        mixer.play(0, impulse(), 1000, MAX_VOLUME);
+        let dry = render(&mut mixer);
+        assert!(dry[0] > 0.9);
+        assert!(dry[1..].iter().all(|&s| s == 0.0));
+
+        let mut wet_reverb = Reverb::new(1000, 10, 0.5);
+        wet_reverb.set_mix(1.0);
+        let mut mixer = Mixer::new(1000);
+        mixer.set_effect(Some(Box::new(wet_reverb)));
+        mixer.play(0, impulse(), 1000, MAX_VOLUME);
+        let wet = render(&mut mixer);
+        assert_eq!(wet[0], 0.0);
+        assert!(wet[10] > 0.9);
+    }
+}
@@ -0,0 +1,63 @@
+//! Game time for the executor, kept apart from the OS clock so runs can be replayed
+//! and batched without waiting on real time.
+
+use std::time::{Duration, Instant};
+
+/// A source of elapsed time, advanced by the executor by each frame's blit duration.
+pub trait Clock {
+    /// Time elapsed since the clock was created.
+    fn now(&self) -> Duration;
+
+    /// Called once per blit with the time the frame is shown for.
+    fn advance(&mut self, duration: Duration);
+}
+
+/// Wall clock time. Frontends wait out each frame themselves, so `advance` is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct RealClock {
+    start: Instant,
+}
+
+impl RealClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn advance(&mut self, _duration: Duration) {}
+}
+
+/// Logical time that only moves when the executor blits, for headless and replayed runs.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VirtualClock {
+    elapsed: Duration,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        self.elapsed
+    }
+
+    fn advance(&mut self, duration: Duration) {
+        self.elapsed += duration;
+    }
+}
@@ -0,0 +1,170 @@
+use crate::resources::{GamePart, GameVersion};
+use crate::vm::VarId;
+
+/// Variable the part bootstrap reads to pick which checkpoint to start from.
+pub const CHECKPOINT_VARIABLE: VarId = VarId(0x00);
+
+/// Where a code resumes play: the part to load and the checkpoint within it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub part: GamePart,
+    pub checkpoint: i16,
+}
+
+impl Checkpoint {
+    /// Variables to set after the part has been loaded.
+    pub fn variables(&self) -> [(VarId, i16); 1] {
+        [(CHECKPOINT_VARIABLE, self.checkpoint)]
+    }
+}
+
+const fn code(part: GamePart, checkpoint: i16) -> Checkpoint {
+    Checkpoint { part, checkpoint }
+}
+
+/// Every place the game can restart from, as the part and the value of
+/// `CHECKPOINT_VARIABLE`. Taken from the `_restartPos` table in rawgl's `engine.cpp`,
+/// where parts are numbered from 16000; the first two entries are the password screen
+/// and the intro.
+pub const RESTART_POSITIONS: [Checkpoint; 36] = [
+    code(GamePart::Nine, 0),
+    code(GamePart::Two, 0),
+    code(GamePart::Three, 10),
+    code(GamePart::Three, 12),
+    code(GamePart::Three, 14),
+    code(GamePart::Four, 20),
+    code(GamePart::Four, 24),
+    code(GamePart::Four, 26),
+    code(GamePart::Five, 30),
+    code(GamePart::Five, 31),
+    code(GamePart::Five, 32),
+    code(GamePart::Five, 33),
+    code(GamePart::Five, 34),
+    code(GamePart::Five, 35),
+    code(GamePart::Five, 36),
+    code(GamePart::Five, 37),
+    code(GamePart::Five, 38),
+    code(GamePart::Five, 39),
+    code(GamePart::Five, 40),
+    code(GamePart::Five, 41),
+    code(GamePart::Five, 42),
+    code(GamePart::Five, 43),
+    code(GamePart::Five, 44),
+    code(GamePart::Five, 45),
+    code(GamePart::Five, 46),
+    code(GamePart::Five, 47),
+    code(GamePart::Five, 48),
+    code(GamePart::Five, 49),
+    code(GamePart::Seven, 64),
+    code(GamePart::Seven, 65),
+    code(GamePart::Seven, 66),
+    code(GamePart::Seven, 67),
+    code(GamePart::Seven, 68),
+    code(GamePart::Six, 50),
+    code(GamePart::Seven, 60),
+    code(GamePart::Eight, 0),
+];
+
+/// Codes shown by the DOS release, in the order they are given. Restart positions are
+/// listed in play order too, so the nth code resumes at the nth position after the
+/// password screen and the intro. The 15th Anniversary Edition accepts the same codes.
+pub const DOS_CODES: [(&str, Checkpoint); 16] = [
+    ("LDKD", RESTART_POSITIONS[2]),
+    ("HTDC", RESTART_POSITIONS[3]),
+    ("CLLD", RESTART_POSITIONS[4]),
+    ("LBKG", RESTART_POSITIONS[5]),
+    ("XDDJ", RESTART_POSITIONS[6]),
+    ("FXLC", RESTART_POSITIONS[7]),
+    ("KRFK", RESTART_POSITIONS[8]),
+    ("KLFB", RESTART_POSITIONS[9]),
+    ("TTCT", RESTART_POSITIONS[10]),
+    ("DDRX", RESTART_POSITIONS[11]),
+    ("TBHK", RESTART_POSITIONS[12]),
+    ("BRTD", RESTART_POSITIONS[13]),
+    ("CKJL", RESTART_POSITIONS[14]),
+    ("LFCK", RESTART_POSITIONS[15]),
+    ("BFLX", RESTART_POSITIONS[16]),
+    ("XJRT", RESTART_POSITIONS[17]),
+];
+
+pub const CODE_LEN: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeError {
+    InvalidLength(usize),
+    InvalidCharacter(char),
+    Unknown(String),
+}
+
+impl std::error::Error for CodeError {}
+
+impl std::fmt::Display for CodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeError::InvalidLength(len) => {
+                write!(f, "codes are {} letters, got {}", CODE_LEN, len)
+            }
+            CodeError::InvalidCharacter(c) => write!(f, "invalid character in code: {:?}", c),
+            CodeError::Unknown(code) => write!(f, "unknown code: {}", code),
+        }
+    }
+}
+
+/// Finds the checkpoint for `code`, ignoring case and surrounding whitespace.
+pub fn lookup(version: GameVersion, code: &str) -> Result<Checkpoint, CodeError> {
+    let code = code.trim().to_ascii_uppercase();
+
+    if let Some(c) = code.chars().find(|c| !c.is_ascii_alphabetic()) {
+        return Err(CodeError::InvalidCharacter(c));
+    }
+
+    if code.len() != CODE_LEN {
+        return Err(CodeError::InvalidLength(code.len()));
+    }
+
+    let codes = match version {
+        GameVersion::Dos | GameVersion::FifteenthAnniversary => &DOS_CODES,
+    };
+
+    codes
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, checkpoint)| *checkpoint)
+        .ok_or(CodeError::Unknown(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_validates_codes() {
+        assert_eq!(
+            lookup(GameVersion::Dos, " ldkd "),
+            Ok(code(GamePart::Three, 10))
+        );
+        assert_eq!(
+            lookup(GameVersion::Dos, "LDK"),
+            Err(CodeError::InvalidLength(3))
+        );
+        assert_eq!(
+            lookup(GameVersion::Dos, "LD1D"),
+            Err(CodeError::InvalidCharacter('1'))
+        );
+        assert_eq!(
+            lookup(GameVersion::Dos, "AAAA"),
+            Err(CodeError::Unknown("AAAA".to_string()))
+        );
+    }
+
+    #[test]
+    fn codes_resume_at_restart_positions() {
+        let dos = |c| lookup(GameVersion::Dos, c).unwrap();
+
+        assert_eq!(dos("HTDC"), code(GamePart::Three, 12));
+        assert_eq!(dos("LBKG"), code(GamePart::Four, 20));
+        assert_eq!(dos("FXLC"), code(GamePart::Four, 26));
+        assert_eq!(dos("KRFK"), code(GamePart::Five, 30));
+        assert_eq!(dos("XJRT"), code(GamePart::Five, 39));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::input::{Input, InputState};
+
+use std::cell::Cell;
+
+const UP: u8 = 0x01;
+const DOWN: u8 = 0x02;
+const LEFT: u8 = 0x04;
+const RIGHT: u8 = 0x08;
+const ACTION: u8 = 0x10;
+
+/// Input captured once per VM frame step, for replaying a play session. Serialized as
+/// one byte per step: up 0x01, down 0x02, left 0x04, right 0x08, action 0x10.
+/// Turbo and rewind are frontend controls and are not recorded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InputRecording {
+    steps: Vec<u8>,
+}
+
+impl InputRecording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            steps: bytes.to_vec(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.steps
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn push(&mut self, input: InputState) {
+        let mut step = 0;
+        for (held, bit) in [
+            (input.up, UP),
+            (input.down, DOWN),
+            (input.left, LEFT),
+            (input.right, RIGHT),
+            (input.action, ACTION),
+        ] {
+            if held {
+                step |= bit;
+            }
+        }
+
+        self.steps.push(step);
+    }
+
+    pub fn get(&self, step: usize) -> Option<InputState> {
+        let step = *self.steps.get(step)?;
+
+        Some(InputState {
+            up: step & UP != 0,
+            down: step & DOWN != 0,
+            left: step & LEFT != 0,
+            right: step & RIGHT != 0,
+            action: step & ACTION != 0,
+            ..Default::default()
+        })
+    }
+}
+
+/// Plays a recording back once the player has been idle for `timeout_ms` of game time.
+#[derive(Debug, Clone)]
+pub struct AttractMode {
+    recording: InputRecording,
+    timeout_ms: u64,
+    idle_ms: u64,
+    step: Option<usize>,
+}
+
+impl AttractMode {
+    pub fn new(recording: InputRecording, timeout_ms: u64) -> Self {
+        Self {
+            recording,
+            timeout_ms,
+            idle_ms: 0,
+            step: None,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.step.is_some()
+    }
+
+    /// Accounts `ms` of game time, returns `true` when playback should begin.
+    pub fn idle(&mut self, ms: u64) -> bool {
+        if self.is_playing() || self.recording.is_empty() {
+            return false;
+        }
+
+        self.idle_ms += ms;
+        self.idle_ms >= self.timeout_ms
+    }
+
+    pub fn reset_idle(&mut self) {
+        self.idle_ms = 0;
+    }
+
+    pub fn start(&mut self) {
+        self.idle_ms = 0;
+        self.step = Some(0);
+    }
+
+    pub fn stop(&mut self) {
+        self.idle_ms = 0;
+        self.step = None;
+    }
+
+    /// The next recorded input, `None` once the recording is exhausted.
+    pub fn next_input(&mut self) -> Option<InputState> {
+        let step = self.step?;
+        let input = self.recording.get(step)?;
+        self.step = Some(step + 1);
+
+        Some(input)
+    }
+}
+
+/// Feeds a recording to an `Executor` as its input, one step per VM frame, for headless
+/// runs that must see the same input every time. Nothing is held once it runs out.
+#[derive(Debug, Default, Clone)]
+pub struct PlaybackInput {
+    recording: InputRecording,
+    step: Cell<usize>,
+}
+
+impl PlaybackInput {
+    pub fn new(recording: InputRecording) -> Self {
+        Self {
+            recording,
+            step: Cell::new(0),
+        }
+    }
+}
+
+impl Input for PlaybackInput {
+    fn get_input(&self) -> InputState {
+        let step = self.step.get();
+        self.step.set(step + 1);
+        self.recording.get(step).unwrap_or_default()
+    }
+}
@@ -0,0 +1,202 @@
+use crate::executor::Executor;
+use crate::gfx::Gfx;
+use crate::input::Input;
+use crate::resources::Io;
+use crate::video::VideoCommand;
+
+use std::fmt::{self, Display};
+
+const FRAME_MARKER: &str = "frame";
+
+/// The video commands an `Executor` issued, grouped by the `run` call that issued them.
+/// Two runs of the same game with the same seed and input should produce identical logs,
+/// so comparing them finds where a change to the engine altered behavior.
+///
+/// As text each frame is a `frame` line followed by one `Display` formatted command per line.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommandLog {
+    frames: Vec<Vec<String>>,
+    current: Vec<String>,
+}
+
+impl CommandLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_command(&mut self, command: &VideoCommand) {
+        self.current.push(command.to_string());
+    }
+
+    /// Closes the frame being logged, commands pushed afterwards belong to the next one.
+    pub fn end_frame(&mut self) {
+        self.frames.push(std::mem::take(&mut self.current));
+    }
+
+    /// Completed frames, commands after the last `end_frame` are not counted.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut log = Self::new();
+        let mut started = false;
+
+        for line in text.lines() {
+            if line == FRAME_MARKER {
+                if started {
+                    log.end_frame();
+                }
+                started = true;
+            } else if !line.is_empty() {
+                log.current.push(line.to_string());
+            }
+        }
+
+        if started {
+            log.end_frame();
+        }
+
+        log
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for frame in &self.frames {
+            text.push_str(FRAME_MARKER);
+            text.push('\n');
+            for command in frame {
+                text.push_str(command);
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+
+    /// The first command that differs between the two logs, `None` when they match.
+    pub fn first_divergence(&self, other: &CommandLog) -> Option<Divergence> {
+        let frames = self.frames.len().max(other.frames.len());
+
+        for frame in 0..frames {
+            let left = self.frames.get(frame);
+            let right = other.frames.get(frame);
+            let (left, right) = match (left, right) {
+                (Some(left), Some(right)) => (left, right),
+                _ => {
+                    return Some(Divergence {
+                        frame,
+                        command: 0,
+                        left: left.map(|_| FRAME_MARKER.to_string()),
+                        right: right.map(|_| FRAME_MARKER.to_string()),
+                    })
+                }
+            };
+
+            for command in 0..left.len().max(right.len()) {
+                let left = left.get(command);
+                let right = right.get(command);
+                if left != right {
+                    return Some(Divergence {
+                        frame,
+                        command,
+                        left: left.cloned(),
+                        right: right.cloned(),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Where two command logs first disagree. A side is `None` when its log ended first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub frame: usize,
+    pub command: usize,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |command: &Option<String>| {
+            command
+                .clone()
+                .unwrap_or_else(|| String::from("<end of log>"))
+        };
+
+        write!(
+            f,
+            "frame {} command {}:\n  < {}\n  > {}",
+            self.frame,
+            self.command,
+            side(&self.left),
+            side(&self.right)
+        )
+    }
+}
+
+/// Runs `executor` for `frames` frames and returns the commands it issued.
+pub fn record_commands<I: Io, G: Gfx, In: Input>(
+    executor: &mut Executor<I, G, In>,
+    frames: usize,
+) -> CommandLog {
+    executor.start_command_log();
+    for _ in 0..frames {
+        executor.run();
+    }
+    executor.take_command_log().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::{BlitCommand, FillVideoPageCommand};
+
+    fn log(frames: &[&[VideoCommand]]) -> CommandLog {
+        let mut log = CommandLog::new();
+        for frame in frames {
+            for command in frame.iter() {
+                log.push_command(command);
+            }
+            log.end_frame();
+        }
+        log
+    }
+
+    const FILL: VideoCommand = VideoCommand::FillVideoPage(FillVideoPageCommand {
+        page_id: 0,
+        color: 1,
+    });
+    const BLIT: VideoCommand = VideoCommand::Blit(BlitCommand { page_id: 0xff });
+
+    #[test]
+    fn text_round_trip() {
+        let log = log(&[&[FILL, BLIT], &[], &[BLIT]]);
+        assert_eq!(CommandLog::from_text(&log.to_text()), log);
+        assert_eq!(log.len(), 3);
+    }
+
+    #[test]
+    fn first_divergence_finds_changed_and_missing_commands() {
+        let a = log(&[&[FILL, BLIT], &[BLIT]]);
+        assert_eq!(a.first_divergence(&a.clone()), None);
+
+        let b = log(&[&[FILL, BLIT], &[FILL, BLIT]]);
+        let divergence = a.first_divergence(&b).unwrap();
+        assert_eq!((divergence.frame, divergence.command), (1, 0));
+        assert_eq!(divergence.left, Some(BLIT.to_string()));
+        assert_eq!(divergence.right, Some(FILL.to_string()));
+
+        let c = log(&[&[FILL, BLIT]]);
+        let divergence = a.first_divergence(&c).unwrap();
+        assert_eq!((divergence.frame, divergence.right.as_ref()), (1, None));
+    }
+}
@@ -4,7 +4,32 @@ pub enum Error {
     InvalidMemEntryState(u8),
     InvalidBankId(u8),
     CrcCheckFailed,
+    CorruptPackedData,
     InputBufferDrained,
+    UnknownGameVersion,
+    ProgramCounterOutOfRange(usize),
+    InvalidOpcode(u8),
+    InvalidJmpCondition(u8),
+    InvalidThreadId(u8),
+    InvalidThreadRange(u8, u8),
+    StackOverflow,
+    StackUnderflow,
+    StepLimitExceeded,
+    MissingPartData,
+    /// A replacement font whose size is not 96 glyphs of 8 bytes each
+    InvalidFontSize(usize),
+    /// A file whose loads kept failing with transient errors, with the attempts made and
+    /// the last error, see `retry::RetryIo`
+    RetriesExhausted(String, u32, std::io::Error),
+    /// A bytecode patch at an offset, of a length, that does not fit in the part's
+    /// bytecode of the given size
+    PatchOutOfRange(usize, usize, usize),
+    /// A resource id that is a game part or past the last entry where an entry was expected
+    InvalidEntryId(u16),
+    /// An entry of a known type other than sound where a sound was expected
+    NotASound(u16),
+    /// An entry that failed to load
+    MissingEntryData(u16),
 }
 
 impl std::error::Error for Error {}
@@ -14,6 +39,40 @@ impl std::fmt::Display for Error {
         match self {
             Error::Io(err) => write!(f, "{}", err),
             Error::InvalidMemEntryState(value) => write!(f, "invalid mem entry state: {}", value),
+            Error::InvalidBankId(value) => write!(f, "invalid bank id: {}", value),
+            Error::CorruptPackedData => write!(f, "packed data references outside its buffer"),
+            Error::UnknownGameVersion => write!(f, "unable to detect game data version"),
+            Error::ProgramCounterOutOfRange(address) => {
+                write!(f, "program counter outside of bytecode: {:#x}", address)
+            }
+            Error::InvalidOpcode(op) => write!(f, "invalid opcode: {:#x}", op),
+            Error::InvalidJmpCondition(op) => write!(f, "invalid jmp condition: {:#x}", op),
+            Error::InvalidThreadId(id) => write!(f, "invalid thread id: {}", id),
+            Error::InvalidThreadRange(start, end) => {
+                write!(f, "invalid thread reset range: {} {}", start, end)
+            }
+            Error::StackOverflow => write!(f, "stack overflow"),
+            Error::StackUnderflow => write!(f, "stack underflow"),
+            Error::StepLimitExceeded => write!(f, "instruction step limit exceeded"),
+            Error::MissingPartData => write!(f, "game part data missing or failed to load"),
+            Error::InvalidFontSize(size) => {
+                write!(f, "font must be 768 bytes, 8 per glyph, found {}", size)
+            }
+            Error::RetriesExhausted(name, attempts, err) => {
+                write!(
+                    f,
+                    "unable to load {} after {} attempts: {}",
+                    name, attempts, err
+                )
+            }
+            Error::PatchOutOfRange(offset, len, size) => write!(
+                f,
+                "bytecode patch of {} bytes at {:#x} does not fit in {} bytes of bytecode",
+                len, offset, size
+            ),
+            Error::InvalidEntryId(id) => write!(f, "resource {:#x} is not an entry", id),
+            Error::NotASound(id) => write!(f, "resource {:#x} is not a sound", id),
+            Error::MissingEntryData(id) => write!(f, "resource {:#x} failed to load", id),
             _ => write!(f, "unknown error"),
         }
     }
@@ -0,0 +1,115 @@
+//! Milestones in a play session, for frontends that keep statistics or achievements.
+
+use crate::codes::Checkpoint;
+use crate::resources::GamePart;
+
+/// Parts where the hero is playable, the only parts a death can happen in.
+pub const GAMEPLAY_PARTS: [GamePart; 6] = [
+    GamePart::Three,
+    GamePart::Four,
+    GamePart::Five,
+    GamePart::Six,
+    GamePart::Seven,
+    GamePart::Eight,
+];
+
+/// The last part of the game, the ending plays at the end of it.
+pub const FINAL_PART: GamePart = GamePart::Eight;
+
+/// The password screen, the code key opens it from any part.
+pub const PASSWORD_PART: GamePart = GamePart::Nine;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    /// The hero died and the current part is being restarted from its checkpoint
+    Died,
+    /// The scripts moved on to a new part
+    ReachedPart(GamePart),
+    /// A checkpoint code was entered through `Executor::enter_code`
+    EnteredCode(Checkpoint),
+    CompletedGame,
+}
+
+impl GameEvent {
+    /// Classifies a part change requested by the scripts. There is no death flag in the
+    /// game's variables, instead the scripts respawn the hero by requesting the gameplay
+    /// part that is already loaded, which restarts it at the checkpoint held in variable
+    /// 0. The only way out of the final part other than the password screen is back to
+    /// the start of the game, the copy protection screen or the intro, once the ending
+    /// has played.
+    pub fn from_part_change(previous: Option<GamePart>, next: GamePart) -> Self {
+        match previous {
+            Some(previous) if previous == next && GAMEPLAY_PARTS.contains(&next) => GameEvent::Died,
+            Some(FINAL_PART) if matches!(next, GamePart::One | GamePart::Two) => {
+                GameEvent::CompletedGame
+            }
+            _ => GameEvent::ReachedPart(next),
+        }
+    }
+}
+
+impl std::fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameEvent::Died => write!(f, "died"),
+            GameEvent::ReachedPart(part) => write!(f, "reached part {:?}", part),
+            GameEvent::EnteredCode(checkpoint) => write!(
+                f,
+                "entered code for checkpoint {} of part {:?}",
+                checkpoint.checkpoint, checkpoint.part
+            ),
+            GameEvent::CompletedGame => write!(f, "completed game"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_changes_are_classified() {
+        let change = GameEvent::from_part_change;
+
+        assert_eq!(
+            change(Some(GamePart::Five), GamePart::Five),
+            GameEvent::Died
+        );
+        assert_eq!(change(Some(FINAL_PART), FINAL_PART), GameEvent::Died);
+        assert_eq!(
+            change(Some(GamePart::Five), GamePart::Six),
+            GameEvent::ReachedPart(GamePart::Six)
+        );
+        assert_eq!(
+            change(Some(FINAL_PART), GamePart::Two),
+            GameEvent::CompletedGame
+        );
+        assert_eq!(
+            change(Some(FINAL_PART), GamePart::One),
+            GameEvent::CompletedGame
+        );
+    }
+
+    #[test]
+    fn the_password_screen_is_neither_a_death_nor_the_ending() {
+        let change = GameEvent::from_part_change;
+
+        assert_eq!(
+            change(Some(FINAL_PART), PASSWORD_PART),
+            GameEvent::ReachedPart(PASSWORD_PART)
+        );
+        assert_eq!(
+            change(Some(PASSWORD_PART), PASSWORD_PART),
+            GameEvent::ReachedPart(PASSWORD_PART)
+        );
+        assert_eq!(
+            change(Some(PASSWORD_PART), FINAL_PART),
+            GameEvent::ReachedPart(FINAL_PART)
+        );
+        // reloading the intro is not a death either
+        assert_eq!(
+            change(Some(GamePart::Two), GamePart::Two),
+            GameEvent::ReachedPart(GamePart::Two)
+        );
+    }
+}
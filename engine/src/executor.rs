@@ -1,66 +1,1744 @@
-use crate::gfx::Gfx;
-use crate::input::Input;
-use crate::resources::{GamePart, Io, Resources};
-use crate::video::Video;
-use crate::vm::{FrameResult, Vm, Yield};
+use crate::audio::{AudioCommand, AudioState, Mixer, DEFAULT_SAMPLE_RATE};
+use crate::clock::{Clock, RealClock};
+use crate::codes::{self, CodeError};
+use crate::demo::{AttractMode, InputRecording};
+use crate::desync::CommandLog;
+use crate::error::Error;
+use crate::events::GameEvent;
+use crate::gfx::{Gfx, PageSnapshot};
+use crate::input::{Input, InputState};
+use crate::options::EngineOptions;
+use crate::resources::{GamePart, Io, LoadProgress, Resources};
+use crate::rewind::RewindBuffer;
+use crate::softlock::SoftlockDetector;
+use crate::video::{self, Video, VideoCommand, VideoState};
+use crate::vm::{vars, FrameResult, Pc, ThreadActivity, ThreadId, VarId, Vm, Yield};
+use crate::watch::VariableWatch;
 
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// VM passes, each a frame or the part of one up to a resource load, run before giving up
+/// on reaching gameplay or a part's first blit.
+const SKIP_PASS_LIMIT: u64 = 100_000;
+
+/// Seconds without input before the attract demo starts, when not set in the options.
+pub const DEFAULT_ATTRACT_TIMEOUT: u64 = 60;
+
+/// Called with the newly loaded part each time the executor changes parts.
+pub type PartObserver = Box<dyn FnMut(GamePart) + Send>;
+
+/// Called with the frame number and the event for each `GameEvent`. Events are not
+/// reported while the attract demo is playing.
+pub type EventObserver = Box<dyn FnMut(u64, GameEvent) + Send>;
+
+/// Cumulative time spent in each stage of `Executor::run`, only collected
+/// once `Executor::enable_timings` has been called.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ExecutorTimings {
+    pub vm: Duration,
+    pub video: Duration,
+    pub resources: Duration,
+}
+
+/// What the most recent `Executor::run` call did, only collected once
+/// `Executor::enable_timings` has been called.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FrameStats {
+    pub timings: ExecutorTimings,
+    pub polygons: u64,
+    pub fills: u32,
+    pub copies: u32,
+    pub strings: u32,
+    /// Fills and copies dropped by `video::coalesce`, not included in `fills` or `copies`
+    pub coalesced: u32,
+    pub bytes_loaded: u64,
+}
+
+impl std::fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "vm {}ms, video {}ms, resources {}ms, {} polygons, {} fills, {} copies, {} strings, {} coalesced, {} bytes loaded",
+            self.timings.vm.as_millis(),
+            self.timings.video.as_millis(),
+            self.timings.resources.as_millis(),
+            self.polygons,
+            self.fills,
+            self.copies,
+            self.strings,
+            self.coalesced,
+            self.bytes_loaded
+        )
+    }
+}
+
+/// A snapshot of the executor taken between frames, see `Executor::save_state`.
+#[derive(Debug, Clone)]
+pub struct ExecutorState {
+    vm: Vm,
+    video: VideoState,
+    audio: AudioState,
+    pages: Option<PageSnapshot>,
+    part: Option<GamePart>,
+    frame: u64,
+}
+
+/// How an `Executor::run_until` call ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RunUntilResult {
+    /// The predicate held after this many `run` calls, 0 when it held before the first.
+    Satisfied(u64),
+    /// The predicate still did not hold after the maximum number of `run` calls.
+    LimitReached,
+}
+
+/// Runs the game on top of an `Io`, `Gfx` and `Input`.
+///
+/// All engine state lives in the executor, there are no globals, so any number of
+/// executors can run side by side in one process, for example to compare two runs frame
+/// by frame. An executor is `Send` whenever its `Io`, `Gfx` and `Input` are, so each can
+/// be moved to a thread of its own. Its methods take `&mut self` for anything that
+/// advances the game, so sharing one executor between threads needs a lock around it.
 pub struct Executor<I: Io, G: Gfx, In: Input> {
     vm: Vm,
     video: Video<G>,
     resources: Resources<I>,
     input: In,
     frame: u64,
+    last_input: InputState,
+    clock: Box<dyn Clock + Send>,
+    rewind: RewindBuffer,
+    timings: Option<ExecutorTimings>,
+    frame_stats: Option<FrameStats>,
+    mixer: Arc<Mutex<Mixer>>,
+    start_part: GamePart,
+    attract: Option<AttractMode>,
+    attract_resume: Option<ExecutorState>,
+    recording: Option<InputRecording>,
+    command_log: Option<CommandLog>,
+    coalesce: bool,
+    thread_render_mask: u64,
+    scroll_override: Option<i16>,
+    command_buffer: Vec<VideoCommand>,
+    part_observer: Option<PartObserver>,
+    event_observer: Option<EventObserver>,
+    load_budget: Option<usize>,
+    loading: bool,
+    /// Part the scripts switched away from, while the part they asked for is loading
+    pending_part: Option<Option<GamePart>>,
+    blit_budget: Option<usize>,
+    rapid_blits: bool,
+    watch: Option<VariableWatch>,
+    softlock: Option<SoftlockDetector>,
 }
 
 impl<I: Io, G: Gfx, In: Input> Executor<I, G, In> {
-    pub fn new(io: I, gfx: G, input: In, bypass: bool) -> Self {
+    pub fn new(io: I, gfx: G, input: In, bypass: bool) -> Result<Self, Error> {
         let video = Video::new(gfx);
-        let vm = Vm::new(bypass);
-        let mut resources = Resources::load(io).unwrap();
+        let mut resources = Resources::load(io)?;
+        let vm = Vm::new(resources.version(), bypass);
 
-        if bypass {
-            resources.prepare_part(GamePart::Two);
-        } else {
-            resources.prepare_part(GamePart::One);
-        }
+        let start_part = if bypass { GamePart::Two } else { GamePart::One };
+        resources.prepare_part(start_part);
 
-        Self {
+        Ok(Self {
             vm,
             video,
             resources,
             input,
             frame: 0,
+            last_input: InputState::default(),
+            clock: Box::new(RealClock::new()),
+            rewind: RewindBuffer::default(),
+            timings: None,
+            frame_stats: None,
+            mixer: Arc::new(Mutex::new(Mixer::new(DEFAULT_SAMPLE_RATE))),
+            start_part,
+            attract: None,
+            attract_resume: None,
+            recording: None,
+            command_log: None,
+            coalesce: false,
+            thread_render_mask: u64::MAX,
+            scroll_override: None,
+            command_buffer: Vec::new(),
+            part_observer: None,
+            event_observer: None,
+            load_budget: None,
+            pending_part: None,
+            loading: false,
+            blit_budget: None,
+            rapid_blits: false,
+            watch: None,
+            softlock: None,
+        })
+    }
+
+    /// Creates an executor and applies the engine level settings from `options`.
+    pub fn with_options(io: I, gfx: G, input: In, options: &EngineOptions) -> Result<Self, Error> {
+        let demo = match options.attract_demo.as_ref() {
+            Some(name) => {
+                let mut bytes = Vec::new();
+                io.load(name)?.read_to_end(&mut bytes)?;
+                Some(InputRecording::from_bytes(&bytes))
+            }
+            None => None,
+        };
+
+        let mut executor = Self::new(io, gfx, input, options.bypass_protection)?;
+
+        if let Some(tick_ms) = options.tick_ms {
+            executor.set_tick_duration(tick_ms);
+        }
+
+        if let Some(limit) = options.step_limit {
+            executor.set_step_limit(Some(limit).filter(|&limit| limit > 0));
         }
+        if let Some(cap) = options.command_cap {
+            executor.set_command_cap(Some(cap).filter(|&cap| cap > 0));
+        }
+
+        executor.set_load_budget(options.load_budget);
+        executor.set_blit_budget(options.blit_budget);
+        executor.set_memory_budget(options.memory_budget);
+        executor.set_coalesce(options.coalesce);
+        executor.watch_variables(&options.watch_variables);
+        executor.set_softlock_timeout(
+            options
+                .softlock_timeout
+                .filter(|&timeout| timeout > 0)
+                .map(|timeout| timeout * 1000),
+        );
+
+        if let Some(part) = options.start_part {
+            executor.jump_to_part(part);
+        }
+
+        if let Some(code) = options.code.as_ref() {
+            if let Err(err) = executor.enter_code(code) {
+                log::warn!("{}", err);
+            }
+        }
+
+        // after the code, as entering one clears the variables
+        if let Some(seed) = options.seed {
+            executor.set_variable(vars::RANDOM_SEED, seed);
+        }
+
+        let timeout = options.attract_timeout.unwrap_or(DEFAULT_ATTRACT_TIMEOUT);
+        executor.set_attract_demo(demo, timeout * 1000);
+
+        if options.skip_intro && !executor.skip_to_gameplay() {
+            log::warn!("unable to skip intro");
+        }
+
+        Ok(executor)
+    }
+
+    /// The mixer fed by PlaySound, frontends pull samples from it on their audio thread.
+    pub fn mixer(&self) -> Arc<Mutex<Mixer>> {
+        self.mixer.clone()
+    }
+
+    pub fn get_variable(&self, variable_id: VarId) -> i16 {
+        self.vm.get_variable(variable_id)
     }
 
+    pub fn set_variable(&mut self, variable_id: VarId, value: i16) {
+        self.vm.set_variable(variable_id, value)
+    }
+
+    /// Loads `part` and restarts its threads, leaving variables untouched.
+    pub fn jump_to_part(&mut self, part: GamePart) {
+        self.prepare_part(part);
+        self.vm.init_part();
+        if let Some(softlock) = self.softlock.as_mut() {
+            softlock.reset();
+        }
+    }
+
+    /// Restarts the loaded part from the checkpoint in variable 0, the same way the
+    /// scripts respawn the hero, for getting out of a softlock.
+    pub fn reload_checkpoint(&mut self) {
+        if let Some(part) = self.resources.loaded_part() {
+            self.jump_to_part(part);
+        }
+    }
+
+    /// Resumes at the checkpoint for a code shown by the original game. Like a restart in
+    /// the original, variables are cleared back to their boot values first.
+    pub fn enter_code(&mut self, code: &str) -> Result<(), CodeError> {
+        let checkpoint = codes::lookup(self.resources.version(), code)?;
+
+        self.vm.reset();
+        self.jump_to_part(checkpoint.part);
+        for (variable_id, value) in checkpoint.variables() {
+            self.vm.set_variable(variable_id, value);
+        }
+        self.emit_event(GameEvent::EnteredCode(checkpoint));
+
+        Ok(())
+    }
+
+    pub fn set_tick_duration(&mut self, ms: u64) {
+        self.vm.set_tick_duration(ms)
+    }
+
+    /// Replaces the clock advanced by each blit, a `VirtualClock` makes `elapsed` count
+    /// game time only.
+    pub fn set_clock(&mut self, clock: Box<dyn Clock + Send>) {
+        self.clock = clock;
+    }
+
+    /// Time elapsed on the executor's clock, wall time unless replaced with `set_clock`.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Instructions allowed per VM frame before the running thread is killed, see
+    /// `Vm::set_step_limit`.
+    pub fn set_step_limit(&mut self, limit: Option<usize>) {
+        self.vm.set_step_limit(limit)
+    }
+
+    /// Video commands queued between blits before a warning is logged, see
+    /// `Vm::set_command_cap`.
+    pub fn set_command_cap(&mut self, cap: Option<usize>) {
+        self.vm.set_command_cap(cap)
+    }
+
+    /// The input given to the VM for the most recent frame, including attract demo input.
+    pub fn last_input(&self) -> InputState {
+        self.last_input
+    }
+
+    /// See `Vm::action_hold_frames`.
+    pub fn action_hold_frames(&self) -> u32 {
+        self.vm.action_hold_frames()
+    }
+
+    pub fn last_thread_activity(&self) -> &[ThreadActivity; 64] {
+        self.vm.last_thread_activity()
+    }
+
+    pub fn thread_pc(&self, thread: ThreadId) -> Option<Pc> {
+        self.vm.thread_pc(thread)
+    }
+
+    /// Also reads the pages back from the `Gfx`, see `Gfx::save_pages`. A part switch
+    /// held back by the load budget is finished first, so the state has a whole part.
+    pub fn save_state(&mut self) -> ExecutorState {
+        while self.load_part_entry() {}
+
+        ExecutorState {
+            vm: self.vm.clone(),
+            video: self.video.state(),
+            audio: self.mixer.lock().unwrap().state(),
+            pages: self.video.gfx_mut().save_pages(),
+            part: self.resources.loaded_part(),
+            frame: self.frame,
+        }
+    }
+
+    pub fn load_state(&mut self, state: ExecutorState) {
+        if let Some(part) = state.part {
+            self.prepare_part(part);
+        }
+
+        self.vm = state.vm;
+        self.frame = state.frame;
+        // before the video state, which shows the restored front page
+        if let Some(pages) = state.pages.as_ref() {
+            self.video.gfx_mut().restore_pages(pages);
+        }
+        self.video.restore(state.video);
+        self.mixer.lock().unwrap().restore(state.audio);
+        if let Some(softlock) = self.softlock.as_mut() {
+            softlock.reset();
+        }
+    }
+
+    pub fn push_rewind_snapshot(&mut self) {
+        let state = self.save_state();
+        self.rewind.push(state);
+    }
+
+    /// Restores the most recent rewind snapshot, returning `false` once the
+    /// buffer is exhausted.
+    pub fn rewind_step(&mut self) -> bool {
+        match self.rewind.pop() {
+            Some(state) => {
+                self.load_state(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_rewind_capacity(&mut self, capacity: usize) {
+        self.rewind = RewindBuffer::new(capacity);
+    }
+
+    pub fn set_load_progress(&mut self, progress: Option<LoadProgress>) {
+        self.resources.set_progress_callback(progress);
+    }
+
+    /// Notifies `observer` whenever a different game part is loaded.
+    pub fn set_part_observer(&mut self, observer: Option<PartObserver>) {
+        self.part_observer = observer;
+    }
+
+    pub fn set_event_observer(&mut self, observer: Option<EventObserver>) {
+        self.event_observer = observer;
+    }
+
+    pub fn gfx(&self) -> &G {
+        self.video.gfx()
+    }
+
+    pub fn gfx_mut(&mut self) -> &mut G {
+        self.video.gfx_mut()
+    }
+
+    /// See `Video::current_palette`.
+    pub fn current_palette(&self) -> Option<[(u8, u8, u8); 16]> {
+        self.video.current_palette()
+    }
+
+    pub fn loaded_part(&self) -> Option<GamePart> {
+        self.resources.loaded_part()
+    }
+
+    /// See `Resources::apply_bytecode_patch`.
+    pub fn apply_bytecode_patch(
+        &mut self,
+        part: GamePart,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<(), Error> {
+        self.resources.apply_bytecode_patch(part, offset, bytes)
+    }
+
+    /// Plays `recording` from a fresh boot once the menu, the start part, has seen no
+    /// input for `idle_timeout_ms` of game time. Any real input, or the end of the
+    /// recording, returns to the menu as it was when playback began, pages included.
+    /// `None` disables it.
+    pub fn set_attract_demo(&mut self, recording: Option<InputRecording>, idle_timeout_ms: u64) {
+        self.stop_attract();
+        self.attract = recording.map(|recording| AttractMode::new(recording, idle_timeout_ms));
+    }
+
+    pub fn is_attract_playing(&self) -> bool {
+        self.attract.as_ref().is_some_and(|a| a.is_playing())
+    }
+
+    /// Begins capturing the input fed to the VM, discarding any previous recording.
+    pub fn start_input_recording(&mut self) {
+        self.recording = Some(InputRecording::new());
+    }
+
+    pub fn take_input_recording(&mut self) -> Option<InputRecording> {
+        self.recording.take()
+    }
+
+    /// Begins logging the video commands issued by each `run` call, discarding any
+    /// previous log. See `desync::CommandLog`.
+    pub fn start_command_log(&mut self) {
+        self.command_log = Some(CommandLog::new());
+    }
+
+    pub fn take_command_log(&mut self) -> Option<CommandLog> {
+        self.command_log.take()
+    }
+
+    /// Drops fills and copies that are immediately overwritten before they reach the
+    /// `Gfx`, see `video::coalesce`. Command logs still record every command.
+    pub fn set_coalesce(&mut self, coalesce: bool) {
+        self.coalesce = coalesce;
+    }
+
+    pub fn enable_timings(&mut self) {
+        self.timings.get_or_insert_with(ExecutorTimings::default);
+    }
+
+    pub fn timings(&self) -> Option<ExecutorTimings> {
+        self.timings
+    }
+
+    /// Limits how many resource loads a single `run` call handles before handing control
+    /// back to the caller, `None` loads everything the frame asks for. Each entry of a
+    /// part the scripts switch to counts as one load.
+    pub fn set_load_budget(&mut self, budget: Option<usize>) {
+        self.load_budget = budget.map(|budget| budget.max(1));
+    }
+
+    /// Limits how many blits that ask for no sleep a single `run` call handles before
+    /// handing control back to the caller, so the frontend can repaint and read input
+    /// during quick successions of them. `None` runs them all until a blit that sleeps.
+    pub fn set_blit_budget(&mut self, budget: Option<usize>) {
+        self.blit_budget = budget.map(|budget| budget.max(1));
+    }
+
+    /// See `Resources::set_memory_budget`, evicted sounds and music are reloaded when
+    /// played.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.resources.set_memory_budget(budget);
+    }
+
+    /// Debug filter for finding which thread draws something, only threads with their bit
+    /// set in `mask` get their polygons and strings drawn. Fills, copies, page selects,
+    /// palettes and blits from every thread still go through so frames stay coherent, and
+    /// command logs still record every command. Defaults to `u64::MAX`, drawing everything.
+    pub fn set_thread_render_mask(&mut self, mask: u64) {
+        self.thread_render_mask = mask;
+    }
+
+    /// Free camera for exploring scrolling scenes, copies that scroll use `scroll` rather
+    /// than `vars::SCROLL_Y`. The variable itself is left to the scripts so they play on
+    /// as usual, and `None` hands the scroll back to them. Saved states don't include it,
+    /// so rewinding leaves the camera where it is.
+    pub fn set_scroll_override(&mut self, scroll: Option<i16>) {
+        self.scroll_override = scroll;
+    }
+
+    pub fn scroll_override(&self) -> Option<i16> {
+        self.scroll_override
+    }
+
+    /// Logs each of `variable_ids` that changed value over a VM frame, at info level with
+    /// the frame number and the old and new values. Replaces any previous watch, an empty
+    /// list stops watching.
+    pub fn watch_variables(&mut self, variable_ids: &[VarId]) {
+        let watch = VariableWatch::new(variable_ids, |id| self.vm.get_variable(id));
+        self.watch = Some(watch).filter(|watch| !watch.is_empty());
+    }
+
+    /// Warns once the screen and every variable have stayed the same for `timeout_ms` of
+    /// game time, see `softlock::SoftlockDetector`. `None` disables the check.
+    pub fn set_softlock_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.softlock = timeout_ms.map(SoftlockDetector::new);
+    }
+
+    /// Whether the game has looked frozen since the softlock timeout was reached, the
+    /// frontend can offer `reload_checkpoint` or a rewind.
+    pub fn is_softlocked(&self) -> bool {
+        self.softlock.as_ref().is_some_and(|s| s.is_detected())
+    }
+
+    /// Whether the last `run` call returned early, part way through loading resources.
+    /// The caller should call `run` again without sleeping.
+    pub fn is_loading(&self) -> bool {
+        self.loading
+    }
+
+    /// Whether the last `run` call returned early after a run of blits with no sleep.
+    /// The caller should call `run` again without sleeping.
+    pub fn is_rapid_blitting(&self) -> bool {
+        self.rapid_blits
+    }
+
+    /// Breakdown of the last `run` call, for explaining slow frames.
+    pub fn frame_stats(&self) -> Option<FrameStats> {
+        self.frame_stats
+    }
+
+    /// VM frames completed since the game started, as reported to the event observer.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Calls `run` until `pred` holds, checking it before the first call and after each
+    /// one, for at most `max_runs` calls. The sleeps `run` asks for are not waited out,
+    /// so game time only moves as fast as the executor's clock allows.
+    pub fn run_until(
+        &mut self,
+        mut pred: impl FnMut(&Self) -> bool,
+        max_runs: u64,
+    ) -> RunUntilResult {
+        for runs in 0..=max_runs {
+            if pred(self) {
+                return RunUntilResult::Satisfied(runs);
+            }
+            if runs < max_runs {
+                self.run();
+            }
+        }
+
+        RunUntilResult::LimitReached
+    }
+
+    pub fn skip_to_gameplay(&mut self) -> bool {
+        for _ in 0..SKIP_PASS_LIMIT {
+            if let Some(GamePart::Three) = self.resources.loaded_part() {
+                return true;
+            }
+
+            let res = self.vm.execute_frame(
+                self.resources.bytecode().expect("bytecode loaded"),
+                InputState::default(),
+            );
+            self.vm.audio_commands().for_each(drop);
+
+            match res {
+                Ok(FrameResult::Yield(Yield::Blit(_))) => {
+                    for cmd in self.vm.video_commands() {
+                        self.video.skip_command(cmd, &self.resources);
+                    }
+                }
+                Ok(FrameResult::Yield(Yield::ReqResource(resource_id))) => {
+                    self.resources.load_part_or_entry(resource_id)
+                }
+                Ok(FrameResult::Complete) => self.complete_frame(),
+                Err(err) => log::error!("vm error, killed thread: {}", err),
+            }
+        }
+
+        false
+    }
+
+    /// Runs the game until it next blits and returns the ms to wait before calling again.
+    /// Returns 0 when stopped early by the load budget, see `is_loading`, or by the blit
+    /// budget, see `is_rapid_blitting`.
     pub fn run(&mut self) -> u64 {
+        let polygons = self.video.polygons_drawn();
+        let bytes_loaded = self.resources.bytes_loaded();
+        let timings = self.timings;
+        if self.timings.is_some() {
+            self.frame_stats = Some(FrameStats::default());
+        }
+
+        let ms = self.run_until_blit();
+
+        if let (Some(stats), Some(before), Some(after)) =
+            (self.frame_stats.as_mut(), timings, self.timings)
+        {
+            stats.timings = ExecutorTimings {
+                vm: after.vm - before.vm,
+                video: after.video - before.video,
+                resources: after.resources - before.resources,
+            };
+            stats.polygons = self.video.polygons_drawn() - polygons;
+            stats.bytes_loaded = self.resources.bytes_loaded() - bytes_loaded;
+        }
+
+        ms
+    }
+
+    fn run_until_blit(&mut self) -> u64 {
+        let mut loads = 0;
+        let mut blits = 0;
+        self.loading = false;
+        self.rapid_blits = false;
+        let view = debug_view(self.thread_render_mask, self.scroll_override);
+
         loop {
-            let input = self.input.get_input();
+            if self.pending_part.is_some() {
+                let start = self.timer();
+                while self.load_part_entry() {
+                    loads += 1;
+                    if self.load_budget.is_some_and(|budget| loads >= budget) {
+                        self.record_timing(start, |t| &mut t.resources);
+                        self.loading = true;
+                        return 0;
+                    }
+                }
+                self.record_timing(start, |t| &mut t.resources);
+            }
+
+            let input = self.next_input();
+            self.last_input = input;
+            let start = self.timer();
             let res = self
                 .vm
                 .execute_frame(self.resources.bytecode().expect("bytecode loaded"), input);
+            self.record_timing(start, |t| &mut t.vm);
+            self.apply_audio_commands();
 
             match res {
-                FrameResult::Yield(Yield::Blit(ms)) => {
-                    for cmd in self.vm.video_commands() {
+                Ok(FrameResult::Yield(Yield::Blit(ms))) => {
+                    let start = self.timer();
+                    let mut commands = std::mem::take(&mut self.command_buffer);
+                    for (thread, cmd) in self.vm.thread_video_commands() {
+                        if let Some(log) = self.command_log.as_mut() {
+                            log.push_command(&cmd);
+                        }
+                        commands.extend(view(thread, cmd));
+                    }
+                    if self.coalesce {
+                        let dropped = video::coalesce(&mut commands);
+                        if let Some(stats) = self.frame_stats.as_mut() {
+                            stats.coalesced += dropped as u32;
+                        }
+                    }
+
+                    for cmd in commands.drain(..) {
+                        if let Some(softlock) = self.softlock.as_mut() {
+                            softlock.push_command(&cmd);
+                        }
+                        if let Some(stats) = self.frame_stats.as_mut() {
+                            match cmd {
+                                VideoCommand::FillVideoPage(_) => stats.fills += 1,
+                                VideoCommand::CopyVideoPage(_) => stats.copies += 1,
+                                VideoCommand::DrawString(_) => stats.strings += 1,
+                                _ => (),
+                            }
+                        }
                         self.video.push_command(cmd, &self.resources);
                     }
+                    self.command_buffer = commands;
+                    self.record_timing(start, |t| &mut t.video);
 
                     if ms > 0 {
+                        if let Some(log) = self.command_log.as_mut() {
+                            log.end_frame();
+                        }
+                        if let Some(softlock) = self.softlock.as_mut() {
+                            if softlock.end_frame(self.vm.variables(), ms) {
+                                log::warn!(
+                                    "nothing on screen or in the variables has changed, the game may be softlocked"
+                                );
+                            }
+                        }
+                        self.clock.advance(Duration::from_millis(ms));
+                        self.advance_music(ms);
+                        self.update_attract(input, ms);
                         return ms;
                     }
+
+                    blits += 1;
+                    if self.blit_budget.is_some_and(|budget| blits >= budget) {
+                        self.rapid_blits = true;
+                        return 0;
+                    }
+                }
+                Ok(FrameResult::Yield(Yield::ReqResource(resource_id))) => {
+                    let start = self.timer();
+                    self.resources.load_part_or_entry(resource_id);
+                    self.record_timing(start, |t| &mut t.resources);
+
+                    loads += 1;
+                    if self.load_budget.is_some_and(|budget| loads >= budget) {
+                        self.loading = true;
+                        return 0;
+                    }
+                }
+                Ok(FrameResult::Complete) => self.end_frame(),
+                // the next pass carries on with the threads after the killed one
+                Err(err) => log::error!("vm error, killed thread: {}", err),
+            }
+        }
+    }
+
+    fn next_input(&mut self) -> InputState {
+        let input = self.input.get_input();
+
+        if self.is_attract_playing() {
+            let demo_input = self.attract.as_mut().and_then(|a| a.next_input());
+            match demo_input {
+                Some(demo_input) if !input.is_active() => return demo_input,
+                _ => self.stop_attract(),
+            }
+        }
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(input);
+        }
+
+        input
+    }
+
+    fn update_attract(&mut self, input: InputState, ms: u64) {
+        let on_menu = self.loaded_part() == Some(self.start_part);
+        let attract = match self.attract.as_mut() {
+            Some(attract) if !attract.is_playing() => attract,
+            _ => return,
+        };
+
+        if input.is_active() || !on_menu {
+            attract.reset_idle();
+        } else if attract.idle(ms) {
+            attract.start();
+            self.attract_resume = Some(self.save_state());
+            self.restart();
+        }
+    }
+
+    /// Loads `part` and runs it up to its first blit, for checking that a data set is
+    /// complete. Leaves the executor in `part` on success.
+    pub fn boot_part(&mut self, part: GamePart) -> Result<(), Error> {
+        self.jump_to_part(part);
+        let view = debug_view(self.thread_render_mask, self.scroll_override);
+
+        for _ in 0..SKIP_PASS_LIMIT {
+            if self.resources.palette().is_none() || self.resources.cinematic().is_none() {
+                return Err(Error::MissingPartData);
+            }
+            let bytecode = self.resources.bytecode().ok_or(Error::MissingPartData)?;
+
+            let res = self.vm.execute_frame(bytecode, InputState::default())?;
+            self.vm.audio_commands().for_each(drop);
+
+            match res {
+                FrameResult::Yield(Yield::Blit(_)) => {
+                    for (thread, cmd) in self.vm.thread_video_commands() {
+                        if let Some(cmd) = view(thread, cmd) {
+                            self.video.push_command(cmd, &self.resources);
+                        }
+                    }
+                    return Ok(());
                 }
                 FrameResult::Yield(Yield::ReqResource(resource_id)) => {
                     self.resources.load_part_or_entry(resource_id)
                 }
-                FrameResult::Complete => {
-                    self.frame += 1;
-                    if let Some(part) = self.resources.requested_part() {
-                        self.resources.prepare_part(part);
-                        self.vm.init_part();
+                FrameResult::Complete => self.complete_frame(),
+            }
+        }
+
+        Err(Error::StepLimitExceeded)
+    }
+
+    fn stop_attract(&mut self) {
+        if let Some(attract) = self.attract.as_mut() {
+            attract.stop();
+        }
+
+        if let Some(state) = self.attract_resume.take() {
+            self.load_state(state);
+        }
+    }
+
+    fn restart(&mut self) {
+        self.vm.reset();
+        self.prepare_part(self.start_part);
+        self.frame = 0;
+    }
+
+    /// Music is timed by game time rather than by the audio thread, so markers land on
+    /// the same frames however fast the frontend runs, and replays and rewinds see them
+    /// at the same points.
+    fn advance_music(&mut self, ms: u64) {
+        let marker = self.mixer.lock().unwrap().advance_music(ms as u32);
+        if let Some(marker) = marker {
+            self.vm.set_variable(vars::MUSIC_MARKER, marker);
+        }
+    }
+
+    fn apply_audio_commands(&mut self) {
+        let mut mixer = self.mixer.lock().unwrap();
+        for cmd in self.vm.audio_commands() {
+            match cmd {
+                AudioCommand::PlaySound(sound) => {
+                    self.resources.reload_evicted(sound.resource_id);
+                    mixer.play_sound(sound, self.resources.entry_data(sound.resource_id))
+                }
+                AudioCommand::PlayMusic(music) => {
+                    if music.resource_id != 0 {
+                        self.resources.reload_evicted(music.resource_id);
                     }
+                    mixer.play_music(music, self.resources.entry_data(music.resource_id))
                 }
             }
         }
     }
+
+    fn timer(&self) -> Option<Instant> {
+        self.timings.map(|_| Instant::now())
+    }
+
+    fn record_timing(
+        &mut self,
+        start: Option<Instant>,
+        stage: fn(&mut ExecutorTimings) -> &mut Duration,
+    ) {
+        if let (Some(start), Some(timings)) = (start, self.timings.as_mut()) {
+            *stage(timings) += start.elapsed();
+        }
+    }
+
+    fn complete_frame(&mut self) {
+        self.end_frame();
+        while self.load_part_entry() {}
+    }
+
+    /// Counts the frame and starts any part switch the scripts asked for, its entries are
+    /// then loaded by `load_part_entry`.
+    fn end_frame(&mut self) {
+        self.frame += 1;
+        if let Some(watch) = self.watch.as_mut() {
+            for change in watch.diff(self.frame, |id| self.vm.get_variable(id)) {
+                log::info!("{}", change);
+            }
+        }
+        if let Some(part) = self.resources.requested_part() {
+            self.pending_part = Some(self.resources.loaded_part());
+            self.resources.begin_part(part);
+        }
+    }
+
+    /// Loads one entry of a pending part switch, returning false once there is nothing
+    /// left to load. The part's threads are restarted after its last entry.
+    fn load_part_entry(&mut self) -> bool {
+        let previous = match self.pending_part {
+            Some(previous) => previous,
+            None => return false,
+        };
+
+        if self.resources.load_part_entry() {
+            return true;
+        }
+
+        self.pending_part = None;
+        let part = match self.resources.loaded_part() {
+            Some(part) => part,
+            None => return false,
+        };
+        if previous != Some(part) {
+            if let Some(observer) = self.part_observer.as_mut() {
+                observer(part);
+            }
+        }
+        self.vm.init_part();
+        self.emit_event(GameEvent::from_part_change(previous, part));
+        false
+    }
+
+    fn emit_event(&mut self, event: GameEvent) {
+        if self.is_attract_playing() {
+            return;
+        }
+
+        if let Some(observer) = self.event_observer.as_mut() {
+            observer(self.frame, event);
+        }
+    }
+
+    fn prepare_part(&mut self, part: GamePart) {
+        self.pending_part = None;
+        let previous = self.resources.loaded_part();
+        self.resources.prepare_part(part);
+
+        if previous != Some(part) {
+            if let Some(observer) = self.part_observer.as_mut() {
+                observer(part);
+            }
+        }
+    }
+}
+
+/// Applies the debug views to each command on its way to the `Gfx`, returning `None` for
+/// commands that are hidden. See `Executor::set_thread_render_mask` and
+/// `Executor::set_scroll_override`.
+fn debug_view(
+    mask: u64,
+    scroll_override: Option<i16>,
+) -> impl Fn(ThreadId, VideoCommand) -> Option<VideoCommand> {
+    move |thread, mut command| {
+        match &mut command {
+            VideoCommand::Draw(_) | VideoCommand::DrawString(_) if mask & (1 << thread.0) == 0 => {
+                return None
+            }
+            VideoCommand::CopyVideoPage(copy) => {
+                if let Some(scroll) = scroll_override {
+                    copy.scroll = scroll;
+                }
+            }
+            _ => (),
+        }
+
+        Some(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::VirtualClock;
+    use crate::resources::ResourceType;
+    use crate::testing::{MemIo, RecordingGfx, ScriptedInput};
+    use crate::video::Page;
+    use crate::vm::DEFAULT_TICK_MS;
+
+    /// Entries listed first win when an index repeats.
+    fn test_io(entries: &[(usize, &[u8])]) -> MemIo {
+        entries
+            .iter()
+            .rev()
+            .fold(MemIo::builder(), |builder, (idx, data)| {
+                builder.resource(*idx, ResourceType::Unknown, data)
+            })
+            .build()
+    }
+
+    /// Each of `parts` running its bytecode with a blank palette and no polygons, plus the
+    /// resource `entries`.
+    fn parts_io(parts: &[(GamePart, &[u8])], entries: &[(usize, &[u8])]) -> MemIo {
+        let palette = [0; 32];
+        let mut all = entries.to_vec();
+        for &(part, bytecode) in parts {
+            all.push((part.palette(), &palette[..]));
+            all.push((part.bytecode(), bytecode));
+            all.push((part.cinematic(), &[][..]));
+        }
+        test_io(&all)
+    }
+
+    /// Starts in part two, sleeping one tick per blit so each `run` returns after a frame.
+    fn executor_with<In: Input>(io: MemIo, input: In) -> Executor<MemIo, RecordingGfx, In> {
+        let mut executor = Executor::new(io, RecordingGfx::default(), input, true).unwrap();
+        executor.set_variable(vars::SLEEP_TICKS, 1);
+        executor
+    }
+
+    /// See `executor_with`, running `bytecode` as part two without input.
+    fn one_part_executor(bytecode: &[u8]) -> Executor<MemIo, RecordingGfx, TestInput> {
+        executor_with(parts_io(&[(GamePart::Two, bytecode)], &[]), TestInput)
+    }
+
+    struct TestInput;
+
+    impl Input for TestInput {
+        fn get_input(&self) -> InputState {
+            InputState::default()
+        }
+    }
+
+    #[test]
+    fn skip_to_gameplay_reaches_part_three() {
+        let intro = [
+            0x0d, 0x02, // select page 2
+            0x0e, 0x01, 0x04, // fill page 1 with color 4
+            0x0b, 0x00, 0x00, // set palette 0
+            0x12, 0x00, 0x01, 0x01, 0x01, 0x01, // draw string 1
+            0x00, 0x10, 0x00, 0x03, // var 0x10 = 3
+            0x10, 0xff, // blit
+            0x06, // pause
+            0x09, 0x10, 0x00, 0x12, // decrement var 0x10, jmp 0x12 if not zero
+            0x19, 0x3e, 0x82, // load part three
+            0x06, // pause
+        ];
+        // pause forever
+        let gameplay = [0x06, 0x07, 0x00, 0x00];
+
+        let io = parts_io(
+            &[(GamePart::Two, &intro), (GamePart::Three, &gameplay)],
+            &[],
+        );
+
+        let mut executor = executor_with(io, TestInput);
+
+        assert_eq!(executor.resources.loaded_part(), Some(GamePart::Two));
+        assert!(executor.skip_to_gameplay());
+        assert_eq!(executor.resources.loaded_part(), Some(GamePart::Three));
+
+        // page and palette changes land, drawing and presenting are skipped
+        let gfx = executor.gfx();
+        assert_eq!(gfx.selects.last(), Some(&Page::Two));
+        assert!(gfx.pages.page(Page::One).iter().all(|&c| c == 4));
+        assert_eq!(gfx.palettes.len(), 1);
+        assert!(gfx.strings.is_empty());
+        assert!(gfx.blits.is_empty());
+    }
+
+    #[test]
+    fn part_observer_sees_part_changes() {
+        // blit, then load part three
+        let intro = [0x10, 0xff, 0x19, 0x3e, 0x82, 0x06];
+        // pause forever
+        let gameplay = [0x06, 0x07, 0x00, 0x00];
+
+        let io = parts_io(
+            &[(GamePart::Two, &intro), (GamePart::Three, &gameplay)],
+            &[],
+        );
+
+        let mut executor = executor_with(io, TestInput);
+        let parts = Arc::new(Mutex::new(Vec::new()));
+        let observed = parts.clone();
+        executor.set_part_observer(Some(Box::new(move |part| {
+            observed.lock().unwrap().push(part)
+        })));
+
+        let intro_state = executor.save_state();
+        assert!(executor.skip_to_gameplay());
+        assert_eq!(executor.loaded_part(), Some(GamePart::Three));
+
+        executor.jump_to_part(GamePart::Three);
+        executor.load_state(intro_state);
+        assert_eq!(executor.loaded_part(), Some(GamePart::Two));
+
+        assert_eq!(*parts.lock().unwrap(), [GamePart::Three, GamePart::Two]);
+    }
+
+    #[test]
+    fn load_budget_returns_between_loads() {
+        let part = [
+            0x19, 0x00, 0x01, // load entry 1
+            0x19, 0x00, 0x02, // load entry 2
+            0x19, 0x00, 0x03, // load entry 3
+            0x10, 0xff, // blit
+            0x19, 0x3e, 0x82, // switch to part three
+            0x06, // pause
+            0x07, 0x00, 0x0e, // jmp 0x0e
+        ];
+        let blit_forever = [0x10, 0xff, 0x07, 0x00, 0x00];
+
+        let io = parts_io(
+            &[(GamePart::Two, &part), (GamePart::Three, &blit_forever)],
+            &[(1, &[1]), (2, &[2]), (3, &[3])],
+        );
+
+        let mut executor = executor_with(io, TestInput);
+        executor.set_load_budget(Some(2));
+
+        assert_eq!(executor.run(), 0);
+        assert!(executor.is_loading());
+        assert!(executor.resources.entry_data(2).is_some());
+        assert!(executor.resources.entry_data(3).is_none());
+
+        assert_eq!(executor.run(), DEFAULT_TICK_MS);
+        assert!(!executor.is_loading());
+        assert!(executor.resources.entry_data(3).is_some());
+
+        // the part request and the first entry of part three, then two entries a run
+        assert_eq!(executor.run(), 0);
+        assert!(executor.is_loading());
+        assert_eq!(executor.loaded_part(), None);
+        assert_eq!(executor.run(), 0);
+        assert!(executor.is_loading());
+        assert_eq!(executor.loaded_part(), None);
+
+        let blits = executor.gfx().blits.len();
+        assert_eq!(executor.run(), DEFAULT_TICK_MS);
+        assert!(!executor.is_loading());
+        assert_eq!(executor.loaded_part(), Some(GamePart::Three));
+        assert_eq!(executor.gfx().blits.len(), blits + 1);
+    }
+
+    #[test]
+    fn blit_budget_returns_between_rapid_blits() {
+        // blit forever
+        let part = [0x10, 0xff, 0x07, 0x00, 0x00];
+        let mut executor = one_part_executor(&part);
+        executor.set_variable(vars::SLEEP_TICKS, 0);
+        executor.set_blit_budget(Some(3));
+
+        assert_eq!(executor.run(), 0);
+        assert!(executor.is_rapid_blitting());
+        assert_eq!(executor.gfx().blits.len(), 3);
+
+        assert_eq!(executor.run(), 0);
+        assert_eq!(executor.gfx().blits.len(), 6);
+
+        executor.set_variable(vars::SLEEP_TICKS, 1);
+        assert_eq!(executor.run(), DEFAULT_TICK_MS);
+        assert!(!executor.is_rapid_blitting());
+        assert_eq!(executor.gfx().blits.len(), 7);
+    }
+
+    #[test]
+    fn executors_on_separate_threads_stay_independent() {
+        fn run_instance(seed: i16, input: InputState) -> (i16, i16, Duration) {
+            // add the horizontal input to a variable each frame
+            let part = [0x02, 0x10, 0xfc, 0x10, 0xff, 0x07, 0x00, 0x00];
+            let io = parts_io(&[(GamePart::Two, &part)], &[]);
+
+            let input = ScriptedInput::new(vec![(0..u64::MAX, input)]);
+            let mut executor = executor_with(io, input);
+            executor.set_variable(vars::RANDOM_SEED, seed);
+            executor.set_clock(Box::new(VirtualClock::new()));
+
+            for _ in 0..50 {
+                executor.run();
+            }
+
+            (
+                executor.get_variable(VarId(0x10)),
+                executor.get_variable(vars::RANDOM_SEED),
+                executor.elapsed(),
+            )
+        }
+
+        fn assert_send<T: Send>() {}
+        assert_send::<Executor<MemIo, RecordingGfx, ScriptedInput>>();
+
+        let right = InputState {
+            right: true,
+            ..Default::default()
+        };
+        let left = InputState {
+            left: true,
+            ..Default::default()
+        };
+        let a = std::thread::spawn(move || run_instance(1234, right));
+        let b = std::thread::spawn(move || run_instance(-42, left));
+        let (a, b) = (a.join().unwrap(), b.join().unwrap());
+
+        assert_eq!(a, (50, 1234, Duration::from_secs(1)));
+        assert_eq!(b, (-50, -42, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn music_markers_gate_scripted_branches() {
+        // 20ms rows with marker 1 on the third row
+        let mut music = vec![0; 0xc0 + 0x400];
+        music[0..2].copy_from_slice(&2350u16.to_be_bytes());
+        music[0x3e..0x40].copy_from_slice(&1u16.to_be_bytes());
+        music[0xc0 + 2 * 16..0xc0 + 2 * 16 + 4].copy_from_slice(&[0xff, 0xfd, 0x00, 0x01]);
+        // load and play the music, blit until the marker is 1, then set 0x20 and blit forever
+        let part = [
+            0x19, 0x00, 0x01, 0x1a, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x00, 0xf4, 0x01, 0x00,
+            0x14, 0x10, 0xff, 0x07, 0x00, 0x09, 0x00, 0x20, 0x00, 0x01, 0x10, 0xff, 0x07, 0x00,
+            0x18,
+        ];
+        let io = parts_io(&[(GamePart::Two, &part)], &[(1, &music)]);
+
+        let mut executor = executor_with(io, TestInput);
+
+        for _ in 0..3 {
+            executor.run();
+            assert_eq!(executor.get_variable(VarId(0x20)), 0);
+        }
+        assert_eq!(executor.get_variable(vars::MUSIC_MARKER), 1);
+
+        executor.run();
+        assert_eq!(executor.get_variable(VarId(0x20)), 1);
+    }
+
+    #[test]
+    fn boot_part_reports_missing_and_broken_parts() {
+        let blit = [0x10, 0xff, 0x07, 0x00, 0x00];
+        let invalid = [0xff];
+
+        let io = parts_io(&[(GamePart::Two, &blit), (GamePart::Three, &invalid)], &[]);
+
+        let mut executor = executor_with(io, TestInput);
+
+        assert!(executor.boot_part(GamePart::Two).is_ok());
+        assert!(executor.boot_part(GamePart::Three).is_err());
+        match executor.boot_part(GamePart::Four) {
+            Err(Error::MissingPartData) => (),
+            res => panic!("expected missing part data: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn frame_stats_count_video_commands() {
+        // fill page 0 then blit, forever
+        let part = [0x0e, 0x00, 0x01, 0x10, 0xff, 0x07, 0x00, 0x00];
+
+        let mut executor = one_part_executor(&part);
+
+        executor.run();
+        assert!(executor.frame_stats().is_none());
+
+        executor.enable_timings();
+        executor.run();
+        let stats = executor.frame_stats().unwrap();
+        assert_eq!(stats.fills, 1);
+        assert_eq!(stats.copies, 0);
+        assert_eq!(stats.bytes_loaded, 0);
+    }
+
+    #[test]
+    fn coalesce_drops_overwritten_fills() {
+        // fill page 0 twice then blit, forever
+        let part = [
+            0x0e, 0x00, 0x01, 0x0e, 0x00, 0x02, 0x10, 0xff, 0x07, 0x00, 0x00,
+        ];
+
+        let mut executor = one_part_executor(&part);
+        executor.enable_timings();
+
+        executor.run();
+        let stats = executor.frame_stats().unwrap();
+        assert_eq!((stats.fills, stats.coalesced), (2, 0));
+
+        executor.set_coalesce(true);
+        executor.start_command_log();
+        executor.run();
+        let stats = executor.frame_stats().unwrap();
+        assert_eq!((stats.fills, stats.coalesced), (1, 1));
+        assert_eq!(
+            executor
+                .take_command_log()
+                .unwrap()
+                .to_text()
+                .lines()
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn scripted_input_moves_hero() {
+        // var 0x20 += hero left/right, then blit, forever
+        let part = [0x02, 0x20, 0xfc, 0x10, 0xff, 0x07, 0x00, 0x00];
+
+        let io = parts_io(&[(GamePart::Two, &part)], &[]);
+
+        let right = InputState {
+            right: true,
+            ..Default::default()
+        };
+        let left = InputState {
+            left: true,
+            ..Default::default()
+        };
+        // hold right for frames 0-9, rest, then left for frames 15-17
+        let input = ScriptedInput::new(vec![(0..10, right), (15..18, left)]);
+        let counter = input.frame_counter();
+
+        let mut executor = executor_with(io, input);
+
+        let mut positions = Vec::new();
+        for _ in 0..20 {
+            executor.run();
+            counter.advance();
+            positions.push(executor.get_variable(VarId(0x20)));
+        }
+
+        assert_eq!(positions[9], 10);
+        assert_eq!(positions[14], 10);
+        assert_eq!(positions[19], 7);
+    }
+
+    #[test]
+    fn rewind_restores_variables() {
+        // increment var 0x10 then blit, forever
+        let counter = [0x03, 0x10, 0x00, 0x01, 0x10, 0xff, 0x07, 0x00, 0x00];
+
+        let mut executor = one_part_executor(&counter);
+
+        executor.run();
+        assert_eq!(executor.get_variable(VarId(0x10)), 1);
+
+        executor.push_rewind_snapshot();
+        executor.run();
+        executor.push_rewind_snapshot();
+        for _ in 0..5 {
+            executor.run();
+        }
+        assert_eq!(executor.get_variable(VarId(0x10)), 7);
+
+        assert!(executor.rewind_step());
+        assert_eq!(executor.get_variable(VarId(0x10)), 2);
+        assert!(executor.rewind_step());
+        assert_eq!(executor.get_variable(VarId(0x10)), 1);
+        assert!(!executor.rewind_step());
+
+        executor.run();
+        assert_eq!(executor.get_variable(VarId(0x10)), 2);
+    }
+
+    #[test]
+    fn rewind_restores_page_contents() {
+        let part = [
+            0x0e, 0x01, 0x03, // fill page 1 with color 3
+            0x10, 0xff, // blit
+            0x06, // pause
+            0x0e, 0x01, 0x07, // fill page 1 with color 7
+            0x10, 0xff, // blit
+            0x06, // pause
+            0x07, 0x00, 0x06, // jmp 0x06
+        ];
+        let mut executor = one_part_executor(&part);
+
+        executor.run();
+        executor.push_rewind_snapshot();
+        executor.run();
+        assert!(executor.gfx().pages.page(Page::One).iter().all(|&c| c == 7));
+
+        let blits = executor.gfx().blits.len();
+        assert!(executor.rewind_step());
+        assert!(executor.gfx().pages.page(Page::One).iter().all(|&c| c == 3));
+        // the restored front page is shown straight away
+        assert_eq!(executor.gfx().blits.len(), blits + 1);
+    }
+
+    #[test]
+    fn enter_code_jumps_to_checkpoint() {
+        // copy the checkpoint variable to 0x10, then blit forever
+        let part = [
+            0x00, 0xff, 0x00, 0x01, 0x01, 0x10, 0x00, 0x10, 0xff, 0x07, 0x00, 0x00,
+        ];
+
+        let parts = [
+            (GamePart::Two, &part[..]),
+            (GamePart::Three, &part[..]),
+            (GamePart::Five, &part[..]),
+        ];
+        let mut executor = executor_with(parts_io(&parts, &[]), TestInput);
+
+        for (code, part, checkpoint) in [
+            ("LDKD", GamePart::Three, 10),
+            ("KRFK", GamePart::Five, 30),
+            ("XJRT", GamePart::Five, 39),
+        ] {
+            executor.set_variable(VarId(0x20), 7);
+            executor.enter_code(code).unwrap();
+            for _ in 0..3 {
+                executor.run();
+            }
+            assert_eq!(executor.resources.loaded_part(), Some(part));
+            assert_eq!(executor.get_variable(VarId(0x10)), checkpoint);
+            // the restart clears whatever the previous session left behind
+            assert_eq!(executor.get_variable(VarId(0x20)), 0);
+        }
+
+        assert!(executor.enter_code("ZZZZ").is_err());
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedInput(Arc<Mutex<InputState>>);
+
+    impl Input for SharedInput {
+        fn get_input(&self) -> InputState {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn attract_plays_after_idle_and_input_cancels() {
+        // sleep 1 tick, copy the input mask to var 0x10, count blits in var 0x11, blit
+        let echo = [
+            0x00, 0xff, 0x00, 0x01, 0x01, 0x10, 0xfe, 0x03, 0x11, 0x00, 0x01, 0x10, 0xff, 0x07,
+            0x00, 0x00,
+        ];
+
+        let io = parts_io(&[(GamePart::Two, &echo)], &[]);
+
+        let input = SharedInput::default();
+        let mut executor = executor_with(io, input.clone());
+
+        let mut recording = InputRecording::new();
+        for _ in 0..10 {
+            recording.push(InputState {
+                action: true,
+                ..Default::default()
+            });
+        }
+        executor.set_attract_demo(Some(recording), 5 * DEFAULT_TICK_MS);
+
+        for _ in 0..4 {
+            executor.run();
+            assert!(!executor.is_attract_playing());
+        }
+        executor.run();
+        assert!(executor.is_attract_playing());
+
+        executor.run();
+        assert_eq!(executor.get_variable(VarId(0x10)), 0x80);
+        assert_eq!(executor.get_variable(VarId(0x11)), 1);
+
+        input.0.lock().unwrap().up = true;
+        executor.run();
+        assert!(!executor.is_attract_playing());
+        assert_eq!(executor.get_variable(VarId(0x10)), 0x08);
+        assert_eq!(executor.get_variable(VarId(0x11)), 6);
+    }
+
+    #[test]
+    fn attract_returns_to_the_menu_screen() {
+        let menu = [
+            0x00, 0xff, 0x00, 0x01, // sleep one tick per blit, after a restart too
+            0x0e, 0x02, 0x05, // fill page 2 with color 5
+            0x0e, 0x01, 0x03, // fill page 1 with color 3
+            0x10, 0xff, // blit
+            0x06, // pause
+            0x10, 0xff, // blit
+            0x06, // pause
+            0x07, 0x00, 0x0d, // jmp 0x0d
+        ];
+        let game = [0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+        let io = parts_io(&[(GamePart::Two, &menu), (GamePart::Three, &game)], &[]);
+        let mut executor = executor_with(io, TestInput);
+
+        let mut recording = InputRecording::new();
+        recording.push(InputState::default());
+        recording.push(InputState::default());
+        executor.set_attract_demo(Some(recording), 3 * DEFAULT_TICK_MS);
+
+        executor.run();
+        executor.gfx_mut().pages.page_mut(Page::Two).fill(9);
+        while !executor.is_attract_playing() {
+            executor.run();
+        }
+
+        executor.run();
+        assert!(executor.gfx().pages.page(Page::Two).iter().all(|&c| c == 5));
+
+        let blits = executor.gfx().blits.len();
+        executor.run();
+        executor.run();
+        assert!(!executor.is_attract_playing());
+        assert_eq!(executor.loaded_part(), Some(GamePart::Two));
+        assert!(executor.gfx().pages.page(Page::Two).iter().all(|&c| c == 9));
+        assert!(executor.gfx().blits.len() > blits + 1);
+
+        // idling mid-game never leaves the player's session for the demo
+        executor.jump_to_part(GamePart::Three);
+        for _ in 0..10 {
+            executor.run();
+            assert!(!executor.is_attract_playing());
+        }
+    }
+
+    #[test]
+    fn virtual_clock_counts_blit_durations() {
+        // lengthen the pause by one tick each frame, blit
+        let slowing = [0x03, 0xff, 0x00, 0x01, 0x10, 0xff, 0x07, 0x00, 0x00];
+
+        let mut executor = one_part_executor(&slowing);
+        executor.set_clock(Box::new(VirtualClock::new()));
+
+        let mut total = 0;
+        for _ in 0..10 {
+            total += executor.run();
+        }
+
+        assert!(total > 10 * DEFAULT_TICK_MS);
+        assert_eq!(executor.elapsed(), Duration::from_millis(total));
+    }
+
+    #[test]
+    fn load_state_resumes_music_and_sounds() {
+        let mut music = vec![0; 0xc0 + 0x400];
+        music[0..2].copy_from_slice(&0x3a98u16.to_be_bytes());
+        music[0x3e..0x40].copy_from_slice(&3u16.to_be_bytes());
+        let mut sound = vec![0x00, 0x20, 0x00, 0x00, 0, 0, 0, 0];
+        sound.extend([0x40; 0x40]);
+
+        // load music 0x10 and sound 0x11, start the music at position 2 and the sound on
+        // channel 1, blit, then stop both and blit every frame after
+        let bytecode = [
+            0x19, 0x00, 0x10, 0x19, 0x00, 0x11, 0x1a, 0x00, 0x10, 0x00, 0x00, 0x02, 0x18, 0x00,
+            0x11, 0x27, 0x3f, 0x01, 0x00, 0xff, 0x00, 0x01, 0x10, 0xff, 0x1a, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x18, 0x00, 0x11, 0x27, 0x00, 0x01, 0x10, 0xff, 0x07, 0x00, 0x18,
+        ];
+
+        let io = parts_io(
+            &[(GamePart::Two, &bytecode)],
+            &[(0x10, &music), (0x11, &sound)],
+        );
+
+        let mut executor = executor_with(io, TestInput);
+        let mixer = executor.mixer();
+
+        executor.run();
+        assert!(mixer.lock().unwrap().music().is_playing());
+        assert!(mixer.lock().unwrap().is_playing(1));
+        let state = executor.save_state();
+
+        executor.run();
+        assert!(!mixer.lock().unwrap().music().is_playing());
+        assert!(!mixer.lock().unwrap().is_playing(1));
+
+        executor.load_state(state);
+        let mixer = mixer.lock().unwrap();
+        let music = mixer.music();
+        assert!(music.is_playing());
+        assert_eq!(music.resource_id(), Some(0x10));
+        assert_eq!(music.position(), 2);
+        assert_eq!(music.row(), 0);
+        assert_eq!(music.delay(), 0x3a98);
+        assert!(mixer.is_playing(1));
+    }
+
+    #[test]
+    fn reloading_the_current_part_reports_a_death() {
+        // blit, then request part three again and end the frame
+        let part = [0x00, 0xff, 0x00, 0x01, 0x10, 0xff, 0x19, 0x3e, 0x82, 0x11];
+
+        let io = parts_io(&[(GamePart::Two, &[]), (GamePart::Three, &part)], &[]);
+        let mut executor = executor_with(io, TestInput);
+        executor.jump_to_part(GamePart::Three);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let observed = events.clone();
+        executor.set_event_observer(Some(Box::new(move |frame, event| {
+            observed.lock().unwrap().push((frame, event))
+        })));
+
+        executor.run();
+        assert!(events.lock().unwrap().is_empty());
+
+        executor.run();
+        executor.run();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [(1, GameEvent::Died), (2, GameEvent::Died)]
+        );
+    }
+
+    #[test]
+    fn run_until_stops_on_part_change() {
+        // blit, then load part three
+        let intro = [0x10, 0xff, 0x19, 0x3e, 0x82, 0x06];
+        // blit, yield and loop
+        let gameplay = [0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+
+        let io = parts_io(
+            &[(GamePart::Two, &intro), (GamePart::Three, &gameplay)],
+            &[],
+        );
+
+        let mut executor = executor_with(io, TestInput);
+
+        assert_eq!(
+            executor.run_until(|e| e.loaded_part() == Some(GamePart::Three), 10),
+            RunUntilResult::Satisfied(2)
+        );
+        assert_eq!(executor.loaded_part(), Some(GamePart::Three));
+    }
+
+    #[test]
+    fn run_until_stops_on_frame_count() {
+        // blit, yield and loop
+        let part = [0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+
+        let mut executor = one_part_executor(&part);
+
+        assert_eq!(
+            executor.run_until(|e| e.frame() >= 5, 3),
+            RunUntilResult::LimitReached
+        );
+        assert!(matches!(
+            executor.run_until(|e| e.frame() >= 5, 10),
+            RunUntilResult::Satisfied(_)
+        ));
+        assert_eq!(executor.frame(), 5);
+        assert_eq!(
+            executor.run_until(|e| e.frame() >= 5, 0),
+            RunUntilResult::Satisfied(0)
+        );
+    }
+
+    #[test]
+    fn run_until_stops_on_variable_value() {
+        // add 1 to 0x10, blit, yield and loop
+        let part = [0x03, 0x10, 0x00, 0x01, 0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+
+        let mut executor = one_part_executor(&part);
+
+        assert_eq!(
+            executor.run_until(|e| e.get_variable(VarId(0x10)) == 4, 100),
+            RunUntilResult::Satisfied(4)
+        );
+        assert_eq!(executor.get_variable(VarId(0x10)), 4);
+    }
+
+    #[test]
+    fn bytecode_patch_changes_mov_const() {
+        // var 0x10 = 5, blit, yield and loop
+        let part = [0x00, 0x10, 0x00, 0x05, 0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+
+        let io = parts_io(&[(GamePart::Two, &part), (GamePart::Three, &part)], &[]);
+
+        let mut executor = executor_with(io, TestInput);
+
+        assert!(matches!(
+            executor.apply_bytecode_patch(GamePart::Two, 9, &[0x00, 0x00]),
+            Err(Error::PatchOutOfRange(9, 2, 10))
+        ));
+        executor
+            .apply_bytecode_patch(GamePart::Two, 2, &[0x00, 0x07])
+            .unwrap();
+
+        let mut watch = VariableWatch::new(&[VarId(0x10)], |id| executor.get_variable(id));
+        executor.run();
+        executor.run();
+        let changes = watch.diff(1, |id| executor.get_variable(id));
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].old, changes[0].new), (0, 7));
+
+        // the patch is reapplied when the part loads again, other parts are untouched
+        executor.jump_to_part(GamePart::Three);
+        executor.run();
+        assert_eq!(executor.get_variable(VarId(0x10)), 5);
+        executor.jump_to_part(GamePart::Two);
+        executor.run();
+        assert_eq!(executor.get_variable(VarId(0x10)), 7);
+    }
+
+    #[test]
+    fn softlock_fires_for_a_frozen_loop() {
+        // blit, yield and loop
+        let frozen = [0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+        // add 1 to 0x10, blit, yield and loop
+        let counting = [0x03, 0x10, 0x00, 0x01, 0x10, 0xff, 0x06, 0x07, 0x00, 0x00];
+
+        let io = parts_io(
+            &[(GamePart::Two, &frozen), (GamePart::Three, &counting)],
+            &[],
+        );
+
+        let mut executor = executor_with(io, TestInput);
+        executor.set_softlock_timeout(Some(10 * DEFAULT_TICK_MS));
+
+        assert_eq!(
+            executor.run_until(|e| e.is_softlocked(), 20),
+            RunUntilResult::Satisfied(11)
+        );
+
+        executor.reload_checkpoint();
+        assert!(!executor.is_softlocked());
+
+        executor.jump_to_part(GamePart::Three);
+        assert_eq!(
+            executor.run_until(|e| e.is_softlocked(), 50),
+            RunUntilResult::LimitReached
+        );
+    }
+
+    #[test]
+    fn thread_render_mask_hides_masked_draws() {
+        let part = [
+            0x08, 0x01, 0x00, 0x10, // setvec thread 1 to 0x10
+            0x12, 0x00, 0x03, 0x01, 0x00, 0x00, // draw string "2"
+            0x10, 0xff, // blit
+            0x06, // pause
+            0x07, 0x00, 0x04, // jmp 0x04
+            0x12, 0x00, 0x04, 0x01, 0x00, 0x00, // thread 1: draw string "3"
+            0x06, // pause
+            0x07, 0x00, 0x10, // jmp 0x10
+        ];
+
+        let mut executor = one_part_executor(&part);
+
+        for _ in 0..3 {
+            executor.run();
+        }
+        // thread 1 starts on the second frame and draws after thread 0 blits, so its
+        // string is shown with the third
+        assert_eq!(executor.gfx().strings, ["2", "2", "3", "2"]);
+
+        executor.set_thread_render_mask(1);
+        for _ in 0..3 {
+            executor.run();
+        }
+        assert_eq!(executor.gfx().strings[4..], ["2", "2", "2"]);
+    }
+
+    #[test]
+    fn scroll_override_replaces_the_copy_scroll() {
+        let part = [
+            0x00, 0xf9, 0x00, 0x10, // scroll y = 16
+            0x0f, 0x81, 0x02, // copy page 1 to 2 with scroll
+            0x10, 0xff, // blit
+            0x07, 0x00, 0x00, // jmp 0x00
+        ];
+
+        let mut executor = one_part_executor(&part);
+
+        executor.run();
+        executor.set_scroll_override(Some(-40));
+        executor.run();
+        assert_eq!(executor.get_variable(vars::SCROLL_Y), 16);
+        executor.set_scroll_override(None);
+        executor.run();
+
+        let scrolls: Vec<_> = executor.gfx().copies.iter().map(|c| c.2).collect();
+        assert_eq!(scrolls, [16, -40, 16]);
+    }
 }
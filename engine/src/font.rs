@@ -1,4 +1,9 @@
-pub const FONT: [u8; 768] = [
+use crate::error::Error;
+
+/// One byte per row of each 8x8 glyph, most significant bit on the left.
+pub type FontBitmap = [u8; FONT_SIZE];
+
+pub const FONT: FontBitmap = [
     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x10, 0x00,
     0x28, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x7E, 0x24, 0x24, 0x7E, 0x24, 0x00,
     0x08, 0x3E, 0x48, 0x3C, 0x12, 0x7C, 0x10, 0x00, 0x42, 0xA4, 0x48, 0x10, 0x24, 0x4A, 0x84, 0x00,
@@ -48,3 +53,224 @@ pub const FONT: [u8; 768] = [
     0xFE, 0xFE, 0xFE, 0xFE, 0xFE, 0xFE, 0xFE, 0x00, 0x38, 0x44, 0xBA, 0xA2, 0xBA, 0x44, 0x38, 0x00,
     0x38, 0x44, 0x82, 0x82, 0x44, 0x28, 0xEE, 0x00, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA,
 ];
+
+/// Width and height of a glyph in game pixels.
+pub const GLYPH_SIZE: i16 = 8;
+/// Glyphs cover the printable ASCII range starting at space.
+pub const GLYPH_COUNT: u8 = 96;
+
+/// Bytes in a font bitmap, see `FontBitmap`.
+pub const FONT_SIZE: usize = GLYPH_COUNT as usize * GLYPH_SIZE as usize;
+
+/// Glyphs per row in the font atlas texture, an 80x80 texel grid of 8x8 cells.
+pub const ATLAS_COLUMNS: u8 = 10;
+/// Width and height of the font atlas in texels.
+pub const ATLAS_SIZE: usize = ATLAS_COLUMNS as usize * GLYPH_SIZE as usize;
+/// Width and height of one glyph cell in atlas texture coordinates.
+pub const ATLAS_STEP: f32 = 1.0 / ATLAS_COLUMNS as f32;
+
+const PAGE_WIDTH: i16 = 320;
+const PAGE_HEIGHT: i16 = 200;
+
+/// Checks that `data` is a complete font bitmap, such as one read from a file to replace
+/// the built in `FONT`.
+pub fn font_from_bytes(data: &[u8]) -> Result<FontBitmap, Error> {
+    data.try_into()
+        .map_err(|_| Error::InvalidFontSize(data.len()))
+}
+
+/// Lays the glyphs of `font` out into an `ATLAS_SIZE` square texture, one byte per texel,
+/// `0xff` where a glyph is set and `0x00` elsewhere.
+pub fn build_atlas(font: &FontBitmap) -> Vec<u8> {
+    let size = GLYPH_SIZE as usize;
+    let columns = ATLAS_COLUMNS as usize;
+    let mut atlas = vec![0u8; ATLAS_SIZE * ATLAS_SIZE];
+
+    for (n, glyph) in font.chunks_exact(size).enumerate() {
+        let x_ind = (n % columns) * size;
+        let y_ind = (n / columns) * size;
+
+        for (y, row) in glyph.iter().enumerate() {
+            for x in 0..size {
+                if row & (0x80 >> x) != 0 {
+                    atlas[(y_ind + y) * ATLAS_SIZE + x_ind + x] = 0xff;
+                }
+            }
+        }
+    }
+
+    atlas
+}
+
+/// A character placed on the page by `layout_text`, `index` is its position in `FONT`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glyph {
+    pub index: u8,
+    pub x: i16,
+    pub y: i16,
+    /// Top left corner of the glyph's cell in the font atlas
+    pub uv: (f32, f32),
+}
+
+impl Glyph {
+    /// Two triangles covering the glyph, as `(position, uv)` pairs in page pixels and
+    /// atlas texture coordinates.
+    pub fn vertices(&self) -> [((f32, f32), (f32, f32)); 6] {
+        let (x, y) = (self.x as f32, self.y as f32);
+        let size = GLYPH_SIZE as f32;
+        let (u, v) = self.uv;
+        let step = ATLAS_STEP;
+
+        [
+            ((x, y), (u, v)),
+            ((x, y + size), (u, v + step)),
+            ((x + size, y), (u + step, v)),
+            ((x + size, y + size), (u + step, v + step)),
+            ((x, y + size), (u, v + step)),
+            ((x + size, y), (u + step, v)),
+        ]
+    }
+}
+
+/// Positions the characters of `text` starting at `x`, `y`. A `\n` returns to `x` on
+/// the next line, as does any character that would cross the right edge of the page.
+/// Glyphs that fall entirely outside the 320x200 page, and bytes with no glyph, are
+/// skipped; partially visible glyphs are kept for the renderer to clip.
+pub fn layout_text(text: &str, x: i16, y: i16) -> impl Iterator<Item = Glyph> + '_ {
+    let x_origin = x;
+    let mut x = x;
+    let mut y = y;
+
+    text.bytes().filter_map(move |c| {
+        if c == b'\n' {
+            x = x_origin;
+            y = y.saturating_add(GLYPH_SIZE);
+            return None;
+        }
+
+        if x > x_origin && x.saturating_add(GLYPH_SIZE) > PAGE_WIDTH {
+            x = x_origin;
+            y = y.saturating_add(GLYPH_SIZE);
+        }
+
+        let index = c.wrapping_sub(b' ');
+        let glyph = Glyph {
+            index,
+            x,
+            y,
+            uv: (
+                (index % ATLAS_COLUMNS) as f32 * ATLAS_STEP,
+                (index / ATLAS_COLUMNS) as f32 * ATLAS_STEP,
+            ),
+        };
+        x = x.saturating_add(GLYPH_SIZE);
+
+        let visible = glyph.x < PAGE_WIDTH
+            && glyph.y < PAGE_HEIGHT
+            && glyph.x.saturating_add(GLYPH_SIZE) > 0
+            && glyph.y.saturating_add(GLYPH_SIZE) > 0;
+
+        if visible && glyph.index < GLYPH_COUNT {
+            Some(glyph)
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(text: &str, x: i16, y: i16) -> Vec<(i16, i16)> {
+        layout_text(text, x, y).map(|g| (g.x, g.y)).collect()
+    }
+
+    #[test]
+    fn newline_returns_to_origin() {
+        assert_eq!(positions("AB\nC", 16, 8), [(16, 8), (24, 8), (16, 16)]);
+    }
+
+    #[test]
+    fn glyph_atlas_coordinates() {
+        let glyphs: Vec<Glyph> = layout_text("!+\nA", 8, 16).collect();
+        let uv: Vec<(u8, u8)> = glyphs
+            .iter()
+            .map(|g| ((g.uv.0 * 80.0).round() as u8, (g.uv.1 * 80.0).round() as u8))
+            .collect();
+
+        assert_eq!(
+            glyphs.iter().map(|g| g.index).collect::<Vec<_>>(),
+            [1, 11, 33]
+        );
+        assert_eq!(uv, [(8, 0), (8, 8), (24, 24)]);
+
+        let vertices = glyphs[2].vertices();
+        assert_eq!(vertices[0].0, (8.0, 24.0));
+        assert_eq!(vertices[3].0, (16.0, 32.0));
+        assert!((vertices[3].1 .0 - 0.4).abs() < 1e-6);
+    }
+
+    /// Rasterizes `text` onto a blank page the way the renderers do, sampling the atlas at
+    /// each glyph's texture coordinates.
+    fn render(font: &FontBitmap, text: &str) -> Vec<u8> {
+        let atlas = build_atlas(font);
+        let mut page = vec![0u8; PAGE_WIDTH as usize * PAGE_HEIGHT as usize];
+
+        for glyph in layout_text(text, 0, 0) {
+            let u = (glyph.uv.0 * ATLAS_SIZE as f32).round() as usize;
+            let v = (glyph.uv.1 * ATLAS_SIZE as f32).round() as usize;
+            for y in 0..GLYPH_SIZE as usize {
+                for x in 0..GLYPH_SIZE as usize {
+                    let texel = atlas[(v + y) * ATLAS_SIZE + u + x];
+                    let pixel = (glyph.y as usize + y) * PAGE_WIDTH as usize + glyph.x as usize + x;
+                    page[pixel] = texel;
+                }
+            }
+        }
+
+        page
+    }
+
+    #[test]
+    fn replacement_font_changes_rendered_text() {
+        let mut bold = FONT;
+        for row in bold.iter_mut() {
+            *row |= *row >> 1;
+        }
+
+        let original = render(&FONT, "HELLO");
+        let replaced = render(&bold, "HELLO");
+
+        assert_ne!(original, replaced);
+        assert_eq!(original, render(&FONT, "HELLO"));
+        assert!(original.iter().any(|&p| p != 0));
+    }
+
+    #[test]
+    fn font_bytes_must_fill_every_glyph() {
+        assert_eq!(font_from_bytes(&FONT).unwrap(), FONT);
+        assert!(matches!(
+            font_from_bytes(&FONT[..760]),
+            Err(Error::InvalidFontSize(760))
+        ));
+        assert_eq!(build_atlas(&FONT).len(), 80 * 80);
+    }
+
+    #[test]
+    fn wraps_at_right_edge() {
+        assert_eq!(
+            positions("ABCD", 296, 0),
+            [(296, 0), (304, 0), (312, 0), (296, 8)]
+        );
+    }
+
+    #[test]
+    fn skips_glyphs_off_page() {
+        assert_eq!(positions("AB", -12, 0), [(-4, 0)]);
+        assert_eq!(positions("A\nB\nC", 0, 184), [(0, 184), (0, 192)]);
+        assert_eq!(positions("A\nB", 0, -8), [(0, 0)]);
+        assert!(positions("A", 320, 0).is_empty());
+        assert!(positions("\x01\u{e9}", 0, 0).is_empty());
+    }
+}
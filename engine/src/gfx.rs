@@ -1,5 +1,36 @@
+use crate::font::FontBitmap;
 use crate::video::{Page, Polygon};
 
+/// Width of a video page in game pixels.
+pub const PAGE_WIDTH: usize = 320;
+/// Height of a video page in game pixels.
+pub const PAGE_HEIGHT: usize = 200;
+
+/// The color indices of all four video pages at game resolution, one byte per pixel in
+/// rows from the top, for putting the screen back along with the game state on rewind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSnapshot {
+    pages: [Vec<u8>; 4],
+}
+
+impl PageSnapshot {
+    /// Every page filled with color index `color`.
+    pub fn filled(color: u8) -> Self {
+        let page = vec![color; PAGE_WIDTH * PAGE_HEIGHT];
+        Self {
+            pages: [page.clone(), page.clone(), page.clone(), page],
+        }
+    }
+
+    pub fn page(&self, page: Page) -> &[u8] {
+        &self.pages[page as usize]
+    }
+
+    pub fn page_mut(&mut self, page: Page) -> &mut [u8] {
+        &mut self.pages[page as usize]
+    }
+}
+
 pub trait Gfx {
     fn blit(&mut self, page: Page);
     fn draw_polygon(&mut self, polygon: Polygon);
@@ -8,4 +39,17 @@ pub trait Gfx {
     fn copy_page(&mut self, src: Page, dest: Page, scroll: i16);
     fn set_palette(&mut self, palette: [(u8, u8, u8); 16]);
     fn draw_string(&mut self, text: &'static str, color: u8, x: i16, y: i16);
+    /// Replaces the font used by `draw_string`, backends without text can ignore it.
+    fn set_font(&mut self, _font: &FontBitmap) {}
+    /// Places a cursor sprite over the output frame at game pixel coordinates, or hides
+    /// it with `None`. The cursor is drawn after the game and never touches its pages.
+    fn draw_cursor(&mut self, _cursor: Option<(i16, i16)>) {}
+    /// Reads back the contents of every page for a rewind snapshot. Backends that can't
+    /// return `None`, their pages then keep whatever they hold when a snapshot is restored
+    /// until the game draws over them.
+    fn save_pages(&mut self) -> Option<PageSnapshot> {
+        None
+    }
+    /// Replaces the contents of every page with a snapshot taken by `save_pages`.
+    fn restore_pages(&mut self, _pages: &PageSnapshot) {}
 }
@@ -1,4 +1,6 @@
-#[derive(Debug, Copy, Clone)]
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Copy, Clone)]
 pub struct InputState {
     pub up: bool,
     pub left: bool,
@@ -6,8 +8,212 @@ pub struct InputState {
     pub down: bool,
     pub action: bool,
     pub turbo: bool,
+    pub rewind: bool,
+    /// Set while the frontend should hold the game still, frontends toggle it per press.
+    pub pause: bool,
+}
+
+impl InputState {
+    /// True when any game control is held, turbo, rewind and pause are not game controls.
+    pub fn is_active(&self) -> bool {
+        self.up || self.down || self.left || self.right || self.action
+    }
 }
 
 pub trait Input {
     fn get_input(&self) -> InputState;
 }
+
+/// Combines several input sources into one. Buttons held on any source count as held.
+/// Sources are kept in priority order, when one source holds a direction and another
+/// holds its opposite the earlier source wins, so a keyboard added first overrides a
+/// drifting gamepad rather than both cancelling out.
+#[derive(Default)]
+pub struct CompositeInput {
+    sources: Vec<Box<dyn Input + Send>>,
+}
+
+impl CompositeInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source with lower priority than every source already added.
+    pub fn push<I: Input + Send + 'static>(&mut self, source: I) {
+        self.sources.push(Box::new(source));
+    }
+
+    pub fn with<I: Input + Send + 'static>(mut self, source: I) -> Self {
+        self.push(source);
+        self
+    }
+}
+
+impl Input for CompositeInput {
+    fn get_input(&self) -> InputState {
+        let mut state = InputState::default();
+        let mut vertical = false;
+        let mut horizontal = false;
+
+        for source in &self.sources {
+            let input = source.get_input();
+
+            if !vertical && (input.up || input.down) {
+                state.up = input.up;
+                state.down = input.down;
+                vertical = true;
+            }
+
+            if !horizontal && (input.left || input.right) {
+                state.left = input.left;
+                state.right = input.right;
+                horizontal = true;
+            }
+
+            state.action |= input.action;
+            state.turbo |= input.turbo;
+            state.rewind |= input.rewind;
+            state.pause |= input.pause;
+        }
+
+        state
+    }
+}
+
+/// Latches game buttons pressed between polls, so a tap released before the next frame
+/// samples input still reaches the game. Frontends call `press` as key events arrive and
+/// `take` once per frame. Presses older than the window are dropped rather than
+/// delivered late, a zero window disables latching.
+#[derive(Debug, Clone)]
+pub struct InputBuffer {
+    window: Duration,
+    latched: InputState,
+    pressed_at: Option<Instant>,
+}
+
+impl InputBuffer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            latched: InputState::default(),
+            pressed_at: None,
+        }
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Records the buttons held in `pressed`, turbo, rewind and pause are never latched.
+    pub fn press(&mut self, pressed: InputState, now: Instant) {
+        if self.window.is_zero() || !pressed.is_active() {
+            return;
+        }
+
+        self.latched.up |= pressed.up;
+        self.latched.down |= pressed.down;
+        self.latched.left |= pressed.left;
+        self.latched.right |= pressed.right;
+        self.latched.action |= pressed.action;
+        self.pressed_at = Some(now);
+    }
+
+    /// Merges the latched presses into the buttons currently `held` and clears them.
+    pub fn take(&mut self, held: InputState, now: Instant) -> InputState {
+        let latched = std::mem::take(&mut self.latched);
+        let fresh = self
+            .pressed_at
+            .take()
+            .is_some_and(|at| now.saturating_duration_since(at) <= self.window);
+
+        if !fresh {
+            return held;
+        }
+
+        InputState {
+            up: held.up || latched.up,
+            down: held.down || latched.down,
+            left: held.left || latched.left,
+            right: held.right || latched.right,
+            action: held.action || latched.action,
+            ..held
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockInput(InputState);
+
+    impl Input for MockInput {
+        fn get_input(&self) -> InputState {
+            self.0
+        }
+    }
+
+    #[test]
+    fn composite_merges_sources() {
+        let keyboard = InputState {
+            left: true,
+            turbo: true,
+            ..Default::default()
+        };
+        let gamepad = InputState {
+            right: true,
+            up: true,
+            action: true,
+            rewind: true,
+            pause: true,
+            ..Default::default()
+        };
+
+        let input = CompositeInput::new()
+            .with(MockInput(keyboard))
+            .with(MockInput(gamepad))
+            .get_input();
+
+        assert!(input.left && !input.right);
+        assert!(input.up && !input.down);
+        assert!(input.action && input.turbo && input.rewind && input.pause);
+
+        let input = CompositeInput::new()
+            .with(MockInput(gamepad))
+            .with(MockInput(keyboard))
+            .get_input();
+
+        assert!(input.right && !input.left);
+        assert!(
+            !CompositeInput::new()
+                .with(MockInput(keyboard))
+                .get_input()
+                .pause
+        );
+    }
+
+    #[test]
+    fn buffered_tap_within_a_frame_is_seen_once() {
+        let action = InputState {
+            action: true,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut buffer = InputBuffer::new(Duration::from_millis(50));
+
+        // pressed and released between two frames
+        buffer.press(action, start);
+        let frame = start + Duration::from_millis(20);
+        assert!(buffer.take(InputState::default(), frame).action);
+        assert!(!buffer.take(InputState::default(), frame).action);
+
+        // a tap from before a long stall is not delivered late
+        buffer.press(action, start);
+        let late = start + Duration::from_millis(200);
+        assert!(!buffer.take(InputState::default(), late).action);
+
+        let mut disabled = InputBuffer::new(Duration::ZERO);
+        disabled.press(action, start);
+        assert!(!disabled.take(InputState::default(), start).action);
+    }
+}
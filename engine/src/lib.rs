@@ -1,16 +1,105 @@
+//! An interpreter for the Another World virtual machine.
+//!
+//! Frontends usually implement [`Io`], [`Gfx`] and [`Input`] and hand them to an
+//! [`Executor`], which runs the game loop, rewind, attract mode and audio. The pieces it
+//! is built from are public too, for embedding the game in a loop of your own:
+//!
+//! - [`Resources`] loads game data through an [`Io`] and holds the current part
+//! - [`Vm`] runs the bytecode a frame at a time, queueing video and audio commands
+//! - [`Video`] turns video commands into calls on a [`Gfx`]
+//!
+//! ```no_run
+//! use engine::error::Error;
+//! use engine::input::InputState;
+//! use engine::resources::GamePart;
+//! use engine::video::{Page, Polygon};
+//! use engine::vm::{FrameResult, Yield};
+//! use engine::{Gfx, Io, Resources, Video, Vm};
+//!
+//! struct DirectoryIo(std::path::PathBuf);
+//!
+//! impl Io for DirectoryIo {
+//!     type Reader = std::fs::File;
+//!
+//!     fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
+//!         Ok(std::fs::File::open(self.0.join(name.as_ref()))?)
+//!     }
+//! }
+//!
+//! struct NullGfx;
+//!
+//! impl Gfx for NullGfx {
+//!     fn blit(&mut self, _page: Page) {}
+//!     fn draw_polygon(&mut self, _polygon: Polygon) {}
+//!     fn fill_page(&mut self, _page: Page, _color: u8) {}
+//!     fn select_page(&mut self, _page: Page) {}
+//!     fn copy_page(&mut self, _src: Page, _dest: Page, _scroll: i16) {}
+//!     fn set_palette(&mut self, _palette: [(u8, u8, u8); 16]) {}
+//!     fn draw_string(&mut self, _text: &'static str, _color: u8, _x: i16, _y: i16) {}
+//! }
+//!
+//! fn main() -> Result<(), Error> {
+//!     let mut resources = Resources::load(DirectoryIo("games/aw".into()))?;
+//!     let mut vm = Vm::new(resources.version(), true);
+//!     let mut video = Video::new(NullGfx);
+//!     resources.prepare_part(GamePart::Two);
+//!
+//!     for _ in 0..1000 {
+//!         let bytecode = resources.bytecode().ok_or(Error::MissingPartData)?;
+//!         let result = vm.execute_frame(bytecode, InputState::default())?;
+//!         vm.audio_commands().for_each(drop);
+//!
+//!         match result {
+//!             FrameResult::Yield(Yield::Blit(ms)) => {
+//!                 for command in vm.video_commands() {
+//!                     video.push_command(command, &resources);
+//!                 }
+//!                 std::thread::sleep(std::time::Duration::from_millis(ms));
+//!             }
+//!             FrameResult::Yield(Yield::ReqResource(resource_id)) => {
+//!                 resources.load_part_or_entry(resource_id)
+//!             }
+//!             FrameResult::Complete => {
+//!                 if let Some(part) = resources.requested_part() {
+//!                     resources.prepare_part(part);
+//!                     vm.init_part();
+//!                 }
+//!             }
+//!         }
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+pub mod audio;
+pub mod clock;
+pub mod codes;
+pub mod demo;
+pub mod desync;
 pub mod error;
+pub mod events;
 pub mod executor;
 pub mod font;
 pub mod gfx;
 pub mod input;
+pub mod options;
 pub mod resources;
+pub mod retry;
+pub mod rewind;
+pub mod softlock;
 pub mod strings;
+pub mod tessellate;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod video;
 pub mod vm;
+pub mod watch;
 
 pub use executor::Executor;
 pub use gfx::Gfx;
 pub use input::Input;
+pub use options::EngineOptions;
 pub use resources::{Io, Resources};
 pub use video::Video;
 pub use vm::Vm;
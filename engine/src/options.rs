@@ -0,0 +1,285 @@
+use crate::resources::GamePart;
+use crate::video::PaletteMode;
+use crate::vm::VarId;
+
+/// Options shared by every frontend, parsed the same way from command line
+/// arguments and URL query strings. Names are given without leading dashes,
+/// `_` and `-` are interchangeable.
+///
+/// | name                     | value  | effect                                   |
+/// |--------------------------|--------|------------------------------------------|
+/// | `data-path`, `d`         | path   | directory holding the game data          |
+/// | `scale`, `s`             | u32    | window size multiplier                   |
+/// | `copy-protection`        |        | run the copy protection screen           |
+/// | `skip-intro`             |        | jump straight to the first gameplay part |
+/// | `part`                   | 1-10   | game part to boot into                   |
+/// | `tick-ms`                | u64    | ms per sleep tick, 20 is authentic       |
+/// | `seed`                   | i16    | initial random seed                      |
+/// | `code`                   | code   | resume at a checkpoint code, e.g. LDKD   |
+/// | `attract-demo`           | name   | input recording to play when idle        |
+/// | `attract-timeout`        | u64    | seconds without input before the demo    |
+/// | `load-budget`            | usize  | resource loads per frame before yielding |
+/// | `memory-budget`          | usize  | bytes of loaded resources to keep        |
+/// | `blit-budget`            | usize  | blits without sleep per frame before yielding |
+/// | `palette-mode`           | mode   | accessibility color remap                |
+/// | `step-limit`             | usize  | instructions per VM frame, 0 for no limit |
+/// | `command-cap`            | usize  | video commands between blits before warning, 0 off |
+/// | `input-buffer`           | u64    | ms a tap is held for the next frame, 0 off |
+/// | `coalesce`               |        | drop fills and copies that are overwritten |
+/// | `debug-clear`            |        | show never filled pages as magenta       |
+/// | `watch`                  | ids    | log changes to these variables, e.g. 0xf9,0x3c |
+/// | `softlock-timeout`       | u64    | seconds without change before warning, 0 off |
+///
+/// Palette modes are `normal`, `grayscale`, and color blind corrections for
+/// `protanopia`, `deuteranopia` and `tritanopia`.
+///
+/// Values that fail to parse are ignored and leave the default in place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineOptions {
+    pub data_path: Option<String>,
+    pub scale: Option<u32>,
+    pub bypass_protection: bool,
+    pub skip_intro: bool,
+    pub start_part: Option<GamePart>,
+    pub tick_ms: Option<u64>,
+    pub seed: Option<i16>,
+    /// Loaded through the game's `Io`, so it lives alongside the game data
+    pub attract_demo: Option<String>,
+    pub attract_timeout: Option<u64>,
+    pub code: Option<String>,
+    pub load_budget: Option<usize>,
+    pub memory_budget: Option<usize>,
+    pub blit_budget: Option<usize>,
+    pub palette_mode: PaletteMode,
+    pub step_limit: Option<usize>,
+    pub command_cap: Option<usize>,
+    pub input_buffer_ms: Option<u64>,
+    pub coalesce: bool,
+    pub debug_clear: bool,
+    /// Variable ids, decimal or `0x` hex, separated by commas
+    pub watch_variables: Vec<VarId>,
+    pub softlock_timeout: Option<u64>,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            data_path: None,
+            scale: None,
+            bypass_protection: true,
+            skip_intro: false,
+            start_part: None,
+            tick_ms: None,
+            seed: None,
+            attract_demo: None,
+            attract_timeout: None,
+            code: None,
+            load_budget: None,
+            memory_budget: None,
+            blit_budget: None,
+            palette_mode: PaletteMode::Normal,
+            step_limit: None,
+            command_cap: None,
+            input_buffer_ms: None,
+            coalesce: false,
+            debug_clear: false,
+            watch_variables: Vec::new(),
+            softlock_timeout: None,
+        }
+    }
+}
+
+impl EngineOptions {
+    /// Parses `--name value` and `--name` style arguments, returning any
+    /// arguments that are not engine options, in order, for the frontend to handle.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> (Self, Vec<String>) {
+        let mut options = Self::default();
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let name = arg.trim_start_matches('-');
+            match Self::takes_value(name) {
+                Some(true) => {
+                    let value = args.next();
+                    options.set(name, value.as_deref());
+                }
+                Some(false) => {
+                    options.set(name, None);
+                }
+                None => rest.push(arg),
+            }
+        }
+
+        (options, rest)
+    }
+
+    /// Parses a `name=value&flag` query string, with or without the leading `?`.
+    pub fn from_query(query: &str) -> Self {
+        let mut options = Self::default();
+
+        for pair in query.trim_start_matches('?').split('&') {
+            let mut parts = pair.splitn(2, '=');
+            let name = parts.next().unwrap_or("");
+            let value = parts.next();
+
+            if Self::takes_value(name).is_some() {
+                options.set(name, value);
+            }
+        }
+
+        options
+    }
+
+    fn takes_value(name: &str) -> Option<bool> {
+        match Self::canonical(name)? {
+            "data-path" | "scale" | "part" | "tick-ms" | "seed" | "attract-demo"
+            | "attract-timeout" | "code" | "load-budget" | "memory-budget" | "blit-budget"
+            | "palette-mode" | "step-limit" | "command-cap" | "input-buffer" | "watch"
+            | "softlock-timeout" => Some(true),
+            _ => Some(false),
+        }
+    }
+
+    fn canonical(name: &str) -> Option<&'static str> {
+        let name = name.replace('_', "-");
+        let name = match name.as_str() {
+            "d" | "data-path" => "data-path",
+            "s" | "scale" => "scale",
+            "copy-protection" => "copy-protection",
+            "skip-intro" => "skip-intro",
+            "part" => "part",
+            "tick-ms" => "tick-ms",
+            "seed" => "seed",
+            "attract-demo" => "attract-demo",
+            "attract-timeout" => "attract-timeout",
+            "code" => "code",
+            "load-budget" => "load-budget",
+            "memory-budget" => "memory-budget",
+            "blit-budget" => "blit-budget",
+            "palette-mode" => "palette-mode",
+            "step-limit" => "step-limit",
+            "command-cap" => "command-cap",
+            "input-buffer" => "input-buffer",
+            "coalesce" => "coalesce",
+            "debug-clear" => "debug-clear",
+            "watch" => "watch",
+            "softlock-timeout" => "softlock-timeout",
+            _ => return None,
+        };
+
+        Some(name)
+    }
+
+    fn set(&mut self, name: &str, value: Option<&str>) {
+        match Self::canonical(name) {
+            Some("data-path") => self.data_path = value.map(String::from),
+            Some("scale") => self.scale = parse(value),
+            Some("copy-protection") => self.bypass_protection = false,
+            Some("skip-intro") => self.skip_intro = true,
+            Some("part") => self.start_part = parse(value).and_then(GamePart::from_number),
+            Some("tick-ms") => self.tick_ms = parse(value),
+            Some("seed") => self.seed = parse(value),
+            Some("attract-demo") => self.attract_demo = value.map(String::from),
+            Some("attract-timeout") => self.attract_timeout = parse(value),
+            Some("code") => self.code = value.map(String::from),
+            Some("load-budget") => self.load_budget = parse(value),
+            Some("memory-budget") => self.memory_budget = parse(value),
+            Some("blit-budget") => self.blit_budget = parse(value),
+            Some("step-limit") => self.step_limit = parse(value),
+            Some("command-cap") => self.command_cap = parse(value),
+            Some("input-buffer") => self.input_buffer_ms = parse(value),
+            Some("coalesce") => self.coalesce = true,
+            Some("debug-clear") => self.debug_clear = true,
+            Some("softlock-timeout") => self.softlock_timeout = parse(value),
+            Some("watch") => {
+                self.watch_variables = value
+                    .map(|v| v.split(',').filter_map(parse_variable_id).collect())
+                    .unwrap_or_default()
+            }
+            Some("palette-mode") => self.palette_mode = parse(value).unwrap_or(self.palette_mode),
+            _ => (),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: Option<&str>) -> Option<T> {
+    value.and_then(|v| v.parse().ok())
+}
+
+fn parse_variable_id(value: &str) -> Option<VarId> {
+    let value = value.trim();
+    let id = match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    };
+    id.map(VarId)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn args_and_query_agree() {
+        let (from_args, rest) = EngineOptions::from_args(args(&[
+            "-d",
+            "games/aw",
+            "--scale",
+            "3",
+            "--pixel-accurate",
+            "--skip-intro",
+            "--part",
+            "4",
+            "--tick-ms",
+            "16",
+            "--seed",
+            "42",
+            "--copy-protection",
+            "--code",
+            "LDKD",
+        ]));
+        let from_query = EngineOptions::from_query(
+            "?data-path=games/aw&scale=3&skip_intro&part=4&tick-ms=16&seed=42&copy-protection&code=LDKD",
+        );
+
+        assert_eq!(rest, args(&["--pixel-accurate"]));
+        assert_eq!(from_args, from_query);
+        assert_eq!(from_args.data_path.as_deref(), Some("games/aw"));
+        assert_eq!(from_args.scale, Some(3));
+        assert!(!from_args.bypass_protection);
+        assert!(from_args.skip_intro);
+        assert_eq!(from_args.start_part, Some(GamePart::Four));
+        assert_eq!(from_args.tick_ms, Some(16));
+        assert_eq!(from_args.seed, Some(42));
+        assert_eq!(from_args.code.as_deref(), Some("LDKD"));
+    }
+
+    #[test]
+    fn invalid_values_keep_defaults() {
+        let options = EngineOptions::from_query("scale=big&part=11&tick-ms=16&unknown=1");
+
+        assert_eq!(options.scale, None);
+        assert_eq!(options.start_part, None);
+        assert_eq!(options.tick_ms, Some(16));
+        assert!(options.bypass_protection);
+
+        let options = EngineOptions::from_query("palette-mode=sepia");
+        assert_eq!(options.palette_mode, PaletteMode::Normal);
+        let options = EngineOptions::from_query("palette_mode=deuteranopia");
+        assert_eq!(options.palette_mode, PaletteMode::Deuteranopia);
+    }
+
+    #[test]
+    fn watch_takes_decimal_and_hex_ids() {
+        let options = EngineOptions::from_query("watch=0xf9,60,0x1ff,x");
+        assert_eq!(options.watch_variables, [VarId(0xf9), VarId(60)]);
+
+        let (options, _) = EngineOptions::from_args(args(&["--watch", "0x3c"]));
+        assert_eq!(options.watch_variables, [VarId(0x3c)]);
+    }
+}
@@ -9,8 +9,19 @@ pub trait Io {
 
     fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error>;
 
-    fn entry(&self, entry: &MemEntry) -> Result<Vec<u8>, Error> {
-        let mut reader = self.load(entry.bank_id.name())?;
+    fn exists<S: AsRef<str>>(&self, name: S) -> bool {
+        self.load(name).is_ok()
+    }
+
+    fn entry(&self, entry: &MemEntry, version: GameVersion) -> Result<Vec<u8>, Error> {
+        let mut reader = self.load(entry.bank_id.name(version)?)?;
+
+        if version == GameVersion::FifteenthAnniversary {
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf)?;
+            return Ok(buf);
+        }
+
         reader.seek(SeekFrom::Start(entry.bank_offset as u64))?;
         let mut buf = vec![0; entry.packed_size as usize];
         reader.read_exact(&mut buf)?;
@@ -18,12 +29,17 @@ pub trait Io {
         if entry.packed_size == entry.size {
             Ok(buf)
         } else {
-            let decoder = Decoder::new(entry, buf);
-            decoder.decode()
+            unpack(buf, entry.size)
         }
     }
 }
 
+/// Decompresses a packed bank entry into a buffer of `size` bytes. Malformed input
+/// returns an error rather than panicking, as the data comes from untrusted files.
+pub fn unpack(packed: Vec<u8>, size: u16) -> Result<Vec<u8>, Error> {
+    Decoder::new(packed, size).decode()
+}
+
 struct Decoder {
     crc: u32,
     check: u32,
@@ -36,16 +52,16 @@ struct Decoder {
 }
 
 impl Decoder {
-    fn new(entry: &MemEntry, input: Vec<u8>) -> Self {
+    fn new(input: Vec<u8>, size: u16) -> Self {
         Self {
             crc: 0,
             check: 0,
             data_size: 0,
             size: 0,
-            output: vec![0; entry.size as usize],
-            output_cursor: entry.size as usize - 1,
+            output: vec![0; size as usize],
+            output_cursor: (size as usize).wrapping_sub(1),
+            input_cursor: input.len(),
             input,
-            input_cursor: entry.packed_size as usize,
         }
     }
 
@@ -126,7 +142,7 @@ impl Decoder {
             let out = self
                 .output
                 .get_mut(self.output_cursor)
-                .expect("write within buffer");
+                .ok_or(Error::CorruptPackedData)?;
             *out = value as u8;
             self.output_cursor = self.output_cursor.wrapping_sub(1);
         }
@@ -139,13 +155,14 @@ impl Decoder {
         self.data_size -= count as i32;
         for _ in 0..count {
             let value = *self
-                .output
-                .get(self.output_cursor + i as usize)
-                .expect("read within buffer");
+                .output_cursor
+                .checked_add(i as usize)
+                .and_then(|idx| self.output.get(idx))
+                .ok_or(Error::CorruptPackedData)?;
             let out = self
                 .output
                 .get_mut(self.output_cursor)
-                .expect("write within buffer");
+                .ok_or(Error::CorruptPackedData)?;
             *out = value;
             self.output_cursor = self.output_cursor.wrapping_sub(1);
         }
@@ -177,41 +194,205 @@ impl Decoder {
     }
 }
 
+/// Called with the number of entries loaded so far and the total being loaded.
+pub type LoadProgress = Box<dyn FnMut(usize, usize) + Send>;
+
 pub struct Resources<T: Io> {
     io: T,
+    version: GameVersion,
     loaded_part: Option<GamePart>,
+    /// Part started by `begin_part`, with the entries loaded so far and the total
+    loading_part: Option<(GamePart, usize, usize)>,
     entries: Vec<MemEntry>,
     requested_part: Option<GamePart>,
+    progress: Option<LoadProgress>,
+    bytes_loaded: u64,
+    memory_budget: Option<usize>,
+    load_sequence: u64,
+    /// Entry index, offset and replacement bytes, applied in order whenever the entry loads
+    patches: Vec<(usize, usize, Vec<u8>)>,
 }
 
 impl<T: Io> Resources<T> {
     pub fn load(io: T) -> Result<Self, Error> {
-        let mut mem_list = std::io::BufReader::new(io.load("MEMLIST.BIN")?);
-        let mut entries = Vec::new();
-        while let Some(entry) = MemEntry::next(&mut mem_list)? {
-            entries.push(entry);
-        }
+        let version = Self::detect_version(&io).ok_or(Error::UnknownGameVersion)?;
+        log::info!("detected version: {:?}", version);
+
+        let entries = match version {
+            GameVersion::Dos => {
+                let mut mem_list = std::io::BufReader::new(io.load("MEMLIST.BIN")?);
+                let mut entries = Vec::new();
+                while let Some(entry) = MemEntry::next(&mut mem_list)? {
+                    entries.push(entry);
+                }
+                entries
+            }
+            GameVersion::FifteenthAnniversary => (0..FIFTEENTH_ANNIVERSARY_ENTRIES)
+                .map(MemEntry::single_file)
+                .collect(),
+        };
         eprintln!("found entries: {}", entries.len());
 
         Ok(Resources {
             io,
+            version,
             loaded_part: None,
+            loading_part: None,
             entries,
             requested_part: None,
+            progress: None,
+            bytes_loaded: 0,
+            memory_budget: None,
+            load_sequence: 0,
+            patches: Vec::new(),
         })
     }
 
+    /// Reports progress after each entry is loaded, including entries that fail to load.
+    pub fn set_progress_callback(&mut self, progress: Option<LoadProgress>) {
+        self.progress = progress;
+    }
+
+    pub fn detect_version(io: &T) -> Option<GameVersion> {
+        if io.exists("MEMLIST.BIN") {
+            Some(GameVersion::Dos)
+        } else if io.exists("Game/DAT/FILE017.DAT") {
+            Some(GameVersion::FifteenthAnniversary)
+        } else {
+            None
+        }
+    }
+
+    pub fn version(&self) -> GameVersion {
+        self.version
+    }
+
+    /// Total bytes of entry data read and unpacked since creation.
+    pub fn bytes_loaded(&self) -> u64 {
+        self.bytes_loaded
+    }
+
+    pub fn loaded_part(&self) -> Option<GamePart> {
+        self.loaded_part
+    }
+
+    /// Caps the bytes held by loaded entries. Once over the budget, entries loaded on
+    /// request by the scripts are dropped oldest first until back under it. The current
+    /// part's palette, bytecode and polygon data are never dropped, and neither is the
+    /// entry just requested, so the budget can still be exceeded by those alone.
+    /// Dropped entries are loaded again when requested or passed to `reload_evicted`.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+        self.enforce_memory_budget(None);
+    }
+
+    /// Bytes held by loaded entries.
+    pub fn memory_used(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|e| match e.state {
+                MemEntryState::Loaded(ref data) => data.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Loads an entry again if the memory budget dropped it, for resources like sounds
+    /// that scripts may use long after requesting them.
+    pub fn reload_evicted(&mut self, resource_id: u16) {
+        let idx = resource_id as usize;
+        if let Some(entry) = self.entries.get_mut(idx) {
+            if let MemEntryState::Evicted = entry.state {
+                entry.state = MemEntryState::Requested;
+                self.load_requested();
+                self.enforce_memory_budget(Some(idx));
+            }
+        }
+    }
+
+    fn enforce_memory_budget(&mut self, keep: Option<usize>) {
+        let budget = match self.memory_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+
+        let essential = self.loaded_part.map(|part| {
+            [
+                Some(part.palette()),
+                Some(part.bytecode()),
+                Some(part.cinematic()),
+                part.alt_video(),
+            ]
+        });
+        let is_essential =
+            |idx: usize| essential.is_some_and(|e| e.contains(&Some(idx))) || keep == Some(idx);
+
+        let mut used = self.memory_used();
+        while used > budget {
+            let oldest = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(idx, e)| {
+                    !is_essential(*idx) && matches!(e.state, MemEntryState::Loaded(_))
+                })
+                .min_by_key(|(_, e)| e.load_sequence)
+                .map(|(idx, _)| idx);
+
+            let entry = match oldest.and_then(|idx| self.entries.get_mut(idx)) {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            if let MemEntryState::Loaded(ref data) = entry.state {
+                used -= data.len();
+            }
+            entry.state = MemEntryState::Evicted;
+        }
+    }
+
     pub fn prepare_part(&mut self, part: GamePart) {
+        self.begin_part(part);
+        while self.load_part_entry() {}
+    }
+
+    /// Unloads the current part and queues the entries of `part`, for loading them one at
+    /// a time with `load_part_entry`. `loaded_part` stays `None` until they are all in.
+    pub fn begin_part(&mut self, part: GamePart) {
         if self.loaded_part == Some(part) {
             return;
         }
 
         self.unload();
-
         self.request_part(part);
 
-        self.load_requested();
-        self.loaded_part = Some(part);
+        let total = self
+            .entries
+            .iter()
+            .filter(|e| matches!(e.state, MemEntryState::Requested))
+            .count();
+        self.loading_part = Some((part, 0, total));
+    }
+
+    /// Loads the next entry queued by `begin_part`. Returns false once there are none
+    /// left, at which point the part is loaded.
+    pub fn load_part_entry(&mut self) -> bool {
+        let (part, loaded, total) = match self.loading_part {
+            Some(loading) => loading,
+            None => return false,
+        };
+
+        if !self.load_next_requested() {
+            self.loading_part = None;
+            self.loaded_part = Some(part);
+            return false;
+        }
+
+        self.loading_part = Some((part, loaded + 1, total));
+        if let Some(progress) = self.progress.as_mut() {
+            progress(loaded + 1, total);
+        }
+        true
     }
 
     fn unload(&mut self) {
@@ -219,6 +400,7 @@ impl<T: Io> Resources<T> {
             entry.state = MemEntryState::NotNeeded;
         }
         self.loaded_part = None;
+        self.loading_part = None;
     }
 
     pub fn requested_part(&mut self) -> Option<GamePart> {
@@ -244,34 +426,81 @@ impl<T: Io> Resources<T> {
     }
 
     fn load_requested(&mut self) {
-        for entry in self.entries.iter_mut() {
-            if let MemEntryState::Requested = entry.state {
-                match self.io.entry(entry) {
-                    Ok(data) => {
-                        entry.state = MemEntryState::Loaded(data);
-                    }
-                    Err(err) => {
-                        eprintln!("unable to load resource: {:?} {:?}", err, entry);
-                        entry.state = MemEntryState::NotNeeded;
-                    }
-                }
+        let total = self
+            .entries
+            .iter()
+            .filter(|e| matches!(e.state, MemEntryState::Requested))
+            .count();
+        let mut loaded = 0;
+
+        while self.load_next_requested() {
+            loaded += 1;
+            if let Some(progress) = self.progress.as_mut() {
+                progress(loaded, total);
             }
         }
     }
 
+    /// Loads the first requested entry, returning false when none are requested.
+    fn load_next_requested(&mut self) -> bool {
+        let (idx, entry) = match self
+            .entries
+            .iter_mut()
+            .enumerate()
+            .find(|(_, e)| matches!(e.state, MemEntryState::Requested))
+        {
+            Some(requested) => requested,
+            None => return false,
+        };
+
+        match self.io.entry(entry, self.version) {
+            Ok(mut data) => {
+                apply_patches(&self.patches, idx, &mut data);
+                self.bytes_loaded += data.len() as u64;
+                self.load_sequence += 1;
+                entry.load_sequence = self.load_sequence;
+                entry.state = MemEntryState::Loaded(data);
+            }
+            Err(err) => {
+                log::warn!("unable to load resource: {:?} {:?}", err, entry);
+                entry.state = MemEntryState::NotNeeded;
+            }
+        }
+
+        true
+    }
+
     pub fn load_part_or_entry(&mut self, resource_id: u16) {
         if resource_id as usize > self.entries.len() {
             self.requested_part = GamePart::from(resource_id);
         } else {
             if let Some(entry) = self.entries.get_mut(resource_id as usize) {
-                if let MemEntryState::NotNeeded = entry.state {
+                if let MemEntryState::NotNeeded | MemEntryState::Evicted = entry.state {
                     entry.state = MemEntryState::Requested;
                     self.load_requested();
+                    self.enforce_memory_budget(Some(resource_id as usize));
                 }
             }
         }
     }
 
+    /// Loads entry `resource_id` for playing as a sound. Part ids and entries of any
+    /// known type other than sound are refused, entries of unknown type, as in the 15th
+    /// Anniversary Edition, are loaded as-is.
+    pub fn load_sound(&mut self, resource_id: u16) -> Result<&[u8], Error> {
+        let entry = self
+            .entries
+            .get(resource_id as usize)
+            .ok_or(Error::InvalidEntryId(resource_id))?;
+        if !matches!(entry.kind, ResourceType::Sound | ResourceType::Unknown) {
+            return Err(Error::NotASound(resource_id));
+        }
+
+        self.load_part_or_entry(resource_id);
+        self.entry_data(resource_id)
+            .ok_or(Error::MissingEntryData(resource_id))
+    }
+
     pub fn palette(&self) -> Option<&[u8]> {
         self.segment(|s| Some(s.palette()))
     }
@@ -288,6 +517,65 @@ impl<T: Io> Resources<T> {
         self.segment(GamePart::alt_video)
     }
 
+    /// Data for a single entry, if it has been loaded.
+    pub fn entry_data(&self, resource_id: u16) -> Option<&[u8]> {
+        match self.entries.get(resource_id as usize)?.state {
+            MemEntryState::Loaded(ref data) => Some(data.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Every part's bytecode, read straight from the game data without touching the
+    /// loaded part, for analyzing scripts across the whole game. Parts nine and ten
+    /// share the same bytecode.
+    pub fn all_bytecode(&self) -> Result<Vec<(GamePart, Vec<u8>)>, Error> {
+        GamePart::ALL
+            .iter()
+            .map(|&part| {
+                let entry = self
+                    .entries
+                    .get(part.bytecode())
+                    .ok_or(Error::MissingPartData)?;
+                Ok((part, self.io.entry(entry, self.version)?))
+            })
+            .collect()
+    }
+
+    /// Overwrites `bytes` of `part`'s bytecode starting at `offset`, now if it is loaded
+    /// and again each time it loads, so the patch survives part changes. Patches apply
+    /// in the order they were added and parts nine and ten share one patched bytecode.
+    ///
+    /// Offsets refer to one release of the game data, a patch written for one version
+    /// will break scripts in any other. A patch that does not fit in the part's bytecode
+    /// returns `PatchOutOfRange` and is not applied.
+    pub fn apply_bytecode_patch(
+        &mut self,
+        part: GamePart,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<(), Error> {
+        let idx = part.bytecode();
+        let entry = self.entries.get(idx).ok_or(Error::MissingPartData)?;
+        let size = match entry.state {
+            MemEntryState::Loaded(ref data) => data.len(),
+            _ if entry.size > 0 => entry.size as usize,
+            _ => self.io.entry(entry, self.version)?.len(),
+        };
+
+        if offset.checked_add(bytes.len()).is_none_or(|end| end > size) {
+            return Err(Error::PatchOutOfRange(offset, bytes.len(), size));
+        }
+
+        self.patches.push((idx, offset, bytes.to_vec()));
+        if let Some(entry) = self.entries.get_mut(idx) {
+            if let MemEntryState::Loaded(ref mut data) = entry.state {
+                apply_patches(&self.patches[self.patches.len() - 1..], idx, data);
+            }
+        }
+
+        Ok(())
+    }
+
     fn segment<F: Fn(&GamePart) -> Option<usize>>(&self, f: F) -> Option<&[u8]> {
         self.loaded_part
             .and_then(|p| f(&p))
@@ -299,6 +587,19 @@ impl<T: Io> Resources<T> {
     }
 }
 
+fn apply_patches(patches: &[(usize, usize, Vec<u8>)], idx: usize, data: &mut [u8]) {
+    for (_, offset, bytes) in patches.iter().filter(|(entry, _, _)| *entry == idx) {
+        match data.get_mut(*offset..*offset + bytes.len()) {
+            Some(dest) => dest.copy_from_slice(bytes),
+            None => log::warn!(
+                "skipping bytecode patch at {:#x} outside entry {:#x}",
+                offset,
+                idx
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MemEntry {
     state: MemEntryState,
@@ -307,6 +608,8 @@ pub struct MemEntry {
     bank_offset: u32,
     packed_size: u16,
     size: u16,
+    /// Orders loaded entries for eviction, higher was loaded more recently
+    load_sequence: u64,
 }
 
 impl MemEntry {
@@ -315,13 +618,18 @@ impl MemEntry {
         if state == 255 {
             return Ok(None);
         }
+        // The state is the original engine's bookkeeping, and a list marking an entry as
+        // loaded or requested has no data to go with it. The original resets every entry
+        // before loading the first part, so all of them start out unloaded here too.
+        if state > 2 {
+            return Err(Error::InvalidMemEntryState(state));
+        }
 
-        let state = state.try_into()?;
         let kind = reader.read_u8()?.into();
         let _buf_ptr = reader.read_u16::<BigEndian>()?;
         let _unknown_a = reader.read_u16::<BigEndian>()?;
         let _rank_number = reader.read_u8()?;
-        let bank_id = reader.read_u8()?.try_into()?;
+        let bank_id = BankId(reader.read_u8()?);
         let bank_offset = reader.read_u32::<BigEndian>()?;
         let _unknown_b = reader.read_u16::<BigEndian>()?;
         let packed_size = reader.read_u16::<BigEndian>()?;
@@ -329,14 +637,27 @@ impl MemEntry {
         let size = reader.read_u16::<BigEndian>()?;
 
         Ok(Some(MemEntry {
-            state,
+            state: MemEntryState::NotNeeded,
             kind,
             bank_id,
             bank_offset,
             packed_size,
             size,
+            load_sequence: 0,
         }))
     }
+
+    fn single_file(index: u8) -> Self {
+        MemEntry {
+            state: MemEntryState::NotNeeded,
+            kind: ResourceType::Unknown,
+            bank_id: BankId(index),
+            bank_offset: 0,
+            packed_size: 0,
+            size: 0,
+            load_sequence: 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -344,25 +665,12 @@ enum MemEntryState {
     NotNeeded,
     Loaded(Vec<u8>),
     Requested,
-}
-
-impl TryFrom<u8> for MemEntryState {
-    type Error = Error;
-
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        let value = match value {
-            0 => Self::NotNeeded,
-            1 => Self::Loaded(vec![]),
-            2 => Self::Requested,
-            _ => return Err(Error::InvalidMemEntryState(value)),
-        };
-
-        Ok(value)
-    }
+    /// Was loaded, then dropped to stay under the memory budget
+    Evicted,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum ResourceType {
+pub enum ResourceType {
     Sound,
     Music,
     PolygonAnimation,
@@ -401,6 +709,47 @@ pub enum GamePart {
 }
 
 impl GamePart {
+    pub const ALL: [GamePart; 10] = [
+        GamePart::One,
+        GamePart::Two,
+        GamePart::Three,
+        GamePart::Four,
+        GamePart::Five,
+        GamePart::Six,
+        GamePart::Seven,
+        GamePart::Eight,
+        GamePart::Nine,
+        GamePart::Ten,
+    ];
+
+    /// Stable number of the part, 1 to 10 in play order. Use this rather than the
+    /// resource id for anything saved or shown to the user.
+    pub const fn number(&self) -> u8 {
+        match self {
+            GamePart::One => 1,
+            GamePart::Two => 2,
+            GamePart::Three => 3,
+            GamePart::Four => 4,
+            GamePart::Five => 5,
+            GamePart::Six => 6,
+            GamePart::Seven => 7,
+            GamePart::Eight => 8,
+            GamePart::Nine => 9,
+            GamePart::Ten => 10,
+        }
+    }
+
+    pub fn from_number(number: u8) -> Option<Self> {
+        let idx = (number as usize).checked_sub(1)?;
+        Self::ALL.get(idx).copied()
+    }
+
+    /// Resource id the bytecode requests the part with, the inverse of `from`.
+    pub const fn id(&self) -> u16 {
+        0x3e7f + self.number() as u16
+    }
+
+    /// Maps the resource id passed to `LoadRes` to a part.
     pub fn from(id: u16) -> Option<Self> {
         let part = match id {
             0x3e80 => GamePart::One,
@@ -480,38 +829,51 @@ impl GamePart {
     }
 }
 
-#[derive(Debug, Clone)]
-struct BankId(u8);
+const DOS_BANKS: [&str; 13] = [
+    "BANK01", "BANK02", "BANK03", "BANK04", "BANK05", "BANK06", "BANK07", "BANK08", "BANK09",
+    "BANK0A", "BANK0B", "BANK0C", "BANK0D",
+];
 
-impl BankId {
-    fn name(&self) -> &'static str {
-        match self.0 {
-            1 => "BANK01",
-            2 => "BANK02",
-            3 => "BANK03",
-            4 => "BANK04",
-            5 => "BANK05",
-            6 => "BANK06",
-            7 => "BANK07",
-            8 => "BANK08",
-            9 => "BANK09",
-            0xa => "BANK0A",
-            0xb => "BANK0B",
-            0xc => "BANK0C",
-            0xd => "BANK0D",
-            _ => unreachable!("invalid bank id: {}", self.0),
+const FIFTEENTH_ANNIVERSARY_ENTRIES: u8 = 146;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GameVersion {
+    /// The original DOS release, a `MEMLIST.BIN` index into 13 packed `BANKxx` files
+    Dos,
+    /// The 15th Anniversary Edition. There is no `MEMLIST.BIN`, instead each of the 146
+    /// entries is stored uncompressed in its own `Game/DAT/FILExxx.DAT` file, numbered by
+    /// entry index, so entry sizes come from the files and resource types are unknown.
+    /// Entry indices match the DOS release, so the classic palette, bytecode, and polygon
+    /// data boot as-is; the remastered bitmaps and audio are not loaded.
+    FifteenthAnniversary,
+}
+
+impl GameVersion {
+    /// Boot value for `vars::PLATFORM`. PC data (DOS, and the Windows based 15th
+    /// Anniversary Edition) expects 0x81, the scripts test bit 7 to pick PC specific
+    /// behaviour. Amiga and Atari data expects 0x01, but neither is supported here.
+    pub fn platform_flags(&self) -> i16 {
+        match self {
+            GameVersion::Dos | GameVersion::FifteenthAnniversary => 0x81,
         }
     }
 }
 
-impl TryFrom<u8> for BankId {
-    type Error = Error;
+#[derive(Debug, Copy, Clone)]
+struct BankId(u8);
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
-        if value > 0 && value <= 0x0d {
-            Ok(BankId(value))
-        } else {
-            Err(Error::InvalidBankId(value))
+impl BankId {
+    fn name(&self, version: GameVersion) -> Result<String, Error> {
+        match version {
+            GameVersion::Dos => (self.0 as usize)
+                .checked_sub(1)
+                .and_then(|idx| DOS_BANKS.get(idx))
+                .map(|name| name.to_string())
+                .ok_or(Error::InvalidBankId(self.0)),
+            GameVersion::FifteenthAnniversary if self.0 < FIFTEENTH_ANNIVERSARY_ENTRIES => {
+                Ok(format!("Game/DAT/FILE{:03}.DAT", self.0))
+            }
+            GameVersion::FifteenthAnniversary => Err(Error::InvalidBankId(self.0)),
         }
     }
 }
@@ -527,3 +889,282 @@ pub struct PolygonResource {
     pub buffer_offset: usize,
     pub source: PolygonSource,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MemIo;
+
+    #[test]
+    fn part_ids_and_numbers_round_trip() {
+        for (idx, part) in GamePart::ALL.into_iter().enumerate() {
+            assert_eq!(part.number() as usize, idx + 1);
+            assert_eq!(GamePart::from_number(part.number()), Some(part));
+            assert_eq!(part.id(), 0x3e80 + idx as u16);
+            assert_eq!(GamePart::from(part.id()), Some(part));
+        }
+
+        assert_eq!(GamePart::from_number(0), None);
+        assert_eq!(GamePart::from_number(11), None);
+        assert_eq!(GamePart::from(0x3e7f), None);
+        assert_eq!(GamePart::from(0x3e8a), None);
+    }
+
+    #[test]
+    fn dos_bank_names() {
+        let expected = [
+            (0x01, "BANK01"),
+            (0x02, "BANK02"),
+            (0x03, "BANK03"),
+            (0x04, "BANK04"),
+            (0x05, "BANK05"),
+            (0x06, "BANK06"),
+            (0x07, "BANK07"),
+            (0x08, "BANK08"),
+            (0x09, "BANK09"),
+            (0x0a, "BANK0A"),
+            (0x0b, "BANK0B"),
+            (0x0c, "BANK0C"),
+            (0x0d, "BANK0D"),
+        ];
+
+        for (id, name) in expected {
+            assert_eq!(BankId(id).name(GameVersion::Dos).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn invalid_bank_id() {
+        for id in [0x00, 0x0e, 0xff] {
+            match BankId(id).name(GameVersion::Dos) {
+                Err(Error::InvalidBankId(value)) => assert_eq!(value, id),
+                res => panic!("expected invalid bank id error: {:?}", res),
+            }
+        }
+    }
+
+    #[test]
+    fn mem_io_entries_load_from_their_banks() {
+        let mut io = MemIo::builder()
+            .resource(0, ResourceType::Palette, &[1, 2, 3])
+            .resource_in_bank(2, ResourceType::Bytecode, 2, &[4, 5])
+            .resource_in_bank(3, ResourceType::Sound, 3, &[6])
+            .packed_resource(4, ResourceType::Sound, 1, &[0; 8], 32)
+            .build();
+        io.remove("BANK03");
+
+        let mut resources = Resources::load(io).unwrap();
+        for idx in 0..5 {
+            resources.load_part_or_entry(idx);
+        }
+
+        assert_eq!(resources.entry_data(0), Some(&[1, 2, 3][..]));
+        assert_eq!(resources.entry_data(1), Some(&[][..]));
+        assert_eq!(resources.entry_data(2), Some(&[4, 5][..]));
+        assert_eq!(resources.entry_data(3), None);
+        assert_eq!(resources.entry_data(4), None);
+    }
+
+    #[test]
+    fn load_sound_refuses_parts_and_other_entries() {
+        let io = MemIo::builder()
+            .resource(1, ResourceType::Sound, &[1, 2])
+            .resource(2, ResourceType::Music, &[3])
+            .build();
+        let mut resources = Resources::load(io).unwrap();
+
+        assert_eq!(resources.load_sound(1).unwrap(), &[1, 2][..]);
+        assert!(matches!(resources.load_sound(2), Err(Error::NotASound(2))));
+        assert_eq!(resources.entry_data(2), None);
+        assert!(matches!(
+            resources.load_sound(GamePart::Two.id()),
+            Err(Error::InvalidEntryId(0x3e81))
+        ));
+        assert_eq!(resources.requested_part(), None);
+    }
+
+    #[test]
+    fn mem_list_states_start_unloaded() {
+        let mut io = MemIo::builder()
+            .resource(0, ResourceType::Palette, &[1, 2, 3])
+            .resource(1, ResourceType::Sound, &[4])
+            .resource(2, ResourceType::Sound, &[5])
+            .build();
+        let mut mem_list = Vec::new();
+        io.load("MEMLIST.BIN")
+            .unwrap()
+            .read_to_end(&mut mem_list)
+            .unwrap();
+        // entry 0 marked loaded and entry 1 requested, each record is 20 bytes
+        mem_list[0] = 1;
+        mem_list[20] = 2;
+        io.insert("MEMLIST.BIN", mem_list.clone());
+
+        let mut resources = Resources::load(io.clone()).unwrap();
+        assert_eq!(resources.entry_data(0), None);
+        assert_eq!(resources.entry_data(1), None);
+        assert_eq!(resources.bytes_loaded(), 0);
+
+        resources.load_part_or_entry(0);
+        assert_eq!(resources.entry_data(0), Some(&[1, 2, 3][..]));
+
+        mem_list[40] = 3;
+        io.insert("MEMLIST.BIN", mem_list);
+        assert!(matches!(
+            Resources::load(io),
+            Err(Error::InvalidMemEntryState(3))
+        ));
+    }
+
+    #[test]
+    fn all_bytecode_reads_every_part() {
+        let builder = GamePart::ALL
+            .iter()
+            .fold(MemIo::builder(), |builder, part| {
+                let code = [part.bytecode() as u8, 0x11];
+                builder.resource(part.bytecode(), ResourceType::Bytecode, &code)
+            });
+        let resources = Resources::load(builder.build()).unwrap();
+
+        let all = resources.all_bytecode().unwrap();
+        assert_eq!(all.len(), GamePart::ALL.len());
+        for ((part, code), expected) in all.iter().zip(GamePart::ALL) {
+            assert_eq!(*part, expected);
+            assert_eq!(code, &[part.bytecode() as u8, 0x11]);
+        }
+        assert_eq!(resources.loaded_part(), None);
+        assert_eq!(resources.bytes_loaded(), 0);
+
+        let short = MemIo::builder()
+            .resource(GamePart::One.bytecode(), ResourceType::Bytecode, &[0x11])
+            .build();
+        let resources = Resources::load(short).unwrap();
+        assert!(matches!(
+            resources.all_bytecode(),
+            Err(Error::MissingPartData)
+        ));
+    }
+
+    #[test]
+    fn memory_budget_evicts_oldest_optional_entries() {
+        let part = GamePart::Two;
+        let io = MemIo::builder()
+            .resource(1, ResourceType::Sound, &[1; 100])
+            .resource(2, ResourceType::Sound, &[2; 100])
+            .resource(3, ResourceType::Sound, &[3; 100])
+            .resource(part.palette(), ResourceType::Palette, &[0; 32])
+            .resource(part.bytecode(), ResourceType::Bytecode, &[0; 50])
+            .resource(part.cinematic(), ResourceType::PolygonCinematic, &[0; 50])
+            .build();
+
+        let mut resources = Resources::load(io).unwrap();
+        resources.prepare_part(part);
+        resources.set_memory_budget(Some(132 + 250));
+
+        for idx in 1..=3 {
+            resources.load_part_or_entry(idx);
+        }
+
+        assert_eq!(resources.entry_data(1), None);
+        assert!(resources.entry_data(2).is_some());
+        assert!(resources.entry_data(3).is_some());
+        assert!(resources.palette().is_some());
+        assert!(resources.bytecode().is_some());
+        assert!(resources.cinematic().is_some());
+        assert_eq!(resources.memory_used(), 332);
+
+        resources.reload_evicted(1);
+        assert_eq!(resources.entry_data(1), Some(&[1; 100][..]));
+        assert_eq!(resources.entry_data(2), None);
+        assert!(resources.entry_data(3).is_some());
+
+        // entries that were never requested are not loaded by reload_evicted
+        resources.set_memory_budget(Some(0));
+        assert_eq!(resources.memory_used(), 132);
+        resources.reload_evicted(4);
+        assert_eq!(resources.entry_data(4), None);
+    }
+
+    /// Looks like a 15th Anniversary Edition install where every file holds 4 zero bytes
+    struct ZeroIo;
+
+    impl Io for ZeroIo {
+        type Reader = std::io::Cursor<Vec<u8>>;
+
+        fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
+            match name.as_ref() {
+                "MEMLIST.BIN" => Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+                _ => Ok(std::io::Cursor::new(vec![0; 4])),
+            }
+        }
+    }
+
+    #[test]
+    fn prepare_part_reports_progress() {
+        use std::sync::{Arc, Mutex};
+
+        let mut resources = Resources::load(ZeroIo).unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let progress_calls = calls.clone();
+        resources.set_progress_callback(Some(Box::new(move |loaded, total| {
+            progress_calls.lock().unwrap().push((loaded, total))
+        })));
+
+        resources.prepare_part(GamePart::Two);
+        assert_eq!(*calls.lock().unwrap(), [(1, 3), (2, 3), (3, 3)]);
+
+        calls.lock().unwrap().clear();
+        resources.prepare_part(GamePart::Three);
+        assert_eq!(*calls.lock().unwrap(), [(1, 4), (2, 4), (3, 4), (4, 4)]);
+    }
+
+    #[test]
+    fn loaded_part_follows_prepare_part() {
+        let mut resources = Resources::load(ZeroIo).unwrap();
+        assert_eq!(resources.loaded_part(), None);
+
+        resources.prepare_part(GamePart::Two);
+        assert_eq!(resources.loaded_part(), Some(GamePart::Two));
+
+        resources.prepare_part(GamePart::Three);
+        assert_eq!(resources.loaded_part(), Some(GamePart::Three));
+    }
+
+    #[test]
+    fn decode_garbage_returns_error() {
+        // Truncated input, too short to hold the trailing size/crc/check words
+        for len in 0..12 {
+            assert!(unpack(vec![0xff; len], 0x100).is_err());
+        }
+
+        // Empty output buffer, every write lands out of bounds
+        assert!(unpack(vec![0xff; 16], 0).is_err());
+
+        let mut seed = 0x1234_5678u32;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        for _ in 0..2000 {
+            let packed_size = (next() % 256) as u16 + 12;
+            let size = (next() % 512) as u16;
+            let mut input: Vec<u8> = (0..packed_size).map(|_| next() as u8).collect();
+            // Keep the remaining size positive so decoding runs past the header
+            let data_size = size as u32 + 1;
+            input[packed_size as usize - 4..].copy_from_slice(&data_size.to_be_bytes());
+
+            assert!(unpack(input, size).is_err());
+        }
+    }
+
+    #[test]
+    fn fifteenth_anniversary_file_names() {
+        let version = GameVersion::FifteenthAnniversary;
+        assert_eq!(BankId(0).name(version).unwrap(), "Game/DAT/FILE000.DAT");
+        assert_eq!(BankId(0x15).name(version).unwrap(), "Game/DAT/FILE021.DAT");
+        assert!(BankId(FIFTEENTH_ANNIVERSARY_ENTRIES).name(version).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+//! Retries for `Io` implementations whose loads can fail temporarily, such as files
+//! fetched over a network.
+
+use crate::error::Error;
+use crate::resources::Io;
+
+use std::cell::Cell;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+/// How often and how patiently `RetryIo` retries a load.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Loads attempted in total before giving up, at least one is always made
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for each retry after it
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 4,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `retry`, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Called with the file name, the retry number and the wait before each retry, for
+/// showing a status while loads are being retried.
+pub type RetryObserver = Box<dyn Fn(&str, u32, Duration) + Send>;
+
+/// Wraps an `Io` and retries loads that fail with a transient error. Missing files and
+/// other errors are returned straight away, transient errors become
+/// `Error::RetriesExhausted` once the policy's attempts are used up.
+pub struct RetryIo<I: Io> {
+    io: I,
+    policy: RetryPolicy,
+    wait: fn(Duration),
+    observer: Option<RetryObserver>,
+    retries: Cell<u64>,
+}
+
+impl<I: Io> RetryIo<I> {
+    /// Retries with `policy`, blocking the calling thread between attempts.
+    pub fn new(io: I, policy: RetryPolicy) -> Self {
+        Self::with_wait(io, policy, std::thread::sleep)
+    }
+
+    /// Retries with `policy`, calling `wait` for each backoff. Targets that cannot block,
+    /// like wasm, should instead retry their own async loads using `RetryPolicy::delay`
+    /// and `is_transient`, as the web fetch loader does.
+    pub fn with_wait(io: I, policy: RetryPolicy, wait: fn(Duration)) -> Self {
+        Self {
+            io,
+            policy,
+            wait,
+            observer: None,
+            retries: Cell::new(0),
+        }
+    }
+
+    pub fn set_observer(&mut self, observer: Option<RetryObserver>) {
+        self.observer = observer;
+    }
+
+    /// Retries made over the lifetime of this `Io`.
+    pub fn retries(&self) -> u64 {
+        self.retries.get()
+    }
+}
+
+impl<I: Io> Io for RetryIo<I> {
+    type Reader = I::Reader;
+
+    fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
+        let name = name.as_ref();
+        let attempts = self.policy.attempts.max(1);
+        let mut retry = 0;
+
+        loop {
+            match self.io.load(name) {
+                Err(Error::Io(err)) if is_transient(err.kind()) => {
+                    retry += 1;
+                    if retry >= attempts {
+                        return Err(Error::RetriesExhausted(name.to_string(), attempts, err));
+                    }
+
+                    let delay = self.policy.delay(retry);
+                    log::warn!("retrying {} in {}ms: {}", name, delay.as_millis(), err);
+                    if let Some(observer) = self.observer.as_ref() {
+                        observer(name, retry, delay);
+                    }
+                    self.retries.set(self.retries.get() + 1);
+                    (self.wait)(delay);
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Whether a load failing with `kind` may succeed if tried again.
+pub fn is_transient(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::ResourceType;
+    use crate::testing::MemIo;
+
+    /// Fails the first `failures` loads with `kind`, then loads from `io`.
+    struct FlakyIo {
+        io: MemIo,
+        kind: ErrorKind,
+        failures: Cell<u32>,
+    }
+
+    impl Io for FlakyIo {
+        type Reader = <MemIo as Io>::Reader;
+
+        fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
+            if self.failures.get() > 0 {
+                self.failures.set(self.failures.get() - 1);
+                return Err(std::io::Error::new(self.kind, "flaky").into());
+            }
+            self.io.load(name)
+        }
+    }
+
+    fn flaky(kind: ErrorKind, failures: u32) -> FlakyIo {
+        FlakyIo {
+            io: MemIo::builder()
+                .resource(0, ResourceType::Sound, &[1, 2, 3])
+                .build(),
+            kind,
+            failures: Cell::new(failures),
+        }
+    }
+
+    #[test]
+    fn retries_transient_errors_within_the_attempt_budget() {
+        let policy = RetryPolicy {
+            attempts: 4,
+            ..Default::default()
+        };
+
+        let io = RetryIo::with_wait(flaky(ErrorKind::TimedOut, 3), policy, |_| ());
+        assert!(io.load("BANK01").is_ok());
+        assert_eq!(io.retries(), 3);
+
+        let io = RetryIo::with_wait(flaky(ErrorKind::TimedOut, 4), policy, |_| ());
+        assert!(matches!(
+            io.load("BANK01"),
+            Err(Error::RetriesExhausted(name, 4, _)) if name == "BANK01"
+        ));
+
+        let io = RetryIo::with_wait(flaky(ErrorKind::NotFound, 1), policy, |_| ());
+        assert!(matches!(io.load("BANK01"), Err(Error::Io(_))));
+        assert_eq!(io.retries(), 0);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        let policy = RetryPolicy {
+            attempts: 10,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+        };
+
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+        assert_eq!(policy.delay(4), Duration::from_millis(500));
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+
+use crate::executor::ExecutorState;
+
+/// Number of snapshots kept by default, at one snapshot per second this covers
+/// the last 30 seconds of play.
+pub const DEFAULT_REWIND_CAPACITY: usize = 30;
+
+/// Ring buffer of recent executor snapshots.
+///
+/// Each snapshot holds the VM (variables, thread table and call stack), the
+/// video page assignments and palette, which come to roughly 2KiB, and the
+/// contents of the four pages when the `Gfx` can read them back, another
+/// 250KiB. The default capacity therefore costs around 7.5MiB.
+pub struct RewindBuffer {
+    snapshots: VecDeque<ExecutorState>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, state: ExecutorState) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(state);
+    }
+
+    pub fn pop(&mut self) -> Option<ExecutorState> {
+        self.snapshots.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+impl Default for RewindBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_REWIND_CAPACITY)
+    }
+}
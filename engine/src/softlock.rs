@@ -0,0 +1,110 @@
+//! Detection of the game running on without anything changing, as happens when the
+//! scripts reach a state they can never leave.
+
+use crate::video::VideoCommand;
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write;
+use std::hash::Hasher;
+
+/// Digests the video commands and variables of each frame and reports once the digest
+/// has stayed the same for `timeout_ms` of game time.
+///
+/// A scene the player is simply watching, or standing still in, also looks frozen, so
+/// the timeout should be long enough to rule that out.
+#[derive(Debug, Clone)]
+pub struct SoftlockDetector {
+    timeout_ms: u64,
+    digest: DefaultHasher,
+    last_digest: Option<u64>,
+    unchanged_ms: u64,
+    detected: bool,
+}
+
+impl SoftlockDetector {
+    pub fn new(timeout_ms: u64) -> Self {
+        Self {
+            timeout_ms,
+            digest: DefaultHasher::new(),
+            last_digest: None,
+            unchanged_ms: 0,
+            detected: false,
+        }
+    }
+
+    /// Adds a command to the digest of the frame being drawn.
+    pub fn push_command(&mut self, command: &VideoCommand) {
+        let _ = write!(HashWriter(&mut self.digest), "{:?}", command);
+    }
+
+    /// Closes the frame being drawn, adding `variables` to its digest, and accounts `ms`
+    /// of game time. Returns `true` on the frame the timeout is first reached, any change
+    /// starts the wait over.
+    pub fn end_frame(&mut self, variables: &[i16], ms: u64) -> bool {
+        let mut digest = std::mem::take(&mut self.digest);
+        for variable in variables {
+            digest.write_i16(*variable);
+        }
+        let digest = digest.finish();
+
+        if self.last_digest != Some(digest) {
+            self.last_digest = Some(digest);
+            self.unchanged_ms = 0;
+            self.detected = false;
+            return false;
+        }
+
+        self.unchanged_ms += ms;
+        if !self.detected && self.unchanged_ms >= self.timeout_ms {
+            self.detected = true;
+            return true;
+        }
+
+        false
+    }
+
+    /// Whether the game has shown no change since the timeout was reached.
+    pub fn is_detected(&self) -> bool {
+        self.detected
+    }
+
+    /// Forgets the last frame, for when the game is moved elsewhere by the frontend.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.timeout_ms);
+    }
+}
+
+struct HashWriter<'a>(&'a mut DefaultHasher);
+
+impl Write for HashWriter<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video::FillVideoPageCommand;
+
+    #[test]
+    fn fires_once_after_unchanged_frames() {
+        let fill = VideoCommand::FillVideoPage(FillVideoPageCommand {
+            page_id: 0,
+            color: 1,
+        });
+        let mut detector = SoftlockDetector::new(100);
+        let mut frame = |variables: &[i16]| {
+            detector.push_command(&fill);
+            detector.end_frame(variables, 40)
+        };
+
+        let fired: Vec<_> = (0..6).map(|_| frame(&[1, 2])).collect();
+        assert_eq!(fired, [false, false, false, true, false, false]);
+
+        assert!(!frame(&[1, 3]));
+        let fired: Vec<_> = (0..4).map(|_| frame(&[1, 3])).collect();
+        assert_eq!(fired, [false, false, true, false]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Turns game polygons into indexed triangles for GPU backends, with lyon when the
+//! `lyon` feature is enabled and with `Polygon::triangles` otherwise.
+
+#[cfg(feature = "lyon")]
+mod imp {
+    use lyon::{
+        lyon_tessellation::{
+            BuffersBuilder, FillOptions, FillVertex, StrokeOptions, StrokeVertex, VertexBuffers,
+        },
+        math::point,
+        path::traits::{Build, PathBuilder},
+        tessellation::{FillTessellator, StrokeTessellator},
+    };
+
+    use crate::video::Polygon;
+
+    pub type Geometry<V> = VertexBuffers<V, u16>;
+
+    #[derive(Default)]
+    pub struct Tessellator {
+        fill: FillTessellator,
+        fill_options: FillOptions,
+        stroke: StrokeTessellator,
+        stroke_options: StrokeOptions,
+    }
+
+    impl Tessellator {
+        pub fn new() -> Self {
+            Self {
+                fill: FillTessellator::new(),
+                fill_options: FillOptions::default(),
+                stroke: StrokeTessellator::new(),
+                stroke_options: StrokeOptions::default(),
+            }
+        }
+
+        pub fn fill<V, F: Fn((f32, f32)) -> V>(
+            &mut self,
+            polygon: &Polygon,
+            geometry: &mut Geometry<V>,
+            vertex: F,
+        ) {
+            let mut points = polygon.points().map(|(x, y)| point(x as f32, y as f32));
+
+            if let Some(first) = points.next() {
+                let mut buffer_builder =
+                    BuffersBuilder::new(geometry, |v: FillVertex| vertex(v.position().to_tuple()));
+                let mut builder = self.fill.builder(&self.fill_options, &mut buffer_builder);
+
+                builder.begin(first);
+                for point in points {
+                    builder.line_to(point);
+                }
+                builder.close();
+
+                let _ = builder.build().unwrap();
+            }
+        }
+
+        pub fn stroke<V, F: Fn((f32, f32)) -> V>(
+            &mut self,
+            polygon: &Polygon,
+            geometry: &mut Geometry<V>,
+            vertex: F,
+        ) {
+            let mut points = polygon.points().map(|(x, y)| point(x as f32, y as f32));
+
+            if let Some(first) = points.next() {
+                let mut buffer_builder = BuffersBuilder::new(geometry, |v: StrokeVertex| {
+                    vertex(v.position().to_tuple())
+                });
+                let mut builder = self
+                    .stroke
+                    .builder(&self.stroke_options, &mut buffer_builder);
+
+                builder.begin(first);
+                for point in points {
+                    builder.line_to(point);
+                }
+                builder.close();
+
+                let _ = builder.build().unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "lyon"))]
+mod imp {
+    use crate::video::Polygon;
+
+    pub struct Geometry<V> {
+        pub vertices: Vec<V>,
+        pub indices: Vec<u16>,
+    }
+
+    impl<V> Geometry<V> {
+        pub fn new() -> Self {
+            Self {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            }
+        }
+    }
+
+    impl<V> Default for Geometry<V> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Triangulates polygons with `Polygon::triangles` instead of tessellating paths.
+    #[derive(Default)]
+    pub struct Tessellator;
+
+    impl Tessellator {
+        pub fn new() -> Self {
+            Tessellator
+        }
+
+        pub fn fill<V, F: Fn((f32, f32)) -> V>(
+            &mut self,
+            polygon: &Polygon,
+            geometry: &mut Geometry<V>,
+            vertex: F,
+        ) {
+            let base = geometry.vertices.len() as u16;
+            geometry
+                .vertices
+                .extend(polygon.points().map(|(x, y)| vertex((x as f32, y as f32))));
+            for triangle in polygon.triangles() {
+                geometry
+                    .indices
+                    .extend(triangle.iter().map(|&idx| base + idx as u16));
+            }
+        }
+
+        /// Outlines each edge with a one pixel wide quad.
+        pub fn stroke<V, F: Fn((f32, f32)) -> V>(
+            &mut self,
+            polygon: &Polygon,
+            geometry: &mut Geometry<V>,
+            vertex: F,
+        ) {
+            let points: Vec<_> = polygon
+                .points()
+                .map(|(x, y)| (x as f32, y as f32))
+                .collect();
+
+            for (n, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(n + 1) % points.len()];
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let len = (dx * dx + dy * dy).sqrt();
+                if len == 0.0 {
+                    continue;
+                }
+                let (nx, ny) = (-dy / len * 0.5, dx / len * 0.5);
+
+                let base = geometry.vertices.len() as u16;
+                geometry.vertices.extend([
+                    vertex((x0 + nx, y0 + ny)),
+                    vertex((x1 + nx, y1 + ny)),
+                    vertex((x1 - nx, y1 - ny)),
+                    vertex((x0 - nx, y0 - ny)),
+                ]);
+                geometry
+                    .indices
+                    .extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+            }
+        }
+    }
+}
+
+pub use imp::{Geometry, Tessellator};
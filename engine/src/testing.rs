@@ -0,0 +1,302 @@
+//! Synthetic game data and input for tests, also available to other crates through the
+//! `testing` feature.
+//!
+//! [`MemIo::builder`] assembles a DOS style install in memory: a `MEMLIST.BIN` index and
+//! the `BANKxx` files it points into. Each `MEMLIST.BIN` record is 20 big endian bytes:
+//!
+//! | Offset | Size | Field                                      |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 1    | state, 0 not needed, 1 loaded, 2 requested |
+//! | 1      | 1    | resource type                              |
+//! | 2      | 2    | buffer pointer, unused                     |
+//! | 4      | 2    | unknown                                    |
+//! | 6      | 1    | rank, unused                               |
+//! | 7      | 1    | bank number, `BANK01` through `BANK0D`     |
+//! | 8      | 4    | offset of the entry within its bank        |
+//! | 12     | 2    | unknown                                    |
+//! | 14     | 2    | packed size                                |
+//! | 16     | 2    | unknown                                    |
+//! | 18     | 2    | unpacked size                              |
+//!
+//! The list ends with a state byte of `0xff`. Entries whose packed and unpacked sizes
+//! match are stored as is, otherwise they are unpacked when loaded.
+
+use crate::error::Error;
+use crate::gfx::{Gfx, PageSnapshot, PAGE_WIDTH};
+use crate::input::{Input, InputState};
+use crate::resources::{Io, ResourceType};
+use crate::video::{Page, Polygon};
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// An `Io` backed by named in-memory files.
+#[derive(Debug, Default, Clone)]
+pub struct MemIo {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemIo {
+    pub fn builder() -> MemIoBuilder {
+        MemIoBuilder::default()
+    }
+
+    /// Deletes a file, for simulating incomplete installs.
+    pub fn remove(&mut self, name: &str) {
+        self.files.remove(name);
+    }
+
+    /// Replaces or adds a file, for simulating corrupt installs.
+    pub fn insert(&mut self, name: &str, data: Vec<u8>) {
+        self.files.insert(name.to_string(), data);
+    }
+}
+
+impl Io for MemIo {
+    type Reader = std::io::Cursor<Vec<u8>>;
+
+    fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
+        match self.files.get(name.as_ref()) {
+            Some(data) => Ok(std::io::Cursor::new(data.clone())),
+            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, name.as_ref()).into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Resource {
+    kind: ResourceType,
+    bank: u8,
+    data: Vec<u8>,
+    size: u16,
+}
+
+/// Collects resources by index and lays them out into a `MEMLIST.BIN` and banks.
+#[derive(Debug, Default, Clone)]
+pub struct MemIoBuilder {
+    resources: BTreeMap<usize, Resource>,
+}
+
+impl MemIoBuilder {
+    /// Adds an uncompressed resource to `BANK01`.
+    pub fn resource(self, index: usize, kind: ResourceType, data: &[u8]) -> Self {
+        self.resource_in_bank(index, kind, 1, data)
+    }
+
+    pub fn resource_in_bank(self, index: usize, kind: ResourceType, bank: u8, data: &[u8]) -> Self {
+        self.packed_resource(index, kind, bank, data, data.len() as u16)
+    }
+
+    /// Adds a resource stored as `packed` bytes that unpack to `size` bytes. When the
+    /// lengths match the data is treated as uncompressed.
+    pub fn packed_resource(
+        mut self,
+        index: usize,
+        kind: ResourceType,
+        bank: u8,
+        packed: &[u8],
+        size: u16,
+    ) -> Self {
+        self.resources.insert(
+            index,
+            Resource {
+                kind,
+                bank,
+                data: packed.to_vec(),
+                size,
+            },
+        );
+        self
+    }
+
+    /// Builds the install. Indices without a resource become empty entries in `BANK01`.
+    pub fn build(self) -> MemIo {
+        let count = self.resources.keys().next_back().map_or(0, |idx| idx + 1);
+        let mut mem_list = Vec::new();
+        let mut banks: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+        banks.insert(1, Vec::new());
+
+        for idx in 0..count {
+            let (kind, bank_id, data, size) = match self.resources.get(&idx) {
+                Some(res) => (res.kind, res.bank, res.data.as_slice(), res.size),
+                None => (ResourceType::Unknown, 1, &[][..], 0),
+            };
+            let bank = banks.entry(bank_id).or_default();
+            let offset = bank.len() as u32;
+            bank.extend_from_slice(data);
+
+            mem_list.push(0);
+            mem_list.push(resource_type_id(kind));
+            mem_list.extend_from_slice(&[0, 0, 0, 0, 0]);
+            mem_list.push(bank_id);
+            mem_list.extend_from_slice(&offset.to_be_bytes());
+            mem_list.extend_from_slice(&[0, 0]);
+            mem_list.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            mem_list.extend_from_slice(&[0, 0]);
+            mem_list.extend_from_slice(&size.to_be_bytes());
+        }
+        mem_list.push(0xff);
+
+        let mut io = MemIo::default();
+        io.insert("MEMLIST.BIN", mem_list);
+        for (bank_id, data) in banks {
+            io.insert(&format!("BANK{:02X}", bank_id), data);
+        }
+
+        io
+    }
+}
+
+fn resource_type_id(kind: ResourceType) -> u8 {
+    match kind {
+        ResourceType::Sound => 0,
+        ResourceType::Music => 1,
+        ResourceType::PolygonAnimation => 2,
+        ResourceType::Palette => 3,
+        ResourceType::Bytecode => 4,
+        ResourceType::PolygonCinematic => 5,
+        ResourceType::Unknown => 0xff,
+    }
+}
+
+/// A `Gfx` that records every call it receives, for checking what reaches the renderer.
+/// Page contents are kept for fills and copies, polygons and strings are not drawn.
+#[derive(Debug)]
+pub struct RecordingGfx {
+    pub pages: PageSnapshot,
+    pub palettes: Vec<[(u8, u8, u8); 16]>,
+    pub polygons: Vec<Polygon>,
+    pub blits: Vec<Page>,
+    pub fills: Vec<(Page, u8)>,
+    pub selects: Vec<Page>,
+    pub copies: Vec<(Page, Page, i16)>,
+    pub strings: Vec<&'static str>,
+}
+
+impl Default for RecordingGfx {
+    fn default() -> Self {
+        Self {
+            pages: PageSnapshot::filled(0),
+            palettes: Vec::new(),
+            polygons: Vec::new(),
+            blits: Vec::new(),
+            fills: Vec::new(),
+            selects: Vec::new(),
+            copies: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+}
+
+impl Gfx for RecordingGfx {
+    fn blit(&mut self, page: Page) {
+        self.blits.push(page);
+    }
+    fn draw_polygon(&mut self, polygon: Polygon) {
+        self.polygons.push(polygon);
+    }
+    fn fill_page(&mut self, page: Page, color: u8) {
+        self.fills.push((page, color));
+        self.pages.page_mut(page).fill(color);
+    }
+    fn select_page(&mut self, page: Page) {
+        self.selects.push(page);
+    }
+    fn copy_page(&mut self, src: Page, dest: Page, scroll: i16) {
+        self.copies.push((src, dest, scroll));
+
+        // rows shift down by a positive scroll, rows scrolled in keep their old contents
+        let source = self.pages.page(src).to_vec();
+        let offset = scroll.unsigned_abs() as usize * PAGE_WIDTH;
+        let dest = self.pages.page_mut(dest);
+        if offset >= dest.len() {
+            return;
+        }
+        let len = dest.len() - offset;
+        if scroll < 0 {
+            dest[..len].copy_from_slice(&source[offset..]);
+        } else {
+            dest[offset..].copy_from_slice(&source[..len]);
+        }
+    }
+    fn set_palette(&mut self, palette: [(u8, u8, u8); 16]) {
+        self.palettes.push(palette);
+    }
+    fn draw_string(&mut self, text: &'static str, _color: u8, _x: i16, _y: i16) {
+        self.strings.push(text);
+    }
+    fn save_pages(&mut self) -> Option<PageSnapshot> {
+        Some(self.pages.clone())
+    }
+    fn restore_pages(&mut self, pages: &PageSnapshot) {
+        self.pages = pages.clone();
+    }
+}
+
+/// Input that follows a fixed schedule, for tests that read as "hold right for frames
+/// 10 to 50, then press action". Each segment holds its buttons for a range of frames,
+/// overlapping segments are merged and frames outside every segment hold nothing.
+///
+/// The frame is not advanced by reading input, since the executor may read it several
+/// times per `run`. Tests advance it through a [`FrameCounter`] instead.
+#[derive(Debug, Default)]
+pub struct ScriptedInput {
+    segments: Vec<(Range<u64>, InputState)>,
+    frame: Arc<AtomicU64>,
+}
+
+impl ScriptedInput {
+    pub fn new(segments: Vec<(Range<u64>, InputState)>) -> Self {
+        Self {
+            segments,
+            frame: Arc::default(),
+        }
+    }
+
+    /// A handle for advancing the schedule after the input is handed to an executor.
+    pub fn frame_counter(&self) -> FrameCounter {
+        FrameCounter {
+            frame: self.frame.clone(),
+        }
+    }
+}
+
+impl Input for ScriptedInput {
+    fn get_input(&self) -> InputState {
+        let frame = self.frame.load(Ordering::Relaxed);
+        let mut state = InputState::default();
+
+        for (_, input) in self.segments.iter().filter(|(r, _)| r.contains(&frame)) {
+            state.up |= input.up;
+            state.down |= input.down;
+            state.left |= input.left;
+            state.right |= input.right;
+            state.action |= input.action;
+            state.turbo |= input.turbo;
+            state.rewind |= input.rewind;
+        }
+
+        state
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameCounter {
+    frame: Arc<AtomicU64>,
+}
+
+impl FrameCounter {
+    pub fn frame(&self) -> u64 {
+        self.frame.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, frame: u64) {
+        self.frame.store(frame, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self) {
+        self.frame.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -2,6 +2,8 @@ use crate::gfx::Gfx;
 use crate::resources::{Io, PolygonResource, PolygonSource, Resources};
 use crate::vm::ProgramCounter;
 
+use std::ops::Range;
+
 #[derive(Debug, Copy, Clone)]
 pub struct DrawCommand {
     pub polygon: PolygonResource,
@@ -57,9 +59,72 @@ pub enum VideoCommand {
     Blit(BlitCommand),
 }
 
+/// One line per command for logs and `CommandLog` dumps, with colors and offsets in hex
+/// and everything else in decimal.
+impl std::fmt::Display for VideoCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VideoCommand::Draw(DrawCommand {
+                polygon,
+                x,
+                y,
+                zoom,
+            }) => write!(
+                f,
+                "Draw@({},{}) zoom={} off={:#06x} src={:?}",
+                x, y, zoom, polygon.buffer_offset, polygon.source
+            ),
+            VideoCommand::Palette(PaletteCommand { palette_id }) => {
+                write!(f, "Palette id={}", palette_id)
+            }
+            VideoCommand::SelectVideoPage(SelectVideoPageCommand { page_id }) => {
+                write!(f, "Select p={}", page_id)
+            }
+            VideoCommand::FillVideoPage(FillVideoPageCommand { page_id, color }) => {
+                write!(f, "Fill p={} c={:#x}", page_id, color)
+            }
+            VideoCommand::CopyVideoPage(CopyVideoPageCommand {
+                src_page_id,
+                dest_page_id,
+                scroll,
+            }) => write!(
+                f,
+                "Copy {}->{} scroll={}",
+                src_page_id, dest_page_id, scroll
+            ),
+            VideoCommand::DrawString(DrawStringCommand {
+                string_id,
+                x,
+                y,
+                color,
+            }) => write!(
+                f,
+                "String@({},{}) id={:#05x} c={:#x}",
+                x, y, string_id, color
+            ),
+            VideoCommand::Blit(BlitCommand { page_id }) => write!(f, "Blit p={}", page_id),
+        }
+    }
+}
+
 pub struct Video<T: Gfx> {
     gfx: T,
+    /// Latched by the next blit, as in the original where `SetPalette` only records the
+    /// palette to switch to. Later requests before that blit replace earlier ones, so
+    /// only one palette is ever shown per frame. Color cycling, which this game does not
+    /// rely on, would need a blit between palettes.
+    requested_palette: Option<[(u8, u8, u8); 16]>,
+    palette: Option<[(u8, u8, u8); 16]>,
+    current_page: Page,
+    working_page_a: Page,
+    working_page_b: Page,
+    polygons_drawn: u64,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct VideoState {
     requested_palette: Option<[(u8, u8, u8); 16]>,
+    palette: Option<[(u8, u8, u8); 16]>,
     current_page: Page,
     working_page_a: Page,
     working_page_b: Page,
@@ -70,10 +135,55 @@ impl<T: Gfx> Video<T> {
         Self {
             gfx,
             requested_palette: None,
+            palette: None,
             current_page: Page::One,
             working_page_a: Page::One,
             working_page_b: Page::Two,
+            polygons_drawn: 0,
+        }
+    }
+
+    /// Total polygons handed to the renderer since creation.
+    pub fn polygons_drawn(&self) -> u64 {
+        self.polygons_drawn
+    }
+
+    /// The palette most recently applied by a blit. A palette command only takes
+    /// effect at the next blit, so a newly requested palette is not current until then.
+    pub fn current_palette(&self) -> Option<[(u8, u8, u8); 16]> {
+        self.palette
+    }
+
+    pub fn gfx(&self) -> &T {
+        &self.gfx
+    }
+
+    pub fn gfx_mut(&mut self) -> &mut T {
+        &mut self.gfx
+    }
+
+    pub fn state(&self) -> VideoState {
+        VideoState {
+            requested_palette: self.requested_palette,
+            palette: self.palette,
+            current_page: self.current_page,
+            working_page_a: self.working_page_a,
+            working_page_b: self.working_page_b,
+        }
+    }
+
+    pub fn restore(&mut self, state: VideoState) {
+        self.requested_palette = state.requested_palette;
+        self.palette = state.palette;
+        self.current_page = state.current_page;
+        self.working_page_a = state.working_page_a;
+        self.working_page_b = state.working_page_b;
+
+        if let Some(palette) = self.palette {
+            self.gfx.set_palette(palette);
         }
+        self.gfx.select_page(self.current_page);
+        self.gfx.blit(self.working_page_a);
     }
 
     pub fn push_command<I: Io>(&mut self, command: VideoCommand, resources: &Resources<I>) {
@@ -81,48 +191,43 @@ impl<T: Gfx> Video<T> {
             VideoCommand::Draw(draw) => self.draw(draw, resources),
             VideoCommand::Palette(pal) => {
                 let offset = (pal.palette_id) as usize * 32;
-                let palette = &resources.palette().expect("palette not loaded")[offset..];
-                let mut new_palette = [(0, 0, 0); 16];
-                for n in 0..16 {
-                    let c0 = palette[n * 2];
-                    let c1 = palette[n * 2 + 1];
-
-                    let r = (((c0 & 0x0f) << 2) | ((c0 & 0x0f) >> 2)) << 2;
-                    let g = (((c1 & 0xf0) >> 2) | ((c1 & 0xf0) >> 6)) << 2;
-                    let b = (((c1 & 0x0f) >> 2) | ((c1 & 0x0f) << 2)) << 2;
-
-                    new_palette[n] = (r, g, b);
-                }
-                self.requested_palette = Some(new_palette);
+                let palette = match resources.palette().and_then(|p| p.get(offset..offset + 32)) {
+                    Some(palette) => palette,
+                    None => {
+                        log::warn!("palette {} not loaded, keeping current", pal.palette_id);
+                        return;
+                    }
+                };
+                self.requested_palette = Some(decode_palette(palette));
             }
             VideoCommand::FillVideoPage(fill) => {
-                let page = self.get_page(fill.page_id);
-                self.gfx.fill_page(page, fill.color);
+                if let Some(page) = self.get_page(fill.page_id) {
+                    self.gfx.fill_page(page, fill.color);
+                }
             }
             VideoCommand::SelectVideoPage(select) => {
-                self.current_page = self.get_page(select.page_id);
-                self.gfx.select_page(self.current_page);
+                if let Some(page) = self.get_page(select.page_id) {
+                    self.current_page = page;
+                    self.gfx.select_page(self.current_page);
+                }
             }
             VideoCommand::CopyVideoPage(copy) => {
-                if copy.src_page_id == copy.dest_page_id {
-                    return;
-                }
-
-                let (src, dest, scroll) = if copy.src_page_id >= 0xfe {
-                    let src = self.get_page(copy.src_page_id);
-                    let dest = self.get_page(copy.dest_page_id);
-                    (src, dest, 0)
-                } else if copy.src_page_id & 0x80 == 0 {
-                    let src = self.get_page(copy.src_page_id & 0xbf);
-                    let dest = self.get_page(copy.dest_page_id);
-                    (src, dest, 0)
-                } else {
-                    let src = self.get_page(copy.src_page_id & 0x3);
-                    let dest = self.get_page(copy.dest_page_id);
-                    (src, dest, copy.scroll)
+                let (src_page_id, scroll) = match copy_source(&copy) {
+                    Some(source) => source,
+                    None => return,
                 };
 
-                self.gfx.copy_page(src, dest, scroll)
+                // Distinct ids can still resolve to the same page, through the working
+                // page aliases or the masking above, and a page can't be copied onto itself.
+                let src = self.get_page(src_page_id);
+                let dest = self.get_page(copy.dest_page_id);
+                match (src, dest) {
+                    (Some(src), Some(dest)) if src == dest => {
+                        log::debug!("skipping copy of {:?} onto itself", src);
+                    }
+                    (Some(src), Some(dest)) => self.gfx.copy_page(src, dest, scroll),
+                    _ => (),
+                }
             }
             VideoCommand::DrawString(string) => {
                 for (id, msg) in crate::strings::STRING_TABLE.iter() {
@@ -138,52 +243,98 @@ impl<T: Gfx> Video<T> {
                 }
             }
             VideoCommand::Blit(blit) => {
-                match blit.page_id {
-                    0xff => {
-                        let temp = self.working_page_a;
-                        self.working_page_a = self.working_page_b;
-                        self.working_page_b = temp;
-                    }
-                    0xfe => (),
-                    _ => {
-                        self.working_page_a = self.get_page(blit.page_id);
-                    }
-                }
-
-                if let Some(palette) = self.requested_palette.take() {
-                    self.gfx.set_palette(palette)
-                }
-
+                self.flip(blit.page_id);
                 self.gfx.blit(self.working_page_a);
             }
         }
     }
 
-    fn get_page(&self, page_id: u8) -> Page {
+    /// Like `push_command`, without drawing polygons or text and without presenting
+    /// blits, for running the game ahead quickly. Fills, copies, page selection and
+    /// palette changes still apply, so the first frame shown afterwards starts from the
+    /// same pages and palette as if every frame had been drawn.
+    pub fn skip_command<I: Io>(&mut self, command: VideoCommand, resources: &Resources<I>) {
+        match command {
+            VideoCommand::Draw(_) | VideoCommand::DrawString(_) => (),
+            VideoCommand::Blit(blit) => self.flip(blit.page_id),
+            command => self.push_command(command, resources),
+        }
+    }
+
+    /// Swaps the working pages the way a blit of `page_id` does and applies any palette
+    /// requested since the last blit.
+    fn flip(&mut self, page_id: u8) {
         match page_id {
+            0xff => {
+                let temp = self.working_page_a;
+                self.working_page_a = self.working_page_b;
+                self.working_page_b = temp;
+            }
+            0xfe => (),
+            _ => {
+                if let Some(page) = self.get_page(page_id) {
+                    self.working_page_a = page;
+                }
+            }
+        }
+
+        if let Some(palette) = self.requested_palette.take() {
+            self.palette = Some(palette);
+            self.gfx.set_palette(palette)
+        }
+    }
+
+    fn get_page(&self, page_id: u8) -> Option<Page> {
+        let page = match page_id {
             0 => Page::Zero,
             1 => Page::One,
             2 => Page::Two,
             3 => Page::Three,
             0xff => self.working_page_b,
             0xfe => self.working_page_a,
-            _ => Page::Zero,
-        }
+            _ => {
+                log::warn!("invalid page id: {:#04x}, skipping", page_id);
+                return None;
+            }
+        };
+
+        Some(page)
     }
 
     fn draw<I: Io>(&mut self, command: DrawCommand, resources: &Resources<I>) {
-        let color = 0xff;
-
         let buffer = match command.polygon.source {
-            PolygonSource::Cinematic => resources.cinematic().expect("cinematic not loaded"),
-            PolygonSource::AltVideo => resources.alt_video().expect("alt video not loaded"),
+            PolygonSource::Cinematic => resources.cinematic(),
+            PolygonSource::AltVideo => resources.alt_video(),
+        };
+
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => {
+                log::debug!(
+                    "{:?} polygons not loaded, skipping draw",
+                    command.polygon.source
+                );
+                return;
+            }
+        };
+
+        self.draw_from(command, buffer)
+    }
+
+    fn draw_from(&mut self, command: DrawCommand, buffer: &[u8]) {
+        let zoom = command.zoom / 64;
+        let zoom = if (0..=MAX_ZOOM).contains(&zoom) {
+            zoom
+        } else {
+            log::warn!("polygon zoom {} out of range, clamping", command.zoom);
+            zoom.clamp(0, MAX_ZOOM)
         };
 
         self.do_draw(
-            color,
+            0xff,
             command.x,
             command.y,
-            command.zoom / 64,
+            zoom,
             command.polygon.buffer_offset,
             buffer,
         )
@@ -202,10 +353,10 @@ impl<T: Gfx> Video<T> {
             let y_bound = pc.read_u8() as i16 * zoom;
             let num_points = pc.read_u8() as usize;
 
-            let x_min = x - x_bound / 2;
-            let x_max = x + x_bound / 2;
-            let y_min = y - y_bound / 2;
-            let y_max = y + y_bound / 2;
+            let x_min = x.saturating_sub(x_bound / 2);
+            let x_max = x.saturating_add(x_bound / 2);
+            let y_min = y.saturating_sub(y_bound / 2);
+            let y_max = y.saturating_add(y_bound / 2);
 
             if x_min > 319 || x_max < 0 || y_min > 199 || y_max < 0 {
                 return;
@@ -225,45 +376,38 @@ impl<T: Gfx> Video<T> {
                 BlendMode::Mask(0x8)
             };
 
-            let mut poly = Polygon {
-                num_points,
-                blend,
-                points: [(0, 0); 50],
-            };
+            let mut points = Vec::with_capacity(num_points);
 
-            if x_bound == 0 && y_bound == 1 && num_points == 4 {
-                poly.points[0] = (x, y);
-                poly.points[1] = (x - 1, y);
-                poly.points[2] = (x - 1, y + 1);
-                poly.points[3] = (x, y + 1);
+            if is_single_pixel(x_bound, y_bound, num_points) {
+                points.extend_from_slice(&single_pixel(x as i32, y as i32));
             } else {
                 for n in 0..num_points {
-                    let x = pc.read_u8() as i16 * zoom;
-                    let y = pc.read_u8() as i16 * zoom;
+                    let x = pc.read_u8() as i32 * zoom as i32;
+                    let y = pc.read_u8() as i32 * zoom as i32;
+                    let x_off = zero_width_offset(x_bound, num_points, n);
 
-                    // Hack for zero width vertical lines
-                    let x_off = if x_bound == 0 && num_points == 4 && n >= 2 {
-                        1
-                    } else {
-                        0
-                    };
-
-                    poly.points[n] = (x + x_min - x_off, y + y_min);
+                    points.push((x + x_min as i32 - x_off, y + y_min as i32));
                 }
             }
 
+            let poly = match Polygon::clipped(&points, blend) {
+                Some(poly) => poly,
+                None => return,
+            };
+
+            self.polygons_drawn += 1;
             self.gfx.draw_polygon(poly);
         } else if mode & 0x3f == 2 {
-            let x = x - pc.read_u8() as i16 * zoom;
-            let y = y - pc.read_u8() as i16 * zoom;
+            let x = x.saturating_sub(pc.read_u8() as i16 * zoom);
+            let y = y.saturating_sub(pc.read_u8() as i16 * zoom);
 
             let num_children = pc.read_u8();
 
             for _ in 0..=num_children {
                 let offset = pc.read_u16();
 
-                let child_x = x + pc.read_u8() as i16 * zoom;
-                let child_y = y + pc.read_u8() as i16 * zoom;
+                let child_x = x.saturating_add(pc.read_u8() as i16 * zoom);
+                let child_y = y.saturating_add(pc.read_u8() as i16 * zoom);
 
                 let color = if offset & 0x8000 != 0 {
                     let color = pc.read_u8();
@@ -283,9 +427,117 @@ impl<T: Gfx> Video<T> {
     }
 }
 
+/// Drops fills and copies whose result is fully replaced by the very next page command,
+/// returning how many were dropped. Only a fill of the same page, or an unscrolled copy
+/// into it between two distinct numbered pages, counts as a replacement, and any other
+/// command in between keeps the earlier one. Page ids `0xfe` and `0xff` may refer to any
+/// page, so copies through them never replace anything. Palette commands touch no page and
+/// are skipped over.
+pub fn coalesce(commands: &mut Vec<VideoCommand>) -> usize {
+    let before = commands.len();
+    let mut keep = vec![true; before];
+
+    for (idx, command) in commands.iter().enumerate() {
+        let dest = match command {
+            VideoCommand::FillVideoPage(fill) => fill.page_id,
+            VideoCommand::CopyVideoPage(copy) if copy_source(copy).is_some() => copy.dest_page_id,
+            _ => continue,
+        };
+
+        let next = commands[idx + 1..]
+            .iter()
+            .find(|c| !matches!(c, VideoCommand::Palette(_)));
+        let replaced = match next {
+            Some(VideoCommand::FillVideoPage(fill)) => fill.page_id == dest,
+            Some(VideoCommand::CopyVideoPage(copy)) => {
+                copy.dest_page_id == dest
+                    && copy_source(copy).is_some_and(|(src, scroll)| {
+                        scroll == 0 && src != dest && src <= 3 && dest <= 3
+                    })
+            }
+            _ => false,
+        };
+
+        keep[idx] = !replaced;
+    }
+
+    let mut keep = keep.into_iter();
+    commands.retain(|_| keep.next().unwrap_or(true));
+    before - commands.len()
+}
+
+/// The page id and scroll `Video::push_command` copies from, `None` when the copy is
+/// skipped entirely.
+fn copy_source(copy: &CopyVideoPageCommand) -> Option<(u8, i16)> {
+    if copy.src_page_id == copy.dest_page_id {
+        return None;
+    }
+
+    let source = if copy.src_page_id >= 0xfe {
+        (copy.src_page_id, 0)
+    } else if copy.src_page_id & 0x80 == 0 {
+        (copy.src_page_id & 0xbf, 0)
+    } else {
+        (copy.src_page_id & 0x3, copy.scroll)
+    };
+
+    Some(source)
+}
+
+/// Expands 16 big endian `0RGB` 4-bit colors to 8-bit components. The result is
+/// already sRGB encoded, both frontends must write it to the screen unchanged.
+fn decode_palette(palette: &[u8]) -> [(u8, u8, u8); 16] {
+    let mut colors = [(0, 0, 0); 16];
+    for (n, color) in colors.iter_mut().enumerate() {
+        let c0 = palette[n * 2];
+        let c1 = palette[n * 2 + 1];
+
+        let r = (((c0 & 0x0f) << 2) | ((c0 & 0x0f) >> 2)) << 2;
+        let g = (((c1 & 0xf0) >> 2) | ((c1 & 0xf0) >> 6)) << 2;
+        let b = (((c1 & 0x0f) >> 2) | ((c1 & 0x0f) << 2)) << 2;
+
+        *color = (r, g, b);
+    }
+    colors
+}
+
+/// A 4 point polygon with a 0x1 bounding box is drawn as a single pixel at its origin,
+/// the same check as `Video::fillPolygon` in rawgl, which plots a point for it.
+fn is_single_pixel(x_bound: i16, y_bound: i16, num_points: usize) -> bool {
+    x_bound == 0 && y_bound == 1 && num_points == 4
+}
+
+fn single_pixel(x: i32, y: i32) -> [(i32, i32); 4] {
+    [(x, y), (x - 1, y), (x - 1, y + 1), (x, y + 1)]
+}
+
+/// Filled triangles cover nothing for a zero width polygon, so the last two points of a
+/// 4 point one are shifted left by a pixel to draw it as a one pixel wide vertical line.
+/// Only the 4 point shape is widened, other point counts are drawn as given.
+fn zero_width_offset(x_bound: i16, num_points: usize, n: usize) -> i32 {
+    if x_bound == 0 && num_points == 4 && n >= 2 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Color index frontends fill pages with before anything is drawn when
+/// `EngineOptions::debug_clear` is set, shown as magenta so reads of pages the game never
+/// filled stand out. It is outside the 16 palette colors, so copies and masked draws
+/// carry it over unchanged.
+pub const DEBUG_CLEAR_COLOR: u8 = 0xff;
+
+const MAX_POLYGON_POINTS: usize = 64;
+/// Zoom comes from a game variable, so cap it well past anything the game uses to keep
+/// `0xff * zoom` within `i16`.
+const MAX_ZOOM: i16 = 32;
+const SCREEN_MAX_X: i32 = 319;
+const SCREEN_MAX_Y: i32 = 199;
+
 #[derive(Debug, Clone)]
 pub struct Polygon {
-    points: [(i16, i16); 50],
+    points: [(i16, i16); MAX_POLYGON_POINTS],
     num_points: usize,
     pub blend: BlendMode,
 }
@@ -296,6 +548,163 @@ impl Polygon {
             .iter()
             .map(|(x, y)| (*x, *y))
     }
+
+    /// Splits the polygon into triangles, as indices into `points`, by clipping ears.
+    /// Clipping can rotate or reverse the game's point order and leave repeated points,
+    /// so nothing is assumed about which point starts which edge. Degenerate polygons
+    /// still produce `num_points - 2` triangles, covering no area.
+    pub fn triangles(&self) -> impl Iterator<Item = [usize; 3]> {
+        let points: Vec<(i64, i64)> = self.points().map(|(x, y)| (x as i64, y as i64)).collect();
+        let cross = |a: usize, b: usize, c: usize| {
+            let ((ax, ay), (bx, by), (cx, cy)) = (points[a], points[b], points[c]);
+            (bx - ax) * (cy - by) - (by - ay) * (cx - bx)
+        };
+
+        let winding: i64 = (0..points.len())
+            .map(|n| {
+                let ((x0, y0), (x1, y1)) = (points[n], points[(n + 1) % points.len()]);
+                x0 * y1 - x1 * y0
+            })
+            .sum();
+        let winding = if winding < 0 { -1 } else { 1 };
+
+        let mut remaining: Vec<usize> = (0..points.len()).collect();
+        let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+
+        while remaining.len() >= 3 {
+            let len = remaining.len();
+            let corner = |n: usize| {
+                (
+                    remaining[(n + len - 1) % len],
+                    remaining[n],
+                    remaining[(n + 1) % len],
+                )
+            };
+            let is_ear = |n: usize| {
+                let (a, b, c) = corner(n);
+                cross(a, b, c) * winding > 0
+                    && remaining.iter().all(|&p| {
+                        [a, b, c].iter().any(|&v| points[v] == points[p])
+                            || cross(a, b, p) * winding < 0
+                            || cross(b, c, p) * winding < 0
+                            || cross(c, a, p) * winding < 0
+                    })
+            };
+
+            // without an ear the rest is degenerate, any corner covers the same nothing
+            let ear = (0..len).find(|&n| is_ear(n)).unwrap_or(0);
+            let (a, b, c) = corner(ear);
+            triangles.push([a, b, c]);
+            remaining.remove(ear);
+        }
+
+        triangles.into_iter()
+    }
+
+    /// A polygon through `points` in screen coordinates, clipped to the screen. `None`
+    /// when nothing is left on screen or clipping leaves too many points.
+    pub fn from_points(points: &[(i32, i32)], blend: BlendMode) -> Option<Self> {
+        Self::clipped(points, blend)
+    }
+
+    fn clipped(points: &[(i32, i32)], blend: BlendMode) -> Option<Self> {
+        let mut clipped = points.to_vec();
+
+        let on_screen = |&(x, y): &(i32, i32)| {
+            (0..=SCREEN_MAX_X).contains(&x) && (0..=SCREEN_MAX_Y).contains(&y)
+        };
+
+        if !points.iter().all(on_screen) {
+            for edge in [
+                ClipEdge::Left,
+                ClipEdge::Right,
+                ClipEdge::Top,
+                ClipEdge::Bottom,
+            ] {
+                clipped = edge.clip(&clipped);
+            }
+        }
+
+        if clipped.is_empty() || clipped.len() > MAX_POLYGON_POINTS {
+            return None;
+        }
+
+        let mut poly = Polygon {
+            points: [(0, 0); MAX_POLYGON_POINTS],
+            num_points: clipped.len(),
+            blend,
+        };
+
+        for (point, (x, y)) in poly.points.iter_mut().zip(clipped) {
+            *point = (x as i16, y as i16);
+        }
+
+        Some(poly)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ClipEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl ClipEdge {
+    fn inside(&self, (x, y): (i32, i32)) -> bool {
+        match self {
+            ClipEdge::Left => x >= 0,
+            ClipEdge::Right => x <= SCREEN_MAX_X,
+            ClipEdge::Top => y >= 0,
+            ClipEdge::Bottom => y <= SCREEN_MAX_Y,
+        }
+    }
+
+    fn intersect(&self, (ax, ay): (i32, i32), (bx, by): (i32, i32)) -> (i32, i32) {
+        let lerp = |from: i32, to: i32, t: f64| from + ((to - from) as f64 * t).round() as i32;
+
+        match self {
+            ClipEdge::Left | ClipEdge::Right => {
+                let edge = if let ClipEdge::Left = self {
+                    0
+                } else {
+                    SCREEN_MAX_X
+                };
+                let t = (edge - ax) as f64 / (bx - ax) as f64;
+                (edge, lerp(ay, by, t))
+            }
+            ClipEdge::Top | ClipEdge::Bottom => {
+                let edge = if let ClipEdge::Top = self {
+                    0
+                } else {
+                    SCREEN_MAX_Y
+                };
+                let t = (edge - ay) as f64 / (by - ay) as f64;
+                (lerp(ax, bx, t), edge)
+            }
+        }
+    }
+
+    fn clip(&self, points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let mut output = Vec::with_capacity(points.len() + 4);
+
+        for (n, &current) in points.iter().enumerate() {
+            let previous = points[(n + points.len() - 1) % points.len()];
+
+            match (self.inside(previous), self.inside(current)) {
+                (true, true) => output.push(current),
+                (true, false) => output.push(self.intersect(previous, current)),
+                (false, true) => {
+                    output.push(self.intersect(previous, current));
+                    output.push(current);
+                }
+                (false, false) => (),
+            }
+        }
+
+        output
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -305,6 +714,65 @@ pub enum BlendMode {
     Blend,
 }
 
+impl BlendMode {
+    /// Pages a polygon with this blend mode reads while it is drawn.
+    pub fn background_snapshot(&self) -> BackgroundSnapshot {
+        match self {
+            BlendMode::Solid(_) => BackgroundSnapshot::default(),
+            BlendMode::Mask(_) => BackgroundSnapshot {
+                active_page: true,
+                page_zero: false,
+            },
+            BlendMode::Blend => BackgroundSnapshot {
+                active_page: false,
+                page_zero: true,
+            },
+        }
+    }
+}
+
+/// Pages a polygon reads back while it is drawn. GPU backends cannot sample a texture
+/// they are rendering into, so they copy these pages aside before drawing the polygon.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct BackgroundSnapshot {
+    /// The page being drawn to, masked polygons brighten what is beneath them
+    pub active_page: bool,
+    /// Page zero, blended polygons reveal it through the page being drawn to
+    pub page_zero: bool,
+}
+
+impl BackgroundSnapshot {
+    pub fn is_empty(&self) -> bool {
+        !self.active_page && !self.page_zero
+    }
+}
+
+/// Splits polygons, in draw order, into ranges a GPU backend can draw with one call each
+/// and the snapshot to take before drawing each range. Runs of solid polygons share a
+/// range, a polygon that reads the background gets a range of its own so its snapshot
+/// includes every polygon drawn before it.
+pub fn draw_batches(
+    polygons: &[Polygon],
+) -> impl Iterator<Item = (Range<usize>, BackgroundSnapshot)> + '_ {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        let first = polygons.get(start)?;
+        let snapshot = first.blend.background_snapshot();
+        let len = if snapshot.is_empty() {
+            polygons[start..]
+                .iter()
+                .take_while(|p| p.blend.background_snapshot().is_empty())
+                .count()
+        } else {
+            1
+        };
+
+        let range = start..start + len;
+        start += len;
+        Some((range, snapshot))
+    })
+}
+
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub enum Page {
     Zero,
@@ -312,3 +780,656 @@ pub enum Page {
     Two,
     Three,
 }
+
+impl Page {
+    pub const ALL: [Page; 4] = [Page::Zero, Page::One, Page::Two, Page::Three];
+}
+
+/// Accessibility remaps applied by the frontends to each resolved palette color, after
+/// the palette lookup. The color blind modes shift the detail lost to each deficiency
+/// into channels that remain distinguishable, leaving grays untouched.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PaletteMode {
+    #[default]
+    Normal,
+    Grayscale,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl PaletteMode {
+    pub const NAMES: [&'static str; 5] = [
+        "normal",
+        "grayscale",
+        "protanopia",
+        "deuteranopia",
+        "tritanopia",
+    ];
+
+    /// Matrix applied to RGB colors, in column-major order for GL `mat3` uniforms.
+    pub fn color_transform(&self) -> [[f32; 3]; 3] {
+        // Simulated dichromat vision, from Machado, Oliveira and Fernandes 2009
+        let simulated = match self {
+            PaletteMode::Normal => return transpose(IDENTITY),
+            PaletteMode::Grayscale => {
+                let luma = [0.299, 0.587, 0.114];
+                return transpose([luma, luma, luma]);
+            }
+            PaletteMode::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            PaletteMode::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            PaletteMode::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        };
+
+        // Redistribute the lost difference, `c + shift * (c - simulated * c)`
+        let shift = match self {
+            PaletteMode::Tritanopia => [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]],
+            _ => [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]],
+        };
+
+        let mut lost = IDENTITY;
+        for (row, sim_row) in lost.iter_mut().zip(simulated) {
+            for (value, sim) in row.iter_mut().zip(sim_row) {
+                *value -= sim;
+            }
+        }
+
+        let mut transform = IDENTITY;
+        for (r, row) in transform.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value += (0..3).map(|n| shift[r][n] * lost[n][c]).sum::<f32>();
+            }
+        }
+
+        transpose(transform)
+    }
+}
+
+impl std::str::FromStr for PaletteMode {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let mode = match name {
+            "normal" => PaletteMode::Normal,
+            "grayscale" | "greyscale" => PaletteMode::Grayscale,
+            "protanopia" => PaletteMode::Protanopia,
+            "deuteranopia" => PaletteMode::Deuteranopia,
+            "tritanopia" => PaletteMode::Tritanopia,
+            _ => return Err(()),
+        };
+
+        Ok(mode)
+    }
+}
+
+const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+fn transpose(m: [[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = m;
+    for (r, row) in m.iter().enumerate() {
+        for (c, value) in row.iter().enumerate() {
+            out[c][r] = *value;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::resources::{GamePart, ResourceType};
+    use crate::testing::{MemIo, RecordingGfx};
+
+    struct EmptyIo;
+
+    impl Io for EmptyIo {
+        type Reader = std::io::Cursor<&'static [u8]>;
+
+        fn load<S: AsRef<str>>(&self, name: S) -> Result<Self::Reader, Error> {
+            match name.as_ref() {
+                "MEMLIST.BIN" => Ok(std::io::Cursor::new(&[0xff])),
+                _ => Err(std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+            }
+        }
+    }
+
+    fn area(points: &[(i32, i32)]) -> i32 {
+        let mut area = 0;
+        for (n, &(x0, y0)) in points.iter().enumerate() {
+            let (x1, y1) = points[(n + 1) % points.len()];
+            area += x0 * y1 - x1 * y0;
+        }
+        area.abs()
+    }
+
+    #[test]
+    fn draw_batches_isolate_polygons_that_read_the_background() {
+        let triangle = [(0, 0), (10, 0), (10, 10)];
+        let polygons: Vec<_> = [
+            BlendMode::Solid(1),
+            BlendMode::Solid(15),
+            BlendMode::Mask(8),
+            BlendMode::Blend,
+            BlendMode::Solid(2),
+        ]
+        .iter()
+        .map(|&blend| Polygon::clipped(&triangle, blend).unwrap())
+        .collect();
+
+        let mask = BackgroundSnapshot {
+            active_page: true,
+            page_zero: false,
+        };
+        let blend = BackgroundSnapshot {
+            active_page: false,
+            page_zero: true,
+        };
+        let none = BackgroundSnapshot::default();
+
+        let batches: Vec<_> = draw_batches(&polygons).collect();
+        assert_eq!(
+            batches,
+            [(0..2, none), (2..3, mask), (3..4, blend), (4..5, none)]
+        );
+        assert_eq!(draw_batches(&[]).count(), 0);
+    }
+
+    #[test]
+    fn triangles_cover_polygon() {
+        let shapes: [&[(i32, i32)]; 4] = [
+            &[(10, 10), (10, 10), (10, 10), (10, 10)],
+            &[(20, 10), (20, 20), (10, 20), (10, 10)],
+            &[(15, 0), (30, 10), (20, 30), (10, 30), (0, 10)],
+            &[
+                (12, 0),
+                (20, 5),
+                (24, 15),
+                (20, 25),
+                (4, 25),
+                (0, 15),
+                (4, 5),
+                (12, 0),
+            ],
+        ];
+
+        for shape in shapes {
+            let poly = Polygon::clipped(shape, BlendMode::Blend).unwrap();
+            let triangles: Vec<_> = poly.triangles().collect();
+            assert_eq!(triangles.len(), shape.len() - 2);
+
+            let covered: i32 = triangles
+                .iter()
+                .map(|t| area(&[shape[t[0]], shape[t[1]], shape[t[2]]]))
+                .sum();
+            assert_eq!(covered, area(shape));
+        }
+    }
+
+    #[test]
+    fn triangles_cover_clipped_and_concave_polygons() {
+        let shapes: [&[(i32, i32)]; 3] = [
+            // clipping starts the points partway down the right edge
+            &[(10, -20), (30, 0), (30, 20), (20, 40), (0, 40), (-10, 0)],
+            &[(300, -5), (340, 50), (330, 210), (280, 190), (290, 60)],
+            &[(0, 0), (40, 0), (40, 40), (20, 10), (0, 40)],
+        ];
+
+        for shape in shapes {
+            let poly = Polygon::clipped(shape, BlendMode::Blend).unwrap();
+            let points: Vec<_> = poly.points().map(|(x, y)| (x as i32, y as i32)).collect();
+            let triangles: Vec<_> = poly.triangles().collect();
+            assert_eq!(triangles.len(), points.len() - 2);
+
+            let covered: i32 = triangles
+                .iter()
+                .map(|t| area(&[points[t[0]], points[t[1]], points[t[2]]]))
+                .sum();
+            assert_eq!(covered, area(&points));
+        }
+    }
+
+    #[test]
+    fn palette_reference_colors() {
+        let mut palette = [0; 32];
+        palette[2..4].copy_from_slice(&[0x0f, 0xff]);
+        palette[4..6].copy_from_slice(&[0x08, 0x40]);
+        palette[6..8].copy_from_slice(&[0x00, 0x0a]);
+
+        let colors = decode_palette(&palette);
+        assert_eq!(colors[0], (0, 0, 0));
+        assert_eq!(colors[1], (252, 252, 252));
+        assert_eq!(colors[2], (136, 68, 0));
+        assert_eq!(colors[3], (0, 0, 168));
+    }
+
+    #[test]
+    fn invalid_page_ids_are_skipped() {
+        let resources = Resources::load(EmptyIo).unwrap();
+        let mut video = Video::new(RecordingGfx::default());
+
+        assert_eq!(video.get_page(0x04), None);
+        assert_eq!(video.get_page(0x40), None);
+
+        for page_id in [0x02, 0x04, 0x7f] {
+            video.push_command(
+                VideoCommand::FillVideoPage(FillVideoPageCommand { page_id, color: 5 }),
+                &resources,
+            );
+            video.push_command(
+                VideoCommand::SelectVideoPage(SelectVideoPageCommand { page_id }),
+                &resources,
+            );
+        }
+        video.push_command(
+            VideoCommand::Blit(BlitCommand { page_id: 0x10 }),
+            &resources,
+        );
+
+        assert_eq!(video.gfx.fills, vec![(Page::Two, 5)]);
+        assert_eq!(video.gfx.selects, vec![Page::Two]);
+        assert_eq!(video.current_page, Page::Two);
+        assert_eq!(video.gfx.blits, vec![Page::One]);
+    }
+
+    #[test]
+    fn copies_onto_the_same_page_are_skipped() {
+        let resources = Resources::load(EmptyIo).unwrap();
+        let mut video = Video::new(RecordingGfx::default());
+
+        let scrolled = VideoCommand::CopyVideoPage(CopyVideoPageCommand {
+            src_page_id: 0x81,
+            dest_page_id: 1,
+            scroll: 8,
+        });
+        for command in [copy(0xfe, 1), copy(0x41, 1), scrolled, copy(0xff, 1)] {
+            video.push_command(command, &resources);
+        }
+
+        assert_eq!(video.gfx.copies, vec![(Page::Two, Page::One, 0)]);
+    }
+
+    #[test]
+    fn palette_without_loaded_resource_is_skipped() {
+        let resources = Resources::load(EmptyIo).unwrap();
+        let mut video = Video::new(RecordingGfx::default());
+
+        video.push_command(
+            VideoCommand::Palette(PaletteCommand { palette_id: 0 }),
+            &resources,
+        );
+        video.push_command(VideoCommand::Blit(BlitCommand { page_id: 1 }), &resources);
+
+        assert!(video.gfx.palettes.is_empty());
+        assert_eq!(video.gfx.blits, vec![Page::One]);
+    }
+
+    #[test]
+    fn current_palette_changes_on_blit() {
+        let part = GamePart::Two;
+        let mut palettes = [0; 64];
+        palettes[32..34].copy_from_slice(&[0x0f, 0xff]);
+        palettes[34..36].copy_from_slice(&[0x08, 0x40]);
+        let io = MemIo::builder()
+            .resource(part.palette(), ResourceType::Palette, &palettes)
+            .resource(part.bytecode(), ResourceType::Bytecode, &[0])
+            .resource(part.cinematic(), ResourceType::PolygonCinematic, &[0])
+            .build();
+        let mut resources = Resources::load(io).unwrap();
+        resources.prepare_part(part);
+        let mut video = Video::new(RecordingGfx::default());
+
+        video.push_command(
+            VideoCommand::Palette(PaletteCommand { palette_id: 1 }),
+            &resources,
+        );
+        assert_eq!(video.current_palette(), None);
+
+        video.push_command(VideoCommand::Blit(BlitCommand { page_id: 1 }), &resources);
+        let palette = video.current_palette().unwrap();
+        assert_eq!(palette, decode_palette(&palettes[32..]));
+        assert_eq!(palette[0], (252, 252, 252));
+        assert_eq!(video.gfx.palettes, vec![palette]);
+    }
+
+    #[test]
+    fn only_the_last_palette_before_a_blit_is_applied() {
+        let part = GamePart::Two;
+        let mut palettes = [0; 96];
+        for (n, palette) in palettes.chunks_exact_mut(32).enumerate() {
+            palette[0..2].copy_from_slice(&[0x00, n as u8 + 1]);
+        }
+        let io = MemIo::builder()
+            .resource(part.palette(), ResourceType::Palette, &palettes)
+            .resource(part.bytecode(), ResourceType::Bytecode, &[0])
+            .resource(part.cinematic(), ResourceType::PolygonCinematic, &[0])
+            .build();
+        let mut resources = Resources::load(io).unwrap();
+        resources.prepare_part(part);
+        let mut video = Video::new(RecordingGfx::default());
+
+        for palette_id in [0, 1, 2] {
+            video.push_command(
+                VideoCommand::Palette(PaletteCommand { palette_id }),
+                &resources,
+            );
+        }
+        video.push_command(VideoCommand::Blit(BlitCommand { page_id: 1 }), &resources);
+        assert_eq!(video.gfx.palettes, vec![decode_palette(&palettes[64..])]);
+
+        // cycling takes a blit per palette, and a blit without a request keeps the last
+        for palette_id in [0, 1] {
+            video.push_command(
+                VideoCommand::Palette(PaletteCommand { palette_id }),
+                &resources,
+            );
+            video.push_command(VideoCommand::Blit(BlitCommand { page_id: 1 }), &resources);
+        }
+        video.push_command(VideoCommand::Blit(BlitCommand { page_id: 1 }), &resources);
+
+        let shown: Vec<_> = video.gfx.palettes.iter().map(|p| p[0]).collect();
+        assert_eq!(shown, [(0, 0, 48), (0, 0, 16), (0, 0, 32)]);
+        assert_eq!(video.gfx.blits.len(), 4);
+    }
+
+    #[test]
+    fn draw_without_loaded_polygons_is_skipped() {
+        let resources = Resources::load(EmptyIo).unwrap();
+        let mut video = Video::new(RecordingGfx::default());
+
+        for source in [PolygonSource::Cinematic, PolygonSource::AltVideo] {
+            video.push_command(
+                VideoCommand::Draw(DrawCommand {
+                    polygon: PolygonResource {
+                        buffer_offset: 0,
+                        source,
+                    },
+                    x: 160,
+                    y: 100,
+                    zoom: 64,
+                }),
+                &resources,
+            );
+        }
+
+        assert!(video.gfx.polygons.is_empty());
+    }
+
+    fn fill(page_id: u8) -> VideoCommand {
+        VideoCommand::FillVideoPage(FillVideoPageCommand { page_id, color: 1 })
+    }
+
+    fn copy(src_page_id: u8, dest_page_id: u8) -> VideoCommand {
+        VideoCommand::CopyVideoPage(CopyVideoPageCommand {
+            src_page_id,
+            dest_page_id,
+            scroll: 0,
+        })
+    }
+
+    #[test]
+    fn coalesce_drops_only_replaced_commands() {
+        let palette = VideoCommand::Palette(PaletteCommand { palette_id: 0 });
+        let select = VideoCommand::SelectVideoPage(SelectVideoPageCommand { page_id: 1 });
+        let scrolled = VideoCommand::CopyVideoPage(CopyVideoPageCommand {
+            src_page_id: 0x82,
+            dest_page_id: 1,
+            scroll: 4,
+        });
+
+        let cases: [(Vec<VideoCommand>, usize); 9] = [
+            (vec![fill(1), fill(1)], 1),
+            (vec![fill(1), palette, fill(1)], 1),
+            (vec![fill(1), copy(2, 1)], 1),
+            (vec![copy(2, 1), copy(2, 1), copy(3, 1)], 2),
+            (vec![fill(1), fill(2)], 0),
+            (vec![fill(1), select, fill(1)], 0),
+            (vec![fill(1), scrolled], 0),
+            (vec![fill(0), copy(0xfe, 0)], 0),
+            (vec![fill(1), copy(1, 1)], 0),
+        ];
+
+        for (mut commands, dropped) in cases {
+            let expected = format!("{:?}", &commands[dropped..]);
+            assert_eq!(coalesce(&mut commands), dropped);
+            assert_eq!(format!("{:?}", commands), expected);
+        }
+    }
+
+    #[test]
+    fn large_zoom_is_clamped() {
+        // a single 0x10 square
+        let buffer = [
+            0xc0, 0x10, 0x10, 0x04, 0x00, 0x00, 0x10, 0x00, 0x10, 0x10, 0x00, 0x10,
+        ];
+        let mut video = Video::new(RecordingGfx::default());
+
+        for (x, zoom) in [(160, i16::MAX), (160, 64 * MAX_ZOOM), (i16::MAX, i16::MAX)] {
+            video.draw_from(
+                DrawCommand {
+                    polygon: PolygonResource {
+                        buffer_offset: 0,
+                        source: PolygonSource::Cinematic,
+                    },
+                    x,
+                    y: 100,
+                    zoom,
+                },
+                &buffer,
+            );
+        }
+
+        assert_eq!(video.gfx.polygons.len(), 2);
+        let clamped: Vec<_> = video.gfx.polygons[0].points().collect();
+        let max: Vec<_> = video.gfx.polygons[1].points().collect();
+        assert_eq!(clamped, max);
+    }
+
+    #[test]
+    fn palette_modes_keep_grays() {
+        for name in PaletteMode::NAMES {
+            let mode: PaletteMode = name.parse().unwrap();
+            let transform = mode.color_transform();
+
+            for gray in [0.0, 0.5, 1.0] {
+                for row in 0..3 {
+                    let value: f32 = transform.iter().map(|column| column[row] * gray).sum();
+                    assert!((value - gray).abs() < 0.001, "{} {}", name, gray);
+                }
+            }
+        }
+
+        assert_eq!(PaletteMode::default().color_transform(), IDENTITY);
+    }
+
+    fn draw_at(video: &mut Video<RecordingGfx>, buffer: &[u8]) -> Vec<(i16, i16)> {
+        video.gfx.polygons.clear();
+        video.draw_from(
+            DrawCommand {
+                polygon: PolygonResource {
+                    buffer_offset: 0,
+                    source: PolygonSource::Cinematic,
+                },
+                x: 160,
+                y: 100,
+                zoom: 64,
+            },
+            buffer,
+        );
+        assert_eq!(video.gfx.polygons.len(), 1);
+        video.gfx.polygons[0].points().collect()
+    }
+
+    #[test]
+    fn single_pixel_polygon() {
+        let mut video = Video::new(RecordingGfx::default());
+        // 0x1 bounds with 4 points, the point data is ignored
+        let buffer = [0xc0, 0x00, 0x01, 0x04, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(
+            draw_at(&mut video, &buffer),
+            vec![(160, 100), (159, 100), (159, 101), (160, 101)]
+        );
+    }
+
+    #[test]
+    fn zero_width_polygons_are_one_pixel_wide() {
+        let mut video = Video::new(RecordingGfx::default());
+
+        // 0x10 tall line, down the right edge then up the left
+        let buffer = [
+            0xc0, 0x00, 0x10, 0x04, 0x00, 0x00, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00,
+        ];
+        assert_eq!(
+            draw_at(&mut video, &buffer),
+            vec![(160, 92), (160, 108), (159, 108), (159, 92)]
+        );
+
+        // anything with width is left alone
+        let buffer = [
+            0xc0, 0x02, 0x10, 0x04, 0x02, 0x00, 0x02, 0x10, 0x00, 0x10, 0x00, 0x00,
+        ];
+        assert_eq!(
+            draw_at(&mut video, &buffer),
+            vec![(161, 92), (161, 108), (159, 108), (159, 92)]
+        );
+    }
+
+    fn clip(points: &[(i32, i32)]) -> Vec<(i16, i16)> {
+        Polygon::clipped(points, BlendMode::Solid(0))
+            .map(|p| p.points().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn polygon_inside_screen_is_unchanged() {
+        let points = [(10, 10), (20, 10), (20, 20), (10, 20)];
+        assert_eq!(clip(&points), vec![(10, 10), (20, 10), (20, 20), (10, 20)]);
+    }
+
+    #[test]
+    fn polygon_straddling_left_edge() {
+        let points = [(-10, 10), (10, 10), (10, 20), (-10, 20)];
+        assert_eq!(clip(&points), vec![(0, 10), (10, 10), (10, 20), (0, 20)]);
+    }
+
+    #[test]
+    fn polygon_straddling_right_edge() {
+        let points = [(310, 10), (330, 10), (330, 20), (310, 20)];
+        assert_eq!(
+            clip(&points),
+            vec![(310, 10), (319, 10), (319, 20), (310, 20)]
+        );
+    }
+
+    #[test]
+    fn polygon_straddling_top_edge() {
+        let points = [(10, -10), (20, -10), (20, 10), (10, 10)];
+        assert_eq!(clip(&points), vec![(10, 0), (20, 0), (20, 10), (10, 10)]);
+    }
+
+    #[test]
+    fn polygon_straddling_bottom_edge() {
+        let points = [(10, 190), (20, 190), (20, 210), (10, 210)];
+        assert_eq!(
+            clip(&points),
+            vec![(10, 199), (10, 190), (20, 190), (20, 199)]
+        );
+    }
+
+    #[test]
+    fn polygon_clipped_diagonally() {
+        let points = [(-10, 100), (10, 80), (10, 120)];
+        assert_eq!(clip(&points), vec![(0, 110), (0, 90), (10, 80), (10, 120)]);
+    }
+
+    #[test]
+    fn polygon_outside_screen_is_dropped() {
+        let points = [(-30, 10), (-10, 10), (-10, 20), (-30, 20)];
+        assert!(Polygon::clipped(&points, BlendMode::Solid(0)).is_none());
+    }
+
+    #[test]
+    fn polygon_with_extreme_coordinates() {
+        let points = [
+            (-100_000, -100_000),
+            (100_000, -100_000),
+            (100_000, 100_000),
+        ];
+        let clipped = clip(&points);
+        assert!(!clipped.is_empty());
+        assert!(clipped
+            .iter()
+            .all(|&(x, y)| (0..=319).contains(&x) && (0..=199).contains(&y)));
+    }
+
+    #[test]
+    fn commands_display_on_one_line() {
+        let cases = [
+            (
+                VideoCommand::Draw(DrawCommand {
+                    polygon: PolygonResource {
+                        buffer_offset: 0x1a2,
+                        source: PolygonSource::Cinematic,
+                    },
+                    x: 160,
+                    y: -4,
+                    zoom: 64,
+                }),
+                "Draw@(160,-4) zoom=64 off=0x01a2 src=Cinematic",
+            ),
+            (
+                VideoCommand::Palette(PaletteCommand { palette_id: 5 }),
+                "Palette id=5",
+            ),
+            (
+                VideoCommand::SelectVideoPage(SelectVideoPageCommand { page_id: 254 }),
+                "Select p=254",
+            ),
+            (
+                VideoCommand::FillVideoPage(FillVideoPageCommand {
+                    page_id: 1,
+                    color: 0,
+                }),
+                "Fill p=1 c=0x0",
+            ),
+            (
+                VideoCommand::CopyVideoPage(CopyVideoPageCommand {
+                    src_page_id: 1,
+                    dest_page_id: 2,
+                    scroll: 0,
+                }),
+                "Copy 1->2 scroll=0",
+            ),
+            (
+                VideoCommand::DrawString(DrawStringCommand {
+                    string_id: 0x181,
+                    x: 3,
+                    y: 180,
+                    color: 0xf,
+                }),
+                "String@(3,180) id=0x181 c=0xf",
+            ),
+            (
+                VideoCommand::Blit(BlitCommand { page_id: 0xff }),
+                "Blit p=255",
+            ),
+        ];
+
+        for (command, expected) in cases {
+            assert_eq!(command.to_string(), expected);
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use crate::audio::{AudioCommand, PlayMusicCommand, PlaySoundCommand};
+use crate::error::Error;
 use crate::input::InputState;
-use crate::resources::{PolygonResource, PolygonSource};
+use crate::resources::{GameVersion, PolygonResource, PolygonSource};
 use crate::video::{
     BlitCommand, CopyVideoPageCommand, DrawCommand, DrawStringCommand, FillVideoPageCommand,
     PaletteCommand, SelectVideoPageCommand, VideoCommand,
@@ -11,6 +13,24 @@ pub struct ProgramCounter<'a> {
 }
 
 impl<'a> ProgramCounter<'a> {
+    pub fn try_read_u8(&mut self) -> Result<u8, Error> {
+        let val = self
+            .mem
+            .get(self.address)
+            .ok_or(Error::ProgramCounterOutOfRange(self.address))?;
+        self.address += 1;
+
+        Ok(*val)
+    }
+    pub fn try_read_i16(&mut self) -> Result<i16, Error> {
+        Ok(self.try_read_u16()? as i16)
+    }
+    pub fn try_read_u16(&mut self) -> Result<u16, Error> {
+        let high = self.try_read_u8()? as u16;
+        let low = self.try_read_u8()? as u16;
+
+        Ok((high << 8) | low)
+    }
     pub fn read_u8(&mut self) -> u8 {
         let val = self
             .mem
@@ -31,7 +51,74 @@ impl<'a> ProgramCounter<'a> {
     }
 }
 
-#[derive(Debug)]
+/// Length of one `SLEEP_TICKS` tick in ms, matching the original 50Hz PAL timing.
+pub const DEFAULT_TICK_MS: u64 = 20;
+/// Instructions a single `execute_frame` call may run by default. The busiest scenes
+/// run a few thousand, so this only trips on threads that loop without yielding.
+pub const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+/// Video commands that may queue up between blits before a warning is logged. Scenes
+/// queue a few hundred at most, so this only trips on scripts that draw without ever
+/// blitting.
+pub const DEFAULT_COMMAND_CAP: usize = 10_000;
+
+/// Index of one of the 256 script variables, see `vars` for the ones with a known use.
+///
+/// The public API takes this rather than a bare `u8` so a variable can't be passed
+/// where a thread is expected, the bytecode itself still works with raw bytes.
+///
+/// ```
+/// use engine::resources::GameVersion;
+/// use engine::vm::{vars, VarId, Vm};
+///
+/// let mut vm = Vm::new(GameVersion::Dos, false);
+/// vm.set_variable(vars::SLEEP_TICKS, 2);
+/// vm.set_variable(VarId(0x10), -1);
+///
+/// assert_eq!(vm.get_variable(VarId::from(0xff)), 2);
+/// assert_eq!(u8::from(vars::SLEEP_TICKS), 0xff);
+/// assert_eq!(vars::SLEEP_TICKS.to_string(), "0xff");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarId(pub u8);
+
+/// Index of one of the 64 script threads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ThreadId(pub u8);
+
+/// A queued video command with the thread that queued it.
+pub type ThreadCommand = (ThreadId, VideoCommand);
+
+/// Bytecode offset within the loaded part.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pc(pub u16);
+
+macro_rules! id_conversions {
+    ($name:ident, $raw:ty, $format:literal) => {
+        impl From<$raw> for $name {
+            fn from(raw: $raw) -> Self {
+                Self(raw)
+            }
+        }
+
+        impl From<$name> for $raw {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, $format, self.0)
+            }
+        }
+    };
+}
+
+id_conversions!(VarId, u8, "{:#04x}");
+id_conversions!(ThreadId, u8, "{}");
+id_conversions!(Pc, u16, "{:#06x}");
+
+#[derive(Debug, Clone)]
 pub struct Vm {
     variables: [i16; 256],
     thread_data: [ThreadData; 64],
@@ -39,12 +126,22 @@ pub struct Vm {
     stack: [u16; 256],
     stack_ptr: usize,
     resume_pending: bool,
-    video_commands: Vec<VideoCommand>,
+    video_commands: Vec<ThreadCommand>,
+    audio_commands: Vec<AudioCommand>,
+    version: GameVersion,
     bypass: bool,
+    tick_ms: u64,
+    thread_activity: [ThreadActivity; 64],
+    last_thread_activity: [ThreadActivity; 64],
+    step_limit: Option<usize>,
+    steps: usize,
+    command_cap: Option<usize>,
+    command_cap_exceeded: bool,
+    action_hold_frames: u32,
 }
 
 impl Vm {
-    pub fn new(bypass: bool) -> Self {
+    pub fn new(version: GameVersion, bypass: bool) -> Self {
         let mut vm = Vm {
             variables: [0; 256],
             thread_data: [ThreadData::default(); 64],
@@ -53,28 +150,127 @@ impl Vm {
             stack_ptr: 0,
             resume_pending: false,
             video_commands: Vec::new(),
+            audio_commands: Vec::new(),
+            version,
             bypass,
+            tick_ms: DEFAULT_TICK_MS,
+            thread_activity: [ThreadActivity::Inactive; 64],
+            last_thread_activity: [ThreadActivity::Inactive; 64],
+            step_limit: Some(DEFAULT_STEP_LIMIT),
+            steps: 0,
+            command_cap: Some(DEFAULT_COMMAND_CAP),
+            command_cap_exceeded: false,
+            action_hold_frames: 0,
         };
 
-        vm.set_var(0x54, 0x81);
-        vm.set_var(vars::RANDOM_SEED, 0x1234);
+        vm.reset();
 
-        if vm.bypass {
-            vm.set_var(0xbc, 0x10);
-            vm.set_var(0xc6, 0x80);
-            vm.set_var(0xf2, 4000);
-            vm.set_var(0xdc, 33);
+        vm
+    }
+
+    /// Returns the VM to the state produced by `new`, reusing the existing buffers.
+    pub fn reset(&mut self) {
+        self.variables = [0; 256];
+        self.thread_data = [ThreadData::default(); 64];
+        self.stack = [0; 256];
+        self.stack_ptr = 0;
+        self.steps = 0;
+        self.action_hold_frames = 0;
+        self.video_commands.clear();
+        self.command_cap_exceeded = false;
+        self.audio_commands.clear();
+        self.thread_activity = [ThreadActivity::Inactive; 64];
+        self.last_thread_activity = [ThreadActivity::Inactive; 64];
+
+        self.set_variable(vars::PLATFORM, self.version.platform_flags());
+        self.set_variable(vars::RANDOM_SEED, 0x1234);
+
+        if self.bypass {
+            self.set_var(0xbc, 0x10);
+            self.set_var(0xc6, 0x80);
+            self.set_var(0xf2, 4000);
+            self.set_var(0xdc, 33);
         }
 
-        vm.init_part();
+        self.init_part();
+    }
 
-        vm
+    /// Sets how many ms each `SLEEP_TICKS` tick lasts. Anything other than
+    /// `DEFAULT_TICK_MS` changes the game speed away from the original.
+    pub fn set_tick_duration(&mut self, ms: u64) {
+        self.tick_ms = ms;
     }
 
+    pub fn tick_duration(&self) -> u64 {
+        self.tick_ms
+    }
+
+    /// Caps the number of instructions a single `execute_frame` call may run before
+    /// returning `Error::StepLimitExceeded`, `None` lets threads run unbounded. Defaults
+    /// to `DEFAULT_STEP_LIMIT`.
+    pub fn set_step_limit(&mut self, limit: Option<usize>) {
+        self.step_limit = limit;
+    }
+
+    /// Sets how many video commands may be queued between blits before a warning is
+    /// logged, `None` never warns. The commands are still kept, so the cap only flags
+    /// runaway scripts rather than stopping them. Defaults to `DEFAULT_COMMAND_CAP`.
+    pub fn set_command_cap(&mut self, cap: Option<usize>) {
+        self.command_cap = cap;
+    }
+
+    /// Whether the video commands queued since they were last drained went over the
+    /// command cap.
+    pub fn command_cap_exceeded(&self) -> bool {
+        self.command_cap_exceeded
+    }
+
+    pub fn get_variable(&self, variable_id: VarId) -> i16 {
+        self.get_var(variable_id.0)
+    }
+
+    pub fn set_variable(&mut self, variable_id: VarId, value: i16) {
+        self.set_var(variable_id.0, value)
+    }
+
+    pub fn variables(&self) -> &[i16; 256] {
+        &self.variables
+    }
+
+    pub fn last_thread_activity(&self) -> &[ThreadActivity; 64] {
+        &self.last_thread_activity
+    }
+
+    /// Where `thread` stopped in the last frame, `None` when it is not active. A pc set
+    /// by `SetVec` only shows once the next frame has started.
+    pub fn thread_pc(&self, thread: ThreadId) -> Option<Pc> {
+        let pc = self.thread_data.get(thread.0 as usize)?.pc;
+        if pc == 0xffff {
+            None
+        } else {
+            Some(Pc(pc))
+        }
+    }
+
+    /// Drains the drawing queued since the last call, pass each to `Video::push_command`
+    /// before the blit `execute_frame` yielded for is presented.
     pub fn video_commands(&mut self) -> impl Iterator<Item = VideoCommand> + '_ {
+        self.thread_video_commands().map(|(_, command)| command)
+    }
+
+    /// Like `video_commands`, also giving the thread that queued each command.
+    pub fn thread_video_commands(&mut self) -> impl Iterator<Item = ThreadCommand> + '_ {
+        self.command_cap_exceeded = false;
         self.video_commands.drain(..)
     }
 
+    /// Drains the sounds and music started since the last call.
+    pub fn audio_commands(&mut self) -> impl Iterator<Item = AudioCommand> + '_ {
+        self.audio_commands.drain(..)
+    }
+
+    /// Stops every thread and restarts thread 0 at the top of the bytecode, call after
+    /// `Resources::prepare_part` loads a new part.
     pub fn init_part(&mut self) {
         self.set_var(0xe4, 0x14);
         for thread in 0..64 {
@@ -90,29 +286,75 @@ impl Vm {
         self.resume_pending = false;
     }
 
-    fn decode<'a>(&mut self, pc: &mut ProgramCounter<'a>) -> Instruction {
+    /// Decodes the instruction at `pc`. Operands are big endian, `var` is a variable id,
+    /// `u8`/`u16`/`i16` are immediates.
+    ///
+    /// | Opcode      | Instruction       | Operands                                 |
+    /// |-------------|-------------------|------------------------------------------|
+    /// | `0x00`      | `MovConst`        | var, i16                                 |
+    /// | `0x01`      | `Mov`             | dest var, src var                        |
+    /// | `0x02`      | `Add`             | dest var, src var                        |
+    /// | `0x03`      | `AddConst`        | var, i16                                 |
+    /// | `0x04`      | `Call`            | u16 address                              |
+    /// | `0x05`      | `Ret`             |                                          |
+    /// | `0x06`      | `TPause`          |                                          |
+    /// | `0x07`      | `Jmp`             | u16 address                              |
+    /// | `0x08`      | `SetVec`          | thread u8, u16 address                   |
+    /// | `0x09`      | `Jnz`             | var, u16 address                         |
+    /// | `0x0a`      | `CondJmp`         | mode u8, var, operand, u16 address       |
+    /// | `0x0b`      | `SetPalette`      | u16, palette in the high byte            |
+    /// | `0x0c`      | `TReset`          | first thread, last thread, mode 0/1/2    |
+    /// | `0x0d`      | `SelectVideoPage` | page u8                                  |
+    /// | `0x0e`      | `FillVideoPage`   | page u8, color u8                        |
+    /// | `0x0f`      | `CopyVideoPage`   | src page u8, dest page u8                |
+    /// | `0x10`      | `Blit`            | page u8                                  |
+    /// | `0x11`      | `TKill`           |                                          |
+    /// | `0x12`      | `DrawString`      | string id u16, x u8, y u8, color u8      |
+    /// | `0x13`      | `Sub`             | dest var, src var                        |
+    /// | `0x14`      | `And`             | var, u16                                 |
+    /// | `0x15`      | `Or`              | var, u16                                 |
+    /// | `0x16`      | `Shl`             | var, u16                                 |
+    /// | `0x17`      | `Shr`             | var, u16                                 |
+    /// | `0x18`      | `PlaySound`       | resource u16, frequency, volume, channel |
+    /// | `0x19`      | `LoadRes`         | resource or part id u16                  |
+    /// | `0x1a`      | `PlayMusic`       | resource u16, delay u16, position u8     |
+    /// | `0x1b-0x3f` | invalid           |                                          |
+    /// | `0x40-0x7f` | `Draw`            | u16 offset, then x, y, zoom per the mode |
+    /// | `0x80-0xff` | `Draw`            | low offset byte, x u8, y u8              |
+    ///
+    /// `CondJmp` mode bits 0-2 pick the comparison, `==`, `!=`, `>`, `>=`, `<`, `<=`, and
+    /// the top two bits the operand: `0b1x` a var, `0b01` an i16, `0b00` a u8.
+    ///
+    /// `0x40-0x7f` draws pack operand modes into the opcode. Bits 4-5 for x: an i16, a
+    /// var, a u8, or a u8 plus 256. Bits 2-3 for y: an i16, a var, or a u8. Bits 0-1 for
+    /// zoom: the default 0x40, a var, a u8, or the default while drawing from the alt
+    /// video segment.
+    ///
+    /// `0x80-0xff` draws take their offset from the opcode's low seven bits and the next
+    /// byte, at the default zoom. A y past 199 is clamped and the excess added to x.
+    fn decode<'a>(&mut self, pc: &mut ProgramCounter<'a>) -> Result<Instruction, Error> {
         //print!("{}:{:04X}\t", self.current_thread, pc.address);
-        let op = pc.read_u8();
+        let op = pc.try_read_u8()?;
         //print!("{:02X}\t", op);
-        match op {
-            0x00 => Instruction::MovConst(pc.read_u8(), pc.read_i16()),
-            0x01 => Instruction::Mov(pc.read_u8(), pc.read_u8()),
-            0x02 => Instruction::Add(pc.read_u8(), pc.read_u8()),
-            0x03 => Instruction::AddConst(pc.read_u8(), pc.read_i16()),
-            0x04 => Instruction::Call(pc.read_u16()),
+        let instruction = match op {
+            0x00 => Instruction::MovConst(pc.try_read_u8()?, pc.try_read_i16()?),
+            0x01 => Instruction::Mov(pc.try_read_u8()?, pc.try_read_u8()?),
+            0x02 => Instruction::Add(pc.try_read_u8()?, pc.try_read_u8()?),
+            0x03 => Instruction::AddConst(pc.try_read_u8()?, pc.try_read_i16()?),
+            0x04 => Instruction::Call(pc.try_read_u16()?),
             0x05 => Instruction::Ret,
             0x06 => Instruction::TPause,
-            0x07 => Instruction::Jmp(pc.read_u16()),
-            0x08 => Instruction::SetVec(pc.read_u8(), pc.read_u16()),
-            0x09 => Instruction::Jnz(pc.read_u8(), pc.read_u16()),
+            0x07 => Instruction::Jmp(pc.try_read_u16()?),
+            0x08 => Instruction::SetVec(pc.try_read_u8()?, pc.try_read_u16()?),
+            0x09 => Instruction::Jnz(pc.try_read_u8()?, pc.try_read_u16()?),
             0x0a => {
-                let op = pc.read_u8();
-                let variable = pc.read_u8();
+                let op = pc.try_read_u8()?;
+                let variable = pc.try_read_u8()?;
 
                 let operand = match op & 0xc0 {
-                    0x80 | 0xc0 => VarOrConst::Variable(pc.read_u8()),
-                    0x40 => VarOrConst::Const(pc.read_i16()),
-                    0x00 => VarOrConst::Const(pc.read_u8() as i16),
+                    0x80 | 0xc0 => VarOrConst::Variable(pc.try_read_u8()?),
+                    0x40 => VarOrConst::Const(pc.try_read_i16()?),
+                    0x00 => VarOrConst::Const(pc.try_read_u8()? as i16),
                     _ => unreachable!("invalid match arm"),
                 };
 
@@ -123,36 +365,46 @@ impl Vm {
                     3 => JmpCondition::GreaterEq,
                     4 => JmpCondition::Less,
                     5 => JmpCondition::LessEq,
-                    _ => panic!("invalid jmp condition: {}", op & 0x8),
+                    _ => return Err(Error::InvalidJmpCondition(op)),
                 };
 
-                let destination = pc.read_u16();
+                let destination = pc.try_read_u16()?;
 
                 Instruction::CondJmp(condition, variable, operand, destination)
             }
-            0x0b => Instruction::SetPalette(pc.read_u16()),
-            0x0c => Instruction::TReset(pc.read_u8(), pc.read_u8(), pc.read_u8()),
-            0x0d => Instruction::SelectVideoPage(pc.read_u8()),
-            0x0e => Instruction::FillVideoPage(pc.read_u8(), pc.read_u8()),
-            0x0f => Instruction::CopyVideoPage(pc.read_u8(), pc.read_u8()),
-            0x10 => Instruction::Blit(pc.read_u8()),
+            0x0b => Instruction::SetPalette(pc.try_read_u16()?),
+            0x0c => Instruction::TReset(pc.try_read_u8()?, pc.try_read_u8()?, pc.try_read_u8()?),
+            0x0d => Instruction::SelectVideoPage(pc.try_read_u8()?),
+            0x0e => Instruction::FillVideoPage(pc.try_read_u8()?, pc.try_read_u8()?),
+            0x0f => Instruction::CopyVideoPage(pc.try_read_u8()?, pc.try_read_u8()?),
+            0x10 => Instruction::Blit(pc.try_read_u8()?),
             0x11 => Instruction::TKill,
-            0x12 => {
-                Instruction::DrawString(pc.read_u16(), pc.read_u8(), pc.read_u8(), pc.read_u8())
-            }
-            0x13 => Instruction::Sub(pc.read_u8(), pc.read_u8()),
-            0x14 => Instruction::And(pc.read_u8(), pc.read_u16()),
-            0x15 => Instruction::Or(pc.read_u8(), pc.read_u16()),
-            0x16 => Instruction::Shl(pc.read_u8(), pc.read_u16()),
-            0x17 => Instruction::Shr(pc.read_u8(), pc.read_u16()),
-            0x18 => Instruction::PlaySound(pc.read_u16(), pc.read_u8(), pc.read_u8(), pc.read_u8()),
-            0x19 => Instruction::LoadRes(pc.read_u16()),
-            0x1a => Instruction::PlayMusic(pc.read_u16(), pc.read_u16(), pc.read_u8()),
+            0x12 => Instruction::DrawString(
+                pc.try_read_u16()?,
+                pc.try_read_u8()?,
+                pc.try_read_u8()?,
+                pc.try_read_u8()?,
+            ),
+            0x13 => Instruction::Sub(pc.try_read_u8()?, pc.try_read_u8()?),
+            0x14 => Instruction::And(pc.try_read_u8()?, pc.try_read_u16()?),
+            0x15 => Instruction::Or(pc.try_read_u8()?, pc.try_read_u16()?),
+            0x16 => Instruction::Shl(pc.try_read_u8()?, pc.try_read_u16()?),
+            0x17 => Instruction::Shr(pc.try_read_u8()?, pc.try_read_u16()?),
+            0x18 => Instruction::PlaySound(
+                pc.try_read_u16()?,
+                pc.try_read_u8()?,
+                pc.try_read_u8()?,
+                pc.try_read_u8()?,
+            ),
+            0x19 => Instruction::LoadRes(pc.try_read_u16()?),
+            0x1a => {
+                Instruction::PlayMusic(pc.try_read_u16()?, pc.try_read_u16()?, pc.try_read_u8()?)
+            }
             op if op & 0x80 != 0 => {
-                let offset = ((op as u16) << 8) | pc.read_u8() as u16;
+                let offset = ((op as u16) << 8) | pc.try_read_u8()? as u16;
 
-                let mut x = pc.read_u8() as i16;
-                let mut y = pc.read_u8() as i16;
+                let mut x = pc.try_read_u8()? as i16;
+                let mut y = pc.try_read_u8()? as i16;
 
                 let h = y - 199;
 
@@ -174,26 +426,26 @@ impl Vm {
                 )
             }
             op if op & 0x40 != 0 => {
-                let offset = pc.read_u16();
+                let offset = pc.try_read_u16()?;
                 let x = match op & 0x30 {
-                    0x00 => VarOrConst::Const(pc.read_i16()),
-                    0x10 => VarOrConst::Variable(pc.read_u8()),
-                    0x20 => VarOrConst::Const(pc.read_u8() as i16),
-                    0x30 => VarOrConst::Const(pc.read_u8() as i16 + 0x100),
+                    0x00 => VarOrConst::Const(pc.try_read_i16()?),
+                    0x10 => VarOrConst::Variable(pc.try_read_u8()?),
+                    0x20 => VarOrConst::Const(pc.try_read_u8()? as i16),
+                    0x30 => VarOrConst::Const(pc.try_read_u8()? as i16 + 0x100),
                     _ => unreachable!("invalid match arm"),
                 };
 
                 let y = match op & 0x0c {
-                    0x00 => VarOrConst::Const(pc.read_i16()),
-                    0x04 => VarOrConst::Variable(pc.read_u8()),
-                    0x08 | 0x0c => VarOrConst::Const(pc.read_u8() as i16),
+                    0x00 => VarOrConst::Const(pc.try_read_i16()?),
+                    0x04 => VarOrConst::Variable(pc.try_read_u8()?),
+                    0x08 | 0x0c => VarOrConst::Const(pc.try_read_u8()? as i16),
                     _ => unreachable!("invalid match arm"),
                 };
 
                 let zoom = match op & 0x03 {
                     0x00 => VarOrConst::Const(0x40),
-                    0x01 => VarOrConst::Variable(pc.read_u8()),
-                    0x02 => VarOrConst::Const(pc.read_u8() as i16),
+                    0x01 => VarOrConst::Variable(pc.try_read_u8()?),
+                    0x02 => VarOrConst::Const(pc.try_read_u8()? as i16),
                     0x03 => VarOrConst::Const(0x40),
                     _ => unreachable!("invalid match arm"),
                 };
@@ -211,14 +463,13 @@ impl Vm {
 
                 Instruction::Draw(polygon, x, y, zoom)
             }
-            _ => panic!("invalid opcode"),
-        }
+            _ => return Err(Error::InvalidOpcode(op)),
+        };
+
+        Ok(instruction)
     }
 
     fn get_var(&self, variable_id: u8) -> i16 {
-        if variable_id == vars::MUSIC_MARKER {
-            eprintln!("unimplemented: read music marker");
-        }
         self.variables[variable_id as usize]
     }
 
@@ -234,7 +485,7 @@ impl Vm {
         &mut self.thread_data[thread_id as usize]
     }
 
-    fn execute(&mut self, instruction: Instruction) -> InstructionResult {
+    fn execute(&mut self, instruction: Instruction) -> Result<InstructionResult, Error> {
         //println!("{:?}", instruction);
         match instruction {
             Instruction::MovConst(dest, value) => self.set_var(dest, value),
@@ -249,7 +500,7 @@ impl Vm {
             }
             Instruction::Call(dest) => {
                 if self.stack_ptr == 0xff {
-                    panic!("stack overflow");
+                    return Err(Error::StackOverflow);
                 }
 
                 self.stack[self.stack_ptr] = self.current_thread().pc;
@@ -259,19 +510,23 @@ impl Vm {
             }
             Instruction::Ret => {
                 if self.stack_ptr == 0 {
-                    panic!("stack underflow");
+                    return Err(Error::StackUnderflow);
                 }
 
                 self.stack_ptr -= 1;
                 self.current_thread().pc = self.stack[self.stack_ptr];
             }
             Instruction::TPause => {
-                return InstructionResult::NextThread;
+                return Ok(InstructionResult::NextThread);
             }
             Instruction::Jmp(dest) => {
                 self.current_thread().pc = dest;
             }
             Instruction::SetVec(thread_id, pc) => {
+                if thread_id >= 64 {
+                    return Err(Error::InvalidThreadId(thread_id));
+                }
+
                 self.thread(thread_id).requested_pc = pc;
             }
             Instruction::Jnz(var, dest) => {
@@ -303,74 +558,71 @@ impl Vm {
                 }
             }
             Instruction::SetPalette(palette_id) => {
-                self.video_commands
-                    .push(VideoCommand::Palette(PaletteCommand {
-                        palette_id: (palette_id >> 8) as u8,
-                    }))
+                self.push_video_command(VideoCommand::Palette(PaletteCommand {
+                    palette_id: (palette_id >> 8) as u8,
+                }))
             }
-            Instruction::TReset(thread_start, mut thread_end, mode) => {
-                if thread_end >= 64 {
-                    thread_end &= 63;
-                }
-
+            Instruction::TReset(thread_start, thread_end, mode) => {
+                let thread_end = thread_end & 63;
                 if thread_end < thread_start {
-                    panic!(
-                        "invalid thread reset range: {} {} {}",
-                        thread_start, thread_end, mode
-                    );
+                    return Err(Error::InvalidThreadRange(thread_start, thread_end));
                 }
 
-                if mode == 2 {
-                    for thread in thread_start..=thread_end {
-                        self.thread(thread).requested_pc = 0xfffe;
+                match mode {
+                    0 | 1 => {
+                        for thread in thread_start..=thread_end {
+                            self.thread(thread).requested_pause = mode == 1;
+                        }
                     }
-                } else if mode < 2 {
-                    for thread in thread_start..=thread_end {
-                        self.thread(thread).requested_pause = mode == 1;
+                    2 => {
+                        for thread in thread_start..=thread_end {
+                            self.thread(thread).requested_pc = 0xfffe;
+                        }
                     }
+                    _ => log::warn!(
+                        "ignoring thread reset of {}..={} with unknown mode {}",
+                        thread_start,
+                        thread_end,
+                        mode
+                    ),
                 }
             }
             Instruction::SelectVideoPage(page_id) => {
-                self.video_commands
-                    .push(VideoCommand::SelectVideoPage(SelectVideoPageCommand {
-                        page_id,
-                    }))
+                self.push_video_command(VideoCommand::SelectVideoPage(SelectVideoPageCommand {
+                    page_id,
+                }))
             }
             Instruction::FillVideoPage(page_id, color) => {
-                self.video_commands
-                    .push(VideoCommand::FillVideoPage(FillVideoPageCommand {
-                        page_id,
-                        color,
-                    }))
+                self.push_video_command(VideoCommand::FillVideoPage(FillVideoPageCommand {
+                    page_id,
+                    color,
+                }))
             }
             Instruction::CopyVideoPage(src_page_id, dest_page_id) => {
-                let scroll = self.get_var(vars::SCROLL_Y);
-                self.video_commands
-                    .push(VideoCommand::CopyVideoPage(CopyVideoPageCommand {
-                        src_page_id,
-                        dest_page_id,
-                        scroll,
-                    }))
+                let scroll = self.get_variable(vars::SCROLL_Y);
+                self.push_video_command(VideoCommand::CopyVideoPage(CopyVideoPageCommand {
+                    src_page_id,
+                    dest_page_id,
+                    scroll,
+                }))
             }
             Instruction::Blit(page_id) => {
                 self.set_var(0xf7, 0);
-                let duration = self.get_var(vars::SLEEP_TICKS) as u64 * 20;
-                self.video_commands
-                    .push(VideoCommand::Blit(BlitCommand { page_id }));
-                return InstructionResult::Yield(Yield::Blit(duration));
+                let duration = self.get_variable(vars::SLEEP_TICKS) as u64 * self.tick_ms;
+                self.push_video_command(VideoCommand::Blit(BlitCommand { page_id }));
+                return Ok(InstructionResult::Yield(Yield::Blit(duration)));
             }
             Instruction::TKill => {
                 self.current_thread().pc = 0xffff;
-                return InstructionResult::NextThread;
+                return Ok(InstructionResult::NextThread);
             }
             Instruction::DrawString(string_id, x, y, color) => {
-                self.video_commands
-                    .push(VideoCommand::DrawString(DrawStringCommand {
-                        string_id,
-                        x,
-                        y,
-                        color,
-                    }));
+                self.push_video_command(VideoCommand::DrawString(DrawStringCommand {
+                    string_id,
+                    x,
+                    y,
+                    color,
+                }));
             }
             Instruction::Sub(dest, src) => {
                 let res = self.get_var(dest).wrapping_sub(self.get_var(src));
@@ -384,19 +636,39 @@ impl Vm {
                 let res = (self.get_var(dest) as u16) | value;
                 self.set_var(dest, res as i16);
             }
+            // The original shifts in a wider register, so shifting a 16-bit variable by 16
+            // or more clears it rather than wrapping the shift amount.
             Instruction::Shl(dest, value) => {
-                let res = (self.get_var(dest) as u16) << value;
+                let res = (self.get_var(dest) as u16)
+                    .checked_shl(value as u32)
+                    .unwrap_or(0);
                 self.set_var(dest, res as i16);
             }
             Instruction::Shr(dest, value) => {
-                let res = (self.get_var(dest) as u16) >> value;
+                let res = (self.get_var(dest) as u16)
+                    .checked_shr(value as u32)
+                    .unwrap_or(0);
                 self.set_var(dest, res as i16);
             }
-            Instruction::PlaySound(_res_id, _freq, _vol, _channel) => (),
+            Instruction::PlaySound(resource_id, frequency, volume, channel) => self
+                .audio_commands
+                .push(AudioCommand::PlaySound(PlaySoundCommand {
+                    resource_id,
+                    frequency,
+                    volume,
+                    channel,
+                })),
             Instruction::LoadRes(res_id) => {
-                return InstructionResult::Yield(Yield::ReqResource(res_id))
+                return Ok(InstructionResult::Yield(Yield::ReqResource(res_id)))
+            }
+            Instruction::PlayMusic(resource_id, delay, position) => {
+                self.audio_commands
+                    .push(AudioCommand::PlayMusic(PlayMusicCommand {
+                        resource_id,
+                        delay,
+                        position,
+                    }))
             }
-            Instruction::PlayMusic(_res_id, _delay, _pos) => (),
             Instruction::Draw(polygon, x, y, zoom) => {
                 let x = match x {
                     VarOrConst::Variable(v) => self.get_var(v),
@@ -411,7 +683,7 @@ impl Vm {
                     VarOrConst::Const(n) => n,
                 };
 
-                self.video_commands.push(VideoCommand::Draw(DrawCommand {
+                self.push_video_command(VideoCommand::Draw(DrawCommand {
                     polygon,
                     x,
                     y,
@@ -420,34 +692,97 @@ impl Vm {
             }
         }
 
-        InstructionResult::Continue
+        Ok(InstructionResult::Continue)
+    }
+
+    fn push_video_command(&mut self, command: VideoCommand) {
+        self.video_commands
+            .push((ThreadId(self.current_thread), command));
+
+        let over_cap = self
+            .command_cap
+            .is_some_and(|cap| self.video_commands.len() > cap);
+        if over_cap && !self.command_cap_exceeded {
+            self.command_cap_exceeded = true;
+            log::warn!(
+                "thread {} queued over {} video commands without a blit, at pc {:#06x}",
+                self.current_thread,
+                self.video_commands.len() - 1,
+                self.thread_data[self.current_thread as usize].pc
+            );
+        }
     }
 
-    fn execute_thread(&mut self, mem: &[u8]) -> ThreadResult {
+    fn execute_thread(&mut self, mem: &[u8]) -> Result<ThreadResult, Error> {
         loop {
+            if let Some(limit) = self.step_limit {
+                if self.steps >= limit {
+                    log::warn!(
+                        "thread {} ran {} instructions without yielding, stopped at pc {:#06x}",
+                        self.current_thread,
+                        limit,
+                        self.thread_data[self.current_thread as usize].pc
+                    );
+                    return Err(Error::StepLimitExceeded);
+                }
+                self.steps += 1;
+            }
+
             let mut pc = ProgramCounter {
                 mem,
                 address: self.current_thread().pc as usize,
             };
-            let instruction = self.decode(&mut pc);
+            let instruction = self.decode(&mut pc)?;
             self.current_thread().pc = pc.address as u16;
 
-            match self.execute(instruction) {
-                InstructionResult::Yield(y) => break ThreadResult::Yield(y),
-                InstructionResult::NextThread => break ThreadResult::Continue,
+            match self.execute(instruction)? {
+                InstructionResult::Yield(y) => break Ok(ThreadResult::Yield(y)),
+                InstructionResult::NextThread => break Ok(ThreadResult::Continue),
                 InstructionResult::Continue => continue,
             }
         }
     }
 
-    pub fn execute_frame(&mut self, mem: &[u8], input: InputState) -> FrameResult {
+    /// Runs threads until one yields or the frame completes. Malformed bytecode returns
+    /// an error and kills the faulting thread, the next call carries on with the threads
+    /// after it, the same as after a yield.
+    pub fn execute_frame(&mut self, mem: &[u8], input: InputState) -> Result<FrameResult, Error> {
+        self.steps = 0;
         if !self.resume_pending {
             self.update_threads();
             self.current_thread = 0;
+            self.last_thread_activity = self.thread_activity;
+            self.thread_activity = [ThreadActivity::Inactive; 64];
+            self.count_action_hold(input);
         }
         self.resume_frame(mem, input)
     }
 
+    /// The scripts only ever see whether action is held this frame, charging the gun is
+    /// done by the scripts counting frames themselves. The same count is kept here so
+    /// frontends can show it, frames resumed after a resource load are not counted twice.
+    fn count_action_hold(&mut self, input: InputState) {
+        self.action_hold_frames = if input.action {
+            self.action_hold_frames.saturating_add(1)
+        } else {
+            0
+        };
+    }
+
+    /// Frames in a row that action has been held for, including the current frame, or 0
+    /// when it was released on the last frame.
+    pub fn action_hold_frames(&self) -> u32 {
+        self.action_hold_frames
+    }
+
+    /// Mirrors the reference engine: up wins over down and left over right, and every
+    /// variable is written each frame so released buttons read as zero.
+    ///
+    /// There is no walk or run state here, a held direction writes the same values on
+    /// every frame and any acceleration comes from the scripts counting frames in their
+    /// own variables. Changing how soon the hero runs, or making them always run or
+    /// walk, therefore means patching the part's bytecode with
+    /// `Resources::apply_bytecode_patch` rather than changing these variables.
     fn update_input(&mut self, input: InputState) {
         let mut left_right = 0;
         let mut up_down = 0;
@@ -465,28 +800,28 @@ impl Vm {
             up_down = 1;
             input_mask |= 4;
         }
-
-        self.set_var(vars::HERO_POS_UP_DOWN, up_down);
-
         if input.up {
             up_down = -1;
             input_mask |= 8;
-            self.set_var(vars::HERO_POS_UP_DOWN, -1);
         }
 
-        self.set_var(vars::HERO_POS_MASK, input_mask);
+        self.set_variable(vars::HERO_POS_UP_DOWN, up_down);
+        self.set_variable(vars::HERO_POS_JUMP_DOWN, up_down);
+        self.set_variable(vars::HERO_POS_LEFT_RIGHT, left_right);
+        self.set_variable(vars::HERO_POS_MASK, input_mask);
 
-        if input.action {
+        let action = if input.action {
             input_mask |= 0x80;
-            self.set_var(vars::HERO_ACTION, 1);
-        }
+            1
+        } else {
+            0
+        };
 
-        self.set_var(vars::HERO_POS_JUMP_DOWN, up_down);
-        self.set_var(vars::HERO_POS_LEFT_RIGHT, left_right);
-        self.set_var(vars::HERO_ACTION_POS_MASK, input_mask);
+        self.set_variable(vars::HERO_ACTION, action);
+        self.set_variable(vars::HERO_ACTION_POS_MASK, input_mask);
     }
 
-    fn resume_frame(&mut self, mem: &[u8], input: InputState) -> FrameResult {
+    fn resume_frame(&mut self, mem: &[u8], input: InputState) -> Result<FrameResult, Error> {
         self.update_input(input);
 
         for thread in self.current_thread..64 {
@@ -494,6 +829,9 @@ impl Vm {
             let thread_data = self.current_thread();
 
             if thread_data.paused {
+                if thread_data.pc != 0xffff {
+                    self.thread_activity[thread as usize] = ThreadActivity::Paused;
+                }
                 continue;
             }
 
@@ -504,14 +842,36 @@ impl Vm {
                     self.resume_pending = false;
                 }
 
-                if let ThreadResult::Yield(y) = self.execute_thread(mem) {
+                let result = match self.execute_thread(mem) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        self.current_thread().pc = 0xffff;
+                        self.thread_activity[thread as usize] = ThreadActivity::Killed;
+                        self.current_thread = thread + 1;
+                        self.stack_ptr = 0;
+                        self.resume_pending = true;
+                        return Err(err);
+                    }
+                };
+                let activity = &mut self.thread_activity[thread as usize];
+
+                if let ThreadResult::Yield(y) = result {
+                    *activity = ThreadActivity::Yielded;
                     self.resume_pending = true;
-                    return FrameResult::Yield(y);
+                    return Ok(FrameResult::Yield(y));
+                }
+
+                if self.thread_data[thread as usize].pc == 0xffff {
+                    *activity = ThreadActivity::Killed;
+                } else if *activity != ThreadActivity::Yielded {
+                    *activity = ThreadActivity::Ran;
                 }
             }
         }
 
-        FrameResult::Complete
+        // a frame resumed after an error may have had no thread left to run
+        self.resume_pending = false;
+        Ok(FrameResult::Complete)
     }
 
     fn update_threads(&mut self) {
@@ -533,6 +893,31 @@ impl Vm {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ThreadActivity {
+    Inactive,
+    Paused,
+    Ran,
+    Yielded,
+    Killed,
+}
+
+/// Scheduling state of one thread.
+///
+/// Only `TKill` acts immediately, on the running thread. `SetVec` and `TReset` just fill
+/// in the `requested_` fields, which `update_threads` applies at the start of the next
+/// frame as the original engine does. So a request for any thread, including a later
+/// thread in the same frame or the requesting thread itself, never changes what runs in
+/// the current frame, and when one frame makes several requests for a thread the last
+/// one wins.
+///
+/// - `TReset` modes 0 and 1 set `requested_pause`, copied into `paused` every frame, so a
+///   thread stays paused until a later mode 0 reset.
+/// - `TReset` mode 2 requests pc `0xfffe`, applied as `0xffff`, which deactivates the
+///   thread but leaves its pause state alone.
+/// - `SetVec` requests a new pc the same way, reactivating a deactivated thread.
+/// - Other `TReset` modes are ignored, a reversed range is an error. The last thread is
+///   taken modulo 64.
 #[derive(Debug, Default, Copy, Clone)]
 struct ThreadData {
     pub pc: u16,
@@ -614,14 +999,718 @@ pub enum FrameResult {
 }
 
 pub mod vars {
-    pub const HERO_POS_UP_DOWN: u8 = 0xe5;
-    pub const HERO_ACTION: u8 = 0xfa;
-    pub const HERO_POS_JUMP_DOWN: u8 = 0xfb;
-    pub const HERO_POS_LEFT_RIGHT: u8 = 0xfc;
-    pub const HERO_POS_MASK: u8 = 0xfd;
-    pub const HERO_ACTION_POS_MASK: u8 = 0xfe;
-    pub const RANDOM_SEED: u8 = 0x3c;
-    pub const MUSIC_MARKER: u8 = 0xf4;
-    pub const SCROLL_Y: u8 = 0xf9;
-    pub const SLEEP_TICKS: u8 = 0xff;
+    use super::VarId;
+
+    /// Seeded at boot, used by scripts as the source for random numbers
+    pub const RANDOM_SEED: VarId = VarId(0x3c);
+    /// Describes the host platform to the scripts, set at boot from `GameVersion::platform_flags`
+    pub const PLATFORM: VarId = VarId(0x54);
+    /// Index of the screen currently being shown within a part
+    pub const SCREEN_NUM: VarId = VarId(0x67);
+    /// Last character typed, read by the copy protection and code entry screens
+    pub const LAST_KEYCHAR: VarId = VarId(0xda);
+    /// -1 when up is held, 1 when down is held, 0 otherwise
+    pub const HERO_POS_UP_DOWN: VarId = VarId(0xe5);
+    /// Written by the music player when a marker is reached, scripts poll it to sync cutscenes
+    pub const MUSIC_MARKER: VarId = VarId(0xf4);
+    /// Vertical offset applied to `CopyVideoPage` for scrolling scenes
+    pub const SCROLL_Y: VarId = VarId(0xf9);
+    /// 1 when the action button is held
+    pub const HERO_ACTION: VarId = VarId(0xfa);
+    /// -1 for jump (up), 1 for crouch (down), 0 otherwise
+    pub const HERO_POS_JUMP_DOWN: VarId = VarId(0xfb);
+    /// -1 when left is held, 1 when right is held, 0 otherwise
+    pub const HERO_POS_LEFT_RIGHT: VarId = VarId(0xfc);
+    /// Held directions as a bitmask: right 1, left 2, down 4, up 8
+    pub const HERO_POS_MASK: VarId = VarId(0xfd);
+    /// `HERO_POS_MASK` with 0x80 set when the action button is held
+    pub const HERO_ACTION_POS_MASK: VarId = VarId(0xfe);
+    /// Number of 20ms ticks to wait after each blit
+    pub const SLEEP_TICKS: VarId = VarId(0xff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_frame(vm: &mut Vm, mem: &[u8]) -> FrameResult {
+        vm.execute_frame(mem, InputState::default()).unwrap()
+    }
+
+    #[test]
+    fn set_variable_changes_branch() {
+        let mem = [
+            0x0a, 0x00, 0x10, 0x05, 0x00, 0x0a, // jump to 0x0a if var 0x10 == 5
+            0x00, 0x11, 0x00, 0x01, // var 0x11 = 1
+            0x00, 0x12, 0x00, 0x01, // var 0x12 = 1
+            0x11, // kill thread
+        ];
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.get_variable(VarId(0x11)), 1);
+        assert_eq!(vm.get_variable(VarId(0x12)), 1);
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        vm.set_variable(VarId(0x10), 5);
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.get_variable(VarId(0x10)), 5);
+        assert_eq!(vm.get_variable(VarId(0x11)), 0);
+        assert_eq!(vm.get_variable(VarId(0x12)), 1);
+    }
+
+    #[test]
+    fn thread_pc_reports_where_threads_resume() {
+        let mem = [
+            0x08, 0x01, 0x00, 0x05, // setvec thread 1 to 0x05
+            0x06, // pause
+            0x11, // kill thread
+        ];
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.thread_pc(ThreadId(0)), Some(Pc(0x05)));
+        assert_eq!(vm.thread_pc(ThreadId(1)), None);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.thread_pc(ThreadId(0)), None);
+        assert_eq!(vm.thread_pc(ThreadId(1)), None);
+        assert_eq!(vm.thread_pc(ThreadId(64)), None);
+    }
+
+    #[test]
+    fn jnz_counts_down_to_zero() {
+        let mem = [
+            0x00, 0x10, 0x00, 0x03, // var 0x10 = 3
+            0x03, 0x11, 0x00, 0x01, // var 0x11 += 1
+            0x09, 0x10, 0x00, 0x04, // var 0x10 -= 1, jump to 0x04 if nonzero
+            0x11, // kill thread
+        ];
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.get_variable(VarId(0x10)), 0);
+        assert_eq!(vm.get_variable(VarId(0x11)), 3);
+    }
+
+    #[test]
+    fn jnz_wraps_and_always_writes_back() {
+        let mem = [
+            0x09, 0x10, 0x00, 0x09, // var 0x10 -= 1, jump to 0x09 if nonzero
+            0x00, 0x11, 0x00, 0x01, // var 0x11 = 1
+            0x11, // kill thread
+            0x00, 0x12, 0x00, 0x01, // var 0x12 = 1
+            0x11, // kill thread
+        ];
+
+        // Matches the original, which decrements a 16-bit variable and branches on any
+        // nonzero result, so a counter starting at 0 wraps to -1 and keeps looping.
+        let cases = [
+            (1, 0, false),
+            (0, -1, true),
+            (-1, -2, true),
+            (i16::MIN, i16::MAX, true),
+        ];
+
+        for (start, end, branched) in cases {
+            let mut vm = Vm::new(GameVersion::Dos, false);
+            vm.set_variable(VarId(0x10), start);
+            run_frame(&mut vm, &mem);
+            assert_eq!(vm.get_variable(VarId(0x10)), end, "start {}", start);
+            assert_eq!(
+                vm.get_variable(VarId(0x11)) == 0,
+                branched,
+                "start {}",
+                start
+            );
+            assert_eq!(
+                vm.get_variable(VarId(0x12)) == 1,
+                branched,
+                "start {}",
+                start
+            );
+        }
+    }
+
+    #[test]
+    fn shifts_of_sixteen_or_more_clear_the_variable() {
+        let cases: [(u8, u16, i16); 8] = [
+            (0x16, 0, 0x4321),
+            (0x16, 4, 0x3210),
+            (0x16, 16, 0),
+            (0x16, 31, 0),
+            (0x17, 0, 0x4321),
+            (0x17, 4, 0x0432),
+            (0x17, 16, 0),
+            (0x17, 0xffff, 0),
+        ];
+
+        for (op, shift, expected) in cases {
+            let [hi, lo] = shift.to_be_bytes();
+            let mem = [op, 0x10, hi, lo, 0x11];
+
+            let mut vm = Vm::new(GameVersion::Dos, false);
+            vm.set_variable(VarId(0x10), 0x4321);
+            run_frame(&mut vm, &mem);
+            assert_eq!(
+                vm.get_variable(VarId(0x10)),
+                expected,
+                "op {:#x} shift {}",
+                op,
+                shift
+            );
+        }
+
+        // shifts and masks treat the variable as unsigned
+        let mem = [
+            0x17, 0x10, 0x00, 0x0f, // var 0x10 >>= 15
+            0x14, 0x11, 0x80, 0x01, // var 0x11 &= 0x8001
+            0x15, 0x12, 0x80, 0x00, // var 0x12 |= 0x8000
+            0x11, // kill thread
+        ];
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        vm.set_variable(VarId(0x10), -1);
+        vm.set_variable(VarId(0x11), -1);
+        vm.set_variable(VarId(0x12), 1);
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.get_variable(VarId(0x10)), 1);
+        assert_eq!(vm.get_variable(VarId(0x11)), 0x8001u16 as i16);
+        assert_eq!(vm.get_variable(VarId(0x12)), 0x8001u16 as i16);
+    }
+
+    #[test]
+    fn action_hold_counts_frames_not_resumes() {
+        let held = InputState {
+            action: true,
+            ..Default::default()
+        };
+        let mut vm = Vm::new(GameVersion::Dos, false);
+
+        // yield for a resource load each frame, then pause
+        let mem = [0x19, 0x00, 0x01, 0x06, 0x07, 0x00, 0x00];
+        for frame in 1..=3 {
+            let res = vm.execute_frame(&mem, held).unwrap();
+            assert!(matches!(res, FrameResult::Yield(Yield::ReqResource(1))));
+            let res = vm.execute_frame(&mem, held).unwrap();
+            assert!(matches!(res, FrameResult::Complete));
+            assert_eq!(vm.action_hold_frames(), frame);
+        }
+
+        vm.execute_frame(&mem, InputState::default()).unwrap();
+        assert_eq!(vm.action_hold_frames(), 0);
+    }
+
+    fn decode(bytes: &[u8]) -> Result<String, Error> {
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        let mut pc = ProgramCounter {
+            mem: bytes,
+            address: 0,
+        };
+        let instruction = vm.decode(&mut pc)?;
+        assert_eq!(pc.address, bytes.len(), "operands of {:02x?}", bytes);
+
+        Ok(format!("{:?}", instruction))
+    }
+
+    #[test]
+    fn decode_every_opcode() {
+        let cases: &[(&[u8], &str)] = &[
+            (&[0x00, 0x10, 0xff, 0xfe], "MovConst(16, -2)"),
+            (&[0x01, 0x10, 0x11], "Mov(16, 17)"),
+            (&[0x02, 0x10, 0x11], "Add(16, 17)"),
+            (&[0x03, 0x10, 0x80, 0x00], "AddConst(16, -32768)"),
+            (&[0x04, 0x12, 0x34], "Call(4660)"),
+            (&[0x05], "Ret"),
+            (&[0x06], "TPause"),
+            (&[0x07, 0x12, 0x34], "Jmp(4660)"),
+            (&[0x08, 0x3f, 0x12, 0x34], "SetVec(63, 4660)"),
+            (&[0x09, 0x10, 0x12, 0x34], "Jnz(16, 4660)"),
+            (
+                &[0x0a, 0x80, 0x10, 0x11, 0x12, 0x34],
+                "CondJmp(Eq, 16, Variable(17), 4660)",
+            ),
+            (
+                &[0x0a, 0xc1, 0x10, 0x11, 0x12, 0x34],
+                "CondJmp(NotEq, 16, Variable(17), 4660)",
+            ),
+            (
+                &[0x0a, 0x42, 0x10, 0xff, 0xfe, 0x12, 0x34],
+                "CondJmp(Greater, 16, Const(-2), 4660)",
+            ),
+            (
+                &[0x0a, 0x03, 0x10, 0xff, 0x12, 0x34],
+                "CondJmp(GreaterEq, 16, Const(255), 4660)",
+            ),
+            (
+                &[0x0a, 0x04, 0x10, 0x01, 0x12, 0x34],
+                "CondJmp(Less, 16, Const(1), 4660)",
+            ),
+            (
+                &[0x0a, 0x85, 0x10, 0x11, 0x12, 0x34],
+                "CondJmp(LessEq, 16, Variable(17), 4660)",
+            ),
+            (&[0x0b, 0x12, 0x34], "SetPalette(4660)"),
+            (&[0x0c, 0x01, 0x02, 0x03], "TReset(1, 2, 3)"),
+            (&[0x0d, 0xfe], "SelectVideoPage(254)"),
+            (&[0x0e, 0x01, 0x02], "FillVideoPage(1, 2)"),
+            (&[0x0f, 0x01, 0x02], "CopyVideoPage(1, 2)"),
+            (&[0x10, 0xff], "Blit(255)"),
+            (&[0x11], "TKill"),
+            (
+                &[0x12, 0x00, 0x01, 0x02, 0x03, 0x04],
+                "DrawString(1, 2, 3, 4)",
+            ),
+            (&[0x13, 0x10, 0x11], "Sub(16, 17)"),
+            (&[0x14, 0x10, 0x80, 0x01], "And(16, 32769)"),
+            (&[0x15, 0x10, 0x80, 0x01], "Or(16, 32769)"),
+            (&[0x16, 0x10, 0x00, 0x04], "Shl(16, 4)"),
+            (&[0x17, 0x10, 0x00, 0x04], "Shr(16, 4)"),
+            (
+                &[0x18, 0x00, 0x11, 0x27, 0x3f, 0x01],
+                "PlaySound(17, 39, 63, 1)",
+            ),
+            (&[0x19, 0x3e, 0x81], "LoadRes(16001)"),
+            (
+                &[0x1a, 0x00, 0x10, 0x1d, 0x4c, 0x02],
+                "PlayMusic(16, 7500, 2)",
+            ),
+            (
+                &[0x40, 0x12, 0x34, 0xff, 0xfe, 0x00, 0x10],
+                "Draw(PolygonResource { buffer_offset: 9320, source: Cinematic }, \
+                 Const(-2), Const(16), Const(64))",
+            ),
+            (
+                &[0x55, 0x00, 0x10, 0x11, 0x12, 0x13],
+                "Draw(PolygonResource { buffer_offset: 32, source: Cinematic }, \
+                 Variable(17), Variable(18), Variable(19))",
+            ),
+            (
+                &[0x6a, 0x00, 0x10, 0xff, 0xc8, 0x20],
+                "Draw(PolygonResource { buffer_offset: 32, source: Cinematic }, \
+                 Const(255), Const(200), Const(32))",
+            ),
+            (
+                &[0x7f, 0x00, 0x10, 0x10, 0x20],
+                "Draw(PolygonResource { buffer_offset: 32, source: AltVideo }, \
+                 Const(272), Const(32), Const(64))",
+            ),
+            (
+                &[0x80, 0x10, 0x20, 0x30],
+                "Draw(PolygonResource { buffer_offset: 32, source: Cinematic }, \
+                 Const(32), Const(48), Const(64))",
+            ),
+            (
+                &[0xff, 0xff, 0x10, 0xd0],
+                "Draw(PolygonResource { buffer_offset: 65534, source: Cinematic }, \
+                 Const(25), Const(199), Const(64))",
+            ),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(decode(bytes).unwrap(), *expected, "{:02x?}", bytes);
+        }
+
+        for op in 0x1b..0x40 {
+            assert!(matches!(decode(&[op]), Err(Error::InvalidOpcode(o)) if o == op));
+        }
+        assert!(matches!(
+            decode(&[0x0a, 0x06, 0x10, 0x01, 0x12, 0x34]),
+            Err(Error::InvalidJmpCondition(0x06))
+        ));
+        assert!(matches!(
+            decode(&[0x00, 0x10, 0xff]),
+            Err(Error::ProgramCounterOutOfRange(3))
+        ));
+    }
+
+    #[test]
+    fn reset_matches_new() {
+        let mem = [
+            0x00, 0x10, 0x00, 0x05, // var 0x10 = 5
+            0x04, 0x00, 0x0e, // call 0x0e
+            0x0e, 0x01, 0x02, // fill page 1 with color 2
+            0x06, // pause
+            0x07, 0x00, 0x00, // jmp 0x00
+            0x08, 0x01, 0x00, 0x0a, // set thread 1 to 0x0a
+            0x05, // ret
+        ];
+
+        let fresh = Vm::new(GameVersion::Dos, true);
+        let mut vm = Vm::new(GameVersion::Dos, true);
+        for _ in 0..3 {
+            run_frame(&mut vm, &mem);
+        }
+        assert_ne!(format!("{:?}", vm), format!("{:?}", fresh));
+
+        vm.reset();
+        assert_eq!(format!("{:?}", vm), format!("{:?}", fresh));
+    }
+
+    #[test]
+    fn blit_duration_scales_with_tick_duration() {
+        let mem = [0x10, 0xff];
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        vm.set_variable(vars::SLEEP_TICKS, 3);
+        match run_frame(&mut vm, &mem) {
+            FrameResult::Yield(Yield::Blit(ms)) => assert_eq!(ms, 3 * DEFAULT_TICK_MS),
+            res => panic!("expected blit: {:?}", res),
+        }
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        vm.set_tick_duration(50);
+        vm.set_variable(vars::SLEEP_TICKS, 3);
+        match run_frame(&mut vm, &mem) {
+            FrameResult::Yield(Yield::Blit(ms)) => assert_eq!(ms, 150),
+            res => panic!("expected blit: {:?}", res),
+        }
+    }
+
+    /// Runs `mem` as thread 0 of a fresh VM, expecting it to fail and be killed.
+    fn frame_error(mem: &[u8]) -> Error {
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        let err = match vm.execute_frame(mem, InputState::default()) {
+            Err(err) => err,
+            res => panic!("expected error: {:?}", res),
+        };
+        assert_eq!(vm.thread_data[0].pc, 0xffff);
+        err
+    }
+
+    #[test]
+    fn malformed_bytecode_returns_error() {
+        assert!(matches!(frame_error(&[0x1b]), Error::InvalidOpcode(0x1b)));
+        assert!(matches!(
+            frame_error(&[0x00, 0x10]),
+            Error::ProgramCounterOutOfRange(2)
+        ));
+        assert!(matches!(frame_error(&[0x05]), Error::StackUnderflow));
+        assert!(matches!(
+            frame_error(&[0x0a, 0x07, 0x10, 0x00, 0x00, 0x00]),
+            Error::InvalidJmpCondition(7)
+        ));
+        assert!(matches!(
+            frame_error(&[0x08, 0x40, 0x00, 0x00]),
+            Error::InvalidThreadId(64)
+        ));
+        assert!(matches!(
+            frame_error(&[0x0c, 0x03, 0x01, 0x02]),
+            Error::InvalidThreadRange(3, 1)
+        ));
+        assert!(matches!(
+            frame_error(&[0x0c, 0x40, 0x40, 0x02]),
+            Error::InvalidThreadRange(64, 0)
+        ));
+    }
+
+    #[test]
+    fn threads_after_an_error_still_run() {
+        let (mut vm, mem) = thread_reset_vm(&[0x1b]);
+
+        assert!(matches!(
+            vm.execute_frame(&mem, InputState::default()),
+            Err(Error::InvalidOpcode(0x1b))
+        ));
+        assert!(matches!(
+            vm.execute_frame(&mem, InputState::default()),
+            Ok(FrameResult::Complete)
+        ));
+        for var in 0x11..=0x13 {
+            assert_eq!(vm.get_var(var), 1);
+        }
+
+        // the next call starts a new frame
+        run_frame(&mut vm, &mem);
+        for var in 0x11..=0x13 {
+            assert_eq!(vm.get_var(var), 2);
+        }
+    }
+
+    /// Thread 0 runs `control` then kills itself, threads 1 to 3 each add 1 to variables
+    /// 0x11 to 0x13 every frame.
+    fn thread_reset_vm(control: &[u8]) -> (Vm, Vec<u8>) {
+        let mut mem = control.to_vec();
+        mem.push(0x11); // kill thread
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        for thread in 1..=3 {
+            let pc = mem.len() as u16;
+            vm.thread_data[thread as usize].pc = pc;
+            mem.extend([0x03, 0x10 + thread, 0x00, 0x01]); // var += 1
+            mem.extend([0x06, 0x07]); // pause, jmp pc
+            mem.extend(pc.to_be_bytes());
+        }
+
+        (vm, mem)
+    }
+
+    fn counters(vm: &Vm) -> [i16; 3] {
+        [
+            vm.get_variable(VarId(0x11)),
+            vm.get_variable(VarId(0x12)),
+            vm.get_variable(VarId(0x13)),
+        ]
+    }
+
+    #[test]
+    fn thread_reset_pause_applies_next_frame() {
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x01, 0x02, 0x01]);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [1, 1, 1]);
+
+        run_frame(&mut vm, &mem);
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [1, 1, 3]);
+        assert_eq!(vm.last_thread_activity()[1], ThreadActivity::Paused);
+        assert_ne!(vm.thread_data[1].pc, 0xffff);
+    }
+
+    #[test]
+    fn thread_reset_unpause_resumes_next_frame() {
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x02, 0x03, 0x00]);
+        for thread in 1..=3 {
+            vm.thread_data[thread].paused = true;
+            vm.thread_data[thread].requested_pause = true;
+        }
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [0, 0, 0]);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [0, 1, 1]);
+    }
+
+    #[test]
+    fn thread_reset_deactivate_applies_next_frame() {
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x02, 0x03, 0x02]);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [1, 1, 1]);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [2, 1, 1]);
+        assert_eq!(vm.thread_data[2].pc, 0xffff);
+        assert_eq!(vm.thread_data[3].pc, 0xffff);
+        assert_eq!(vm.thread_data[2].requested_pc, 0xffff);
+    }
+
+    #[test]
+    fn thread_reset_of_own_thread_finishes_the_frame() {
+        // pause threads 0 to 3 then carry on to var 0x20 = 1
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x00, 0x03, 0x01, 0x00, 0x20, 0x00, 0x01]);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.get_variable(VarId(0x20)), 1);
+        assert_eq!(counters(&vm), [1, 1, 1]);
+
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [1, 1, 1]);
+    }
+
+    #[test]
+    fn thread_reset_last_request_wins() {
+        // pause then unpause thread 1, deactivate then revive thread 2 at its own pc
+        let (mut vm, mut mem) = thread_reset_vm(&[
+            0x0c, 0x01, 0x01, 0x01, 0x0c, 0x01, 0x01, 0x00, 0x0c, 0x02, 0x02, 0x02, 0x08, 0x02,
+            0x00, 0x00,
+        ]);
+        let pc = vm.thread_data[2].pc.to_be_bytes();
+        mem[14..16].copy_from_slice(&pc);
+
+        run_frame(&mut vm, &mem);
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [2, 2, 2]);
+    }
+
+    #[test]
+    fn thread_reset_deactivate_keeps_pause_state() {
+        // pause and deactivate thread 1, then revive it
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x01, 0x01, 0x01, 0x0c, 0x01, 0x01, 0x02]);
+
+        run_frame(&mut vm, &mem);
+        run_frame(&mut vm, &mem);
+        assert_eq!(vm.thread_data[1].pc, 0xffff);
+        assert!(vm.thread_data[1].paused);
+
+        vm.thread_data[1].requested_pc = 0x0009;
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [1, 3, 3]);
+        assert_eq!(vm.thread_data[1].pc, 0x0009);
+    }
+
+    #[test]
+    fn thread_reset_masks_last_thread() {
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x01, 0x43, 0x02]);
+
+        run_frame(&mut vm, &mem);
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [1, 1, 1]);
+        assert!((1..=3).all(|thread| vm.thread_data[thread].pc == 0xffff));
+    }
+
+    #[test]
+    fn thread_reset_ignores_unknown_modes() {
+        let (mut vm, mem) = thread_reset_vm(&[0x0c, 0x01, 0x03, 0x03, 0x0c, 0x01, 0x03, 0xff]);
+
+        run_frame(&mut vm, &mem);
+        run_frame(&mut vm, &mem);
+        assert_eq!(counters(&vm), [2, 2, 2]);
+    }
+
+    #[test]
+    fn step_limit_stops_infinite_loop() {
+        let mem = [0x07, 0x00, 0x00]; // jmp 0x00
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        vm.set_step_limit(Some(1000));
+        match vm.execute_frame(&mem, InputState::default()) {
+            Err(Error::StepLimitExceeded) => (),
+            res => panic!("expected step limit: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn draws_without_a_blit_trip_the_command_cap() {
+        let mem = [0x80, 0x00, 0x10, 0x10, 0x07, 0x00, 0x00]; // draw, jmp 0x00
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        vm.set_step_limit(Some(1000));
+        vm.set_command_cap(Some(100));
+        assert!(vm.execute_frame(&mem, InputState::default()).is_err());
+
+        assert!(vm.command_cap_exceeded());
+        assert_eq!(vm.video_commands().count(), 500);
+        assert!(!vm.command_cap_exceeded());
+
+        vm.init_part();
+        vm.set_command_cap(None);
+        assert!(vm.execute_frame(&mem, InputState::default()).is_err());
+        assert!(!vm.command_cap_exceeded());
+    }
+
+    #[test]
+    fn default_step_limit_stops_infinite_loop() {
+        let mem = [0x07, 0x00, 0x00]; // jmp 0x00
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        assert!(matches!(
+            vm.execute_frame(&mem, InputState::default()),
+            Err(Error::StepLimitExceeded)
+        ));
+        assert_eq!(vm.thread_data[0].pc, 0xffff);
+    }
+
+    #[test]
+    fn input_variables_for_every_combination() {
+        for bits in 0..32 {
+            let input = InputState {
+                up: bits & 1 != 0,
+                down: bits & 2 != 0,
+                left: bits & 4 != 0,
+                right: bits & 8 != 0,
+                action: bits & 16 != 0,
+                ..InputState::default()
+            };
+
+            let up_down = match (input.up, input.down) {
+                (true, _) => -1,
+                (false, true) => 1,
+                (false, false) => 0,
+            };
+            let left_right = match (input.left, input.right) {
+                (true, _) => -1,
+                (false, true) => 1,
+                (false, false) => 0,
+            };
+            let mask = input.right as i16
+                | (input.left as i16) << 1
+                | (input.down as i16) << 2
+                | (input.up as i16) << 3;
+
+            let mut vm = Vm::new(GameVersion::Dos, false);
+            // stale presses from a previous frame must not linger
+            vm.update_input(InputState {
+                up: true,
+                down: true,
+                left: true,
+                right: true,
+                action: true,
+                ..InputState::default()
+            });
+            vm.update_input(input);
+
+            assert_eq!(
+                vm.get_variable(vars::HERO_POS_UP_DOWN),
+                up_down,
+                "{:?}",
+                input
+            );
+            assert_eq!(vm.get_variable(vars::HERO_POS_JUMP_DOWN), up_down);
+            assert_eq!(vm.get_variable(vars::HERO_POS_LEFT_RIGHT), left_right);
+            assert_eq!(vm.get_variable(vars::HERO_POS_MASK), mask);
+            assert_eq!(vm.get_variable(vars::HERO_ACTION), input.action as i16);
+            assert_eq!(
+                vm.get_variable(vars::HERO_ACTION_POS_MASK),
+                mask | (input.action as i16) << 7
+            );
+        }
+    }
+
+    #[test]
+    fn held_direction_does_not_accelerate() {
+        let mem = [
+            0x02, 0x20, 0xfc, // var 0x20 += var 0xfc
+            0x06, // pause
+            0x07, 0x00, 0x00, // jmp 0x00
+        ];
+        let right = InputState {
+            right: true,
+            ..InputState::default()
+        };
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        let mut progression = Vec::new();
+        for frame in 0..6 {
+            let input = if frame < 4 {
+                right
+            } else {
+                InputState::default()
+            };
+            vm.execute_frame(&mem, input).unwrap();
+            progression.push((
+                vm.get_variable(vars::HERO_POS_LEFT_RIGHT),
+                vm.get_variable(vars::HERO_POS_MASK),
+                vm.get_variable(VarId(0x20)),
+            ));
+        }
+
+        // the engine repeats the same values, only the script's own count grows
+        assert_eq!(
+            progression,
+            [
+                (1, 1, 1),
+                (1, 1, 2),
+                (1, 1, 3),
+                (1, 1, 4),
+                (0, 0, 4),
+                (0, 0, 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn platform_variable_is_set_on_boot_and_reset() {
+        assert_eq!(GameVersion::Dos.platform_flags(), 0x81);
+
+        for version in [GameVersion::Dos, GameVersion::FifteenthAnniversary] {
+            let mut vm = Vm::new(version, false);
+            assert_eq!(vm.get_variable(vars::PLATFORM), version.platform_flags());
+
+            // scripts may overwrite it, a restart must hand the scripts the boot value again
+            vm.set_variable(vars::PLATFORM, 0);
+            vm.reset();
+            assert_eq!(vm.get_variable(vars::PLATFORM), version.platform_flags());
+        }
+    }
 }
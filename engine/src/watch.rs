@@ -0,0 +1,108 @@
+//! Reports when chosen script variables change, for working out what a variable means.
+
+use crate::vm::VarId;
+
+/// A watched variable holding a different value at the end of a frame than it did at
+/// the end of the previous one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VariableChange {
+    pub frame: u64,
+    pub variable_id: VarId,
+    pub old: i16,
+    pub new: i16,
+}
+
+impl std::fmt::Display for VariableChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frame {}: var {} {} -> {}",
+            self.frame, self.variable_id, self.old, self.new
+        )
+    }
+}
+
+/// The last seen value of each watched variable. Only values at frame boundaries are
+/// compared, so a variable changed and restored within one frame is not reported.
+#[derive(Debug, Clone, Default)]
+pub struct VariableWatch {
+    watched: Vec<(VarId, i16)>,
+}
+
+impl VariableWatch {
+    /// Watches `variable_ids`, taking their starting values from `read`.
+    pub fn new(variable_ids: &[VarId], read: impl Fn(VarId) -> i16) -> Self {
+        let mut watched: Vec<_> = variable_ids.iter().map(|&id| (id, read(id))).collect();
+        watched.sort_unstable();
+        watched.dedup_by_key(|(id, _)| *id);
+
+        Self { watched }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watched.is_empty()
+    }
+
+    /// Compares each watched variable against `read`, returning the ones that changed in
+    /// variable id order and remembering their new values.
+    pub fn diff(&mut self, frame: u64, read: impl Fn(VarId) -> i16) -> Vec<VariableChange> {
+        let mut changes = Vec::new();
+
+        for (variable_id, value) in self.watched.iter_mut() {
+            let new = read(*variable_id);
+            if new != *value {
+                changes.push(VariableChange {
+                    frame,
+                    variable_id: *variable_id,
+                    old: *value,
+                    new,
+                });
+                *value = new;
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputState;
+    use crate::resources::GameVersion;
+    use crate::vm::{FrameResult, Vm};
+
+    #[test]
+    fn mov_const_reports_a_change() {
+        let mem = [
+            0x00, 0x10, 0x00, 0x05, // var 0x10 = 5
+            0x00, 0x11, 0x00, 0x00, // var 0x11 = 0
+            0x06, // pause
+            0x07, 0x00, 0x08, // jmp 0x08
+        ];
+
+        let mut vm = Vm::new(GameVersion::Dos, false);
+        let mut watch = VariableWatch::new(&[VarId(0x11), VarId(0x10), VarId(0x11)], |id| {
+            vm.get_variable(id)
+        });
+
+        for frame in 1..=2 {
+            let res = vm.execute_frame(&mem, InputState::default());
+            assert!(matches!(res, Ok(FrameResult::Complete)));
+
+            let changes = watch.diff(frame, |id| vm.get_variable(id));
+            if frame == 1 {
+                let change = VariableChange {
+                    frame: 1,
+                    variable_id: VarId(0x10),
+                    old: 0,
+                    new: 5,
+                };
+                assert_eq!(changes, [change]);
+                assert_eq!(change.to_string(), "frame 1: var 0x10 0 -> 5");
+            } else {
+                assert!(changes.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Packs the game data directory into a single blob that `resources.rs` embeds.
+//!
+//! The directory defaults to `games/ootw_2` at the repository root and can be
+//! changed with the `AW_DATA_DIR` environment variable. The pack is a little
+//! endian `u32` file count, then for each file a `u16` name length, the name
+//! relative to the data directory using `/` separators, and `u32` offset and
+//! length of its data from the start of the pack. File data follows the index.
+
+use std::path::{Path, PathBuf};
+
+const DEFAULT_DATA_DIR: &str = "../games/ootw_2";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=AW_DATA_DIR");
+
+    if std::env::var_os("CARGO_FEATURE_PER_FILE_DATA").is_some()
+        || std::env::var_os("CARGO_FEATURE_FETCH_DATA").is_some()
+    {
+        return;
+    }
+
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let data_dir = std::env::var_os("AW_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir.join(DEFAULT_DATA_DIR));
+    println!("cargo:rerun-if-changed={}", data_dir.display());
+
+    let mut files = Vec::new();
+    collect_files(&data_dir, &data_dir, &mut files);
+    files.sort();
+
+    let index_len: usize = 4 + files
+        .iter()
+        .map(|(name, _)| 2 + name.len() + 4 + 4)
+        .sum::<usize>();
+
+    let mut index = Vec::with_capacity(index_len);
+    let mut data = Vec::new();
+    index.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    for (name, path) in files {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let bytes = std::fs::read(&path)
+            .unwrap_or_else(|err| panic!("unable to read {}: {}", path.display(), err));
+
+        index.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        index.extend_from_slice(name.as_bytes());
+        index.extend_from_slice(&((index_len + data.len()) as u32).to_le_bytes());
+        index.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        data.extend_from_slice(&bytes);
+    }
+    index.extend_from_slice(&data);
+
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("game_data.pack"), index).expect("unable to write game data pack");
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) {
+    let entries = std::fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("unable to read data dir {}: {}", dir.display(), err));
+
+    for entry in entries {
+        let path = entry.expect("unable to read data dir entry").path();
+        if path.is_dir() {
+            collect_files(root, &path, files);
+        } else {
+            let name = path
+                .strip_prefix(root)
+                .unwrap()
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((name, path));
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Loads the DOS data files over HTTP when the game starts, for builds that serve the
+//! data alongside the page instead of embedding it.
+
+use js_sys::{Promise, Uint8Array};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Element, Response};
+
+use engine::error::Error;
+use engine::retry::{self, RetryPolicy};
+use engine::Io;
+
+use std::io::{Cursor, ErrorKind};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Where the data files are fetched from, relative to the page.
+const DATA_URL: &str = "data/";
+const DATA_FILES: [&str; 14] = [
+    "MEMLIST.BIN",
+    "BANK01",
+    "BANK02",
+    "BANK03",
+    "BANK04",
+    "BANK05",
+    "BANK06",
+    "BANK07",
+    "BANK08",
+    "BANK09",
+    "BANK0A",
+    "BANK0B",
+    "BANK0C",
+    "BANK0D",
+];
+
+/// The data files, fetched before the executor is created since `Io` loads can't wait
+/// on the network.
+pub struct FetchedResources {
+    files: Vec<(&'static str, Rc<[u8]>)>,
+}
+
+impl FetchedResources {
+    /// Fetches every data file, retrying transient failures as `policy` allows and
+    /// showing progress on `overlay`.
+    pub async fn fetch(policy: RetryPolicy, overlay: &LoadingOverlay) -> Result<Self, Error> {
+        let mut files = Vec::with_capacity(DATA_FILES.len());
+        for name in DATA_FILES {
+            overlay.show(&format!("Loading {}…", name));
+            let data = fetch_with_retry(name, policy, overlay).await?;
+            files.push((name, data.into()));
+        }
+
+        Ok(Self { files })
+    }
+}
+
+impl Io for FetchedResources {
+    type Reader = Cursor<Rc<[u8]>>;
+    fn load<S: AsRef<str>>(&self, file: S) -> Result<Self::Reader, Error> {
+        let file = file.as_ref();
+        match self
+            .files
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(file))
+        {
+            Some((_, data)) => Ok(Cursor::new(data.clone())),
+            None => Err(Error::Io(std::io::Error::new(ErrorKind::NotFound, file))),
+        }
+    }
+}
+
+/// Mirrors `retry::RetryIo`, but waits out each backoff on a browser timer instead of
+/// blocking.
+async fn fetch_with_retry(
+    name: &str,
+    policy: RetryPolicy,
+    overlay: &LoadingOverlay,
+) -> Result<Vec<u8>, Error> {
+    let attempts = policy.attempts.max(1);
+    let mut retry = 0;
+
+    loop {
+        match fetch_file(name).await {
+            Err(err) if retry::is_transient(err.kind()) => {
+                retry += 1;
+                if retry >= attempts {
+                    return Err(Error::RetriesExhausted(name.to_string(), attempts, err));
+                }
+
+                let delay = policy.delay(retry);
+                log::warn!("retrying {} in {}ms: {}", name, delay.as_millis(), err);
+                overlay.show(&format!(
+                    "Retrying {} ({} of {})…",
+                    name,
+                    retry,
+                    attempts - 1
+                ));
+                sleep(delay).await;
+            }
+            res => return res.map_err(Error::Io),
+        }
+    }
+}
+
+async fn fetch_file(name: &str) -> Result<Vec<u8>, std::io::Error> {
+    let url = format!("{}{}", DATA_URL, name);
+    // fetch only rejects when the request never got a response
+    let response = JsFuture::from(window().unwrap().fetch_with_str(&url))
+        .await
+        .map_err(|err| js_error(ErrorKind::ConnectionAborted, err))?;
+    let response: Response = response
+        .dyn_into()
+        .map_err(|err| js_error(ErrorKind::InvalidData, err))?;
+
+    if !response.ok() {
+        // server errors and rate limiting may clear up, other statuses will not
+        let kind = match response.status() {
+            404 | 410 => ErrorKind::NotFound,
+            408 => ErrorKind::TimedOut,
+            429 | 500..=599 => ErrorKind::Interrupted,
+            _ => ErrorKind::Other,
+        };
+        let message = format!("{} {}", response.status(), response.status_text());
+        return Err(std::io::Error::new(kind, message));
+    }
+
+    let body = response
+        .array_buffer()
+        .map_err(|err| js_error(ErrorKind::InvalidData, err))?;
+    let body = JsFuture::from(body)
+        .await
+        .map_err(|err| js_error(ErrorKind::UnexpectedEof, err))?;
+
+    Ok(Uint8Array::new(&body).to_vec())
+}
+
+fn js_error(kind: ErrorKind, err: JsValue) -> std::io::Error {
+    let message = err.as_string().unwrap_or_else(|| format!("{:?}", err));
+    std::io::Error::new(kind, message)
+}
+
+async fn sleep(duration: Duration) {
+    let promise = Promise::new(&mut |resolve, _| {
+        let _ = window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                &resolve,
+                duration.as_millis() as i32,
+            );
+    });
+    let _ = JsFuture::from(promise).await;
+}
+
+/// A line of text over the page for loading progress, retries and load failures.
+pub struct LoadingOverlay {
+    element: Element,
+}
+
+impl LoadingOverlay {
+    pub fn new() -> Self {
+        let document = window().unwrap().document().unwrap();
+        let element = document.create_element("div").unwrap();
+        let _ = element.set_attribute("style", "position: fixed; left: 0; right: 0; top: 45%; text-align: center; color: #fff; font: 16px monospace;");
+        let _ = document
+            .body()
+            .unwrap()
+            .append_with_node_1(element.as_ref());
+
+        Self { element }
+    }
+
+    pub fn show(&self, text: &str) {
+        self.element.set_text_content(Some(text));
+    }
+
+    pub fn remove(self) {
+        self.element.remove();
+    }
+}
@@ -1,9 +1,4 @@
 use byteorder::{LittleEndian, WriteBytesExt};
-use lyon::{
-    lyon_tessellation::{BuffersBuilder, FillOptions, FillVertex, VertexBuffers},
-    path::traits::PathBuilder,
-    tessellation::FillTessellator,
-};
 use wasm_bindgen::JsCast;
 use web_sys::{window, HtmlCanvasElement, WebGlRenderingContext as GL};
 
@@ -11,7 +6,9 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use engine::video::{BlendMode, Page, Polygon};
+use engine::font::{self, FontBitmap};
+use engine::tessellate::{Geometry, Tessellator};
+use engine::video::{self, BlendMode, Page, PaletteMode, Polygon};
 use engine::Gfx;
 
 use crate::gl::*;
@@ -22,17 +19,20 @@ pub struct WebGlGfx {
     palette_tex: GlTexture,
     pages: HashMap<Page, GlFrameBuffer>,
     current_page: Page,
+    output_page: Page,
     frame_program: GlProgram,
     copy_program: RefCell<GlProgram>,
     page_program: GlProgram,
     font_program: GlProgram,
     screen_quad: GlModel<QuadVertex>,
-    tessellate_buffer: VertexBuffers<PolyVertex, u16>,
-    tessellator: FillTessellator,
+    tessellate_buffer: Geometry<PolyVertex>,
+    tessellator: Tessellator,
     work_texture_self: GlFrameBuffer,
     work_texture_zero: GlFrameBuffer,
+    capture_buffer: GlFrameBuffer,
     font_texture: GlTexture,
     text_buffer: Vec<TextVertex>,
+    color_transform: [f32; 9],
 }
 
 impl WebGlGfx {
@@ -88,18 +88,20 @@ impl WebGlGfx {
 
         let screen_quad = GlModel::new(context.clone(), SCREEN_QUAD);
 
-        let tessellate_buffer: VertexBuffers<PolyVertex, u16> = VertexBuffers::new();
+        let tessellate_buffer: Geometry<PolyVertex> = Geometry::new();
 
         let work_texture_self = GlFrameBuffer::new(context.clone(), width, height);
         let work_texture_zero = GlFrameBuffer::new(context.clone(), width, height);
+        let capture_buffer = GlFrameBuffer::new(context.clone(), width, height);
 
-        let font_texture = create_font(context.clone());
+        let font_texture = create_font(context.clone(), &font::FONT);
 
-        Self {
+        let mut gfx = Self {
             context,
             palette_tex,
             pages,
             current_page,
+            output_page: Page::Zero,
             frame_program,
             copy_program,
             page_program,
@@ -108,12 +110,101 @@ impl WebGlGfx {
             tessellate_buffer,
             work_texture_self,
             work_texture_zero,
-            tessellator: FillTessellator::new(),
+            capture_buffer,
+            tessellator: Tessellator::new(),
             font_texture,
             text_buffer: Vec::new(),
+            color_transform: flatten(PaletteMode::Normal.color_transform()),
+        };
+        gfx.warm_up();
+        gfx
+    }
+
+    /// Draws a degenerate triangle with every program into the capture buffer and waits
+    /// for the GPU, so browsers that compile shaders lazily do it now rather than on the
+    /// first frames that use them. Called by `new`.
+    pub fn warm_up(&mut self) {
+        let performance = window().and_then(|window| window.performance());
+        let start = performance.as_ref().map(|p| p.now());
+
+        let mut uniforms = GlUniformCollection::new();
+        uniforms.add("u_page", self.work_texture_zero.texture());
+        uniforms.add("u_palette", &self.palette_tex);
+        uniforms.add("u_color_transform", &self.color_transform);
+        self.capture_buffer.bind();
+        self.frame_program.draw(&self.screen_quad, &uniforms, None);
+
+        let color = 0;
+        let mut uniforms = GlUniformCollection::new();
+        uniforms.add("u_fill", &color);
+        uniforms.add("u_page", self.work_texture_zero.texture());
+        uniforms.add("u_scroll", &color);
+        self.copy_program
+            .borrow_mut()
+            .draw(&self.screen_quad, &uniforms, None);
+
+        let vertex = PolyVertex {
+            position: (0.0, 0.0),
+            color: 0,
+            mask: 0,
+        };
+        let poly_model = GlModel::new(self.context.clone(), [vertex; 3].iter().cloned());
+        let poly_index = GlIndexBuffer::new(self.context.clone(), &[0u16, 1, 2]);
+        let mut uniforms = GlUniformCollection::new();
+        uniforms.add("u_page_self", self.work_texture_self.texture());
+        uniforms.add("u_page_zero", self.work_texture_zero.texture());
+        self.page_program
+            .draw_indexed(&poly_model, &uniforms, Some(&poly_index), None);
+
+        let vertex = TextVertex {
+            position: (0.0, 0.0),
+            uv: (0.0, 0.0),
+        };
+        let text_model = GlModel::new(self.context.clone(), [vertex; 3].iter().cloned());
+        let mut uniforms = GlUniformCollection::new();
+        uniforms.add("u_font_atlas", &self.font_texture);
+        uniforms.add("u_color", &color);
+        self.font_program.draw(&text_model, &uniforms, None);
+        self.capture_buffer.unbind();
+
+        self.context.finish();
+        if let (Some(performance), Some(start)) = (performance, start) {
+            log::debug!("shader warm up took {:.1}ms", performance.now() - start);
+        }
+    }
+
+    pub fn set_palette_mode(&mut self, mode: PaletteMode) {
+        self.color_transform = flatten(mode.color_transform());
+    }
+
+    /// See `video::DEBUG_CLEAR_COLOR`, pages are refilled with it or with color 0.
+    pub fn set_debug_clear(&mut self, enabled: bool) {
+        let clear = if enabled { video::DEBUG_CLEAR_COLOR } else { 0 };
+        for page in self.pages.values() {
+            page.clear(clear);
         }
     }
 
+    /// Renders the last blitted page through the palette into an offscreen framebuffer
+    /// and returns its RGBA pixels, top row first, along with the width and height.
+    pub fn capture_frame(&mut self) -> (Vec<u8>, u32, u32) {
+        let page = self.pages.get(&self.output_page).unwrap();
+        let mut uniforms = GlUniformCollection::new();
+        uniforms.add("u_page", page.texture());
+        uniforms.add("u_palette", &self.palette_tex);
+        uniforms.add("u_color_transform", &self.color_transform);
+
+        self.capture_buffer.bind();
+        self.frame_program.draw(&self.screen_quad, &uniforms, None);
+        self.capture_buffer.unbind();
+
+        (
+            self.capture_buffer.read_pixels(),
+            self.capture_buffer.width(),
+            self.capture_buffer.height(),
+        )
+    }
+
     fn do_copy(&self, src: &GlFrameBuffer, dest: &GlFrameBuffer, scroll: i16) {
         let color = 0xff as i32;
         let scroll = scroll as i32;
@@ -130,55 +221,51 @@ impl WebGlGfx {
     }
 }
 
+fn flatten(columns: [[f32; 3]; 3]) -> [f32; 9] {
+    let mut matrix = [0.0; 9];
+    for (dest, value) in matrix.iter_mut().zip(columns.iter().flatten()) {
+        *dest = *value;
+    }
+    matrix
+}
+
 impl Gfx for WebGlGfx {
     fn blit(&mut self, page: Page) {
+        self.output_page = page;
         let page = self.pages.get(&page).unwrap();
         let mut uniforms = GlUniformCollection::new();
         uniforms.add("u_page", page.texture());
         uniforms.add("u_palette", &self.palette_tex);
+        uniforms.add("u_color_transform", &self.color_transform);
 
         self.frame_program.draw(&self.screen_quad, &uniforms, None);
     }
 
     fn draw_polygon(&mut self, polygon: Polygon) {
-        let fill_options = FillOptions::default();
         let (color, mask) = match polygon.blend {
             BlendMode::Solid(col) => (col & 0xf, 0),
             BlendMode::Mask(mask) => (0, mask),
             BlendMode::Blend => (0xff, 0),
         };
-        let mut points = polygon
-            .points()
-            .map(|(x, y)| lyon::math::point(x as f32, y as f32));
-
-        if let Some(first) = points.next() {
-            let mut buffer_builder =
-                BuffersBuilder::new(&mut self.tessellate_buffer, |vertex: FillVertex| {
-                    PolyVertex {
-                        position: vertex.position().to_tuple(),
-                        color,
-                        mask,
-                    }
-                });
-
-            let mut builder = self.tessellator.builder(&fill_options, &mut buffer_builder);
-
-            builder.begin(first);
-            for point in points {
-                builder.line_to(point);
-            }
-            builder.close();
-
-            let _ = builder.build().unwrap();
-        }
+        self.tessellator
+            .fill(&polygon, &mut self.tessellate_buffer, |position| {
+                PolyVertex {
+                    position,
+                    color,
+                    mask,
+                }
+            });
 
         let page = self.pages.get(&self.current_page).unwrap();
 
         let page_self = self.pages.get(&self.current_page).unwrap();
         let page_zero = self.pages.get(&Page::Zero).unwrap();
 
-        if color >= 0xf || mask != 0 {
+        let snapshot = polygon.blend.background_snapshot();
+        if snapshot.active_page {
             self.do_copy(page_self, &self.work_texture_self, 0);
+        }
+        if snapshot.page_zero {
             self.do_copy(page_zero, &self.work_texture_zero, 0);
         }
 
@@ -201,20 +288,10 @@ impl Gfx for WebGlGfx {
     }
 
     fn fill_page(&mut self, page: Page, color: u8) {
-        let color = color & 0xf;
         let dest_page = self.pages.get(&page).unwrap();
-
-        let color = color as i32;
-        let mut uniforms = GlUniformCollection::new();
-        uniforms.add("u_fill", &color);
-        uniforms.add("u_page", self.work_texture_self.texture());
-
-        dest_page.bind();
-        self.copy_program
-            .borrow_mut()
-            .draw(&self.screen_quad, &uniforms, None);
-        dest_page.unbind();
+        dest_page.clear(color & 0xf);
     }
+
     fn copy_page(&mut self, src: Page, dest: Page, scroll: i16) {
         let dest_page = self.pages.get(&dest).unwrap();
         let src_page = self.pages.get(&src).unwrap();
@@ -236,61 +313,13 @@ impl Gfx for WebGlGfx {
             .sub_image(0, 0, 16, 1, PixelFormat::RGB, pixels.as_slice());
     }
 
-    fn draw_string(&mut self, text: &'static str, color: u8, mut x: i16, mut y: i16) {
+    fn draw_string(&mut self, text: &'static str, color: u8, x: i16, y: i16) {
         self.text_buffer.clear();
 
-        let x_origin = x;
-        for c in text.bytes() {
-            if c == b'\n' {
-                x = x_origin;
-                y += 8;
-                continue;
+        for glyph in font::layout_text(text, x, y) {
+            for (position, uv) in glyph.vertices() {
+                self.text_buffer.push(TextVertex { position, uv });
             }
-
-            let c = c - b' ';
-
-            let x_ind = (c % 10) * 8;
-            let y_ind = (c / 10) * 8;
-
-            let x_ind = x_ind as f32 / 80.0;
-            let y_ind = y_ind as f32 / 80.0;
-
-            let step = 8.0 / 80.0;
-
-            let x_pos = x as f32;
-            let y_pos = y as f32;
-
-            x += 8;
-
-            self.text_buffer.push(TextVertex {
-                position: (x_pos, y_pos),
-                uv: (x_ind, y_ind),
-            });
-
-            self.text_buffer.push(TextVertex {
-                position: (x_pos, y_pos + 8.0),
-                uv: (x_ind, y_ind + step),
-            });
-
-            self.text_buffer.push(TextVertex {
-                position: (x_pos + 8.0, y_pos),
-                uv: (x_ind + step, y_ind),
-            });
-
-            self.text_buffer.push(TextVertex {
-                position: (x_pos + 8.0, y_pos + 8.0),
-                uv: (x_ind + step, y_ind + step),
-            });
-
-            self.text_buffer.push(TextVertex {
-                position: (x_pos, y_pos + 8.0),
-                uv: (x_ind, y_ind + step),
-            });
-
-            self.text_buffer.push(TextVertex {
-                position: (x_pos + 8.0, y_pos),
-                uv: (x_ind + step, y_ind),
-            });
         }
 
         let text_model = GlModel::new(self.context.clone(), self.text_buffer.iter().cloned());
@@ -305,33 +334,18 @@ impl Gfx for WebGlGfx {
         self.font_program.draw(&text_model, &uniforms, None);
         page.unbind();
     }
-}
-
-fn create_font(context: Rc<GlContext>) -> GlTexture {
-    let mut font_data = vec![0u8; 100 * 64];
-    for n in 0..96 {
-        let x_ind = (n % 10) * 8;
-        let y_ind = (n / 10) * 8;
-
-        for y in 0..8 {
-            let mut row = engine::font::FONT[(n * 8) + y];
-            for x in 0..8 {
-                let bit = row & 0x80 != 0;
-                row <<= 1;
-                let color = if bit { 0xff } else { 0x00 };
-
-                let x_off = x_ind + x;
-                let y_off = y_ind + y;
 
-                let index = (y_off * 80) + x_off;
-
-                font_data[index] = color;
-            }
-        }
+    fn set_font(&mut self, font: &FontBitmap) {
+        self.font_texture = create_font(self.context.clone(), font);
     }
+}
+
+fn create_font(context: Rc<GlContext>, font: &FontBitmap) -> GlTexture {
+    let size = font::ATLAS_SIZE as u32;
+    let font_data = font::build_atlas(font);
 
-    let texture = GlTexture::new(context, 80, 80, PixelFormat::Alpha);
-    texture.sub_image(0, 0, 80, 80, PixelFormat::Alpha, font_data.as_slice());
+    let texture = GlTexture::new(context, size, size, PixelFormat::Alpha);
+    texture.sub_image(0, 0, size, size, PixelFormat::Alpha, font_data.as_slice());
     texture
 }
 
@@ -891,6 +891,46 @@ impl GlFrameBuffer {
     pub fn texture(&self) -> &GlTexture {
         &self.texture
     }
+
+    /// Sets every channel of every pixel to `value` with a GPU clear, so the result does
+    /// not depend on any texture or program that happens to be bound.
+    pub fn clear(&self, value: u8) {
+        let value = value as f32 / 255.0;
+        self.bind();
+        self.gl.clear_color(value, value, value, value);
+        self.gl.clear(GL::COLOR_BUFFER_BIT);
+        self.unbind();
+    }
+
+    /// Reads the framebuffer back as RGBA rows from top to bottom. This waits for all
+    /// pending drawing to finish, so it stalls the GPU pipeline.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let row = self.width as usize * 4;
+        let mut pixels = vec![0; row * self.height as usize];
+
+        self.bind();
+        let _ = self.gl.read_pixels_with_opt_u8_array(
+            0,
+            0,
+            self.width as i32,
+            self.height as i32,
+            GL::RGBA,
+            GL::UNSIGNED_BYTE,
+            Some(&mut pixels),
+        );
+        self.unbind();
+
+        // WebGL reads bottom row first
+        pixels.chunks_exact(row).rev().flatten().copied().collect()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 }
 
 impl Drop for GlFrameBuffer {
@@ -12,6 +12,8 @@ static mut INPUT_STATE: InputState = InputState {
     right: false,
     action: false,
     turbo: false,
+    rewind: false,
+    pause: false,
 };
 
 #[allow(dead_code)]
@@ -33,8 +35,16 @@ impl WebInput {
 
         Self { key_down, key_up }
     }
+
+    /// The keys as seen by the listeners, kept apart from them so it can be handed to an
+    /// executor.
+    pub fn handle(&self) -> WebInputHandle {
+        WebInputHandle
+    }
 }
 
+pub struct WebInputHandle;
+
 fn key_down(event: JsValue) {
     let event: KeyboardEvent = event.dyn_into().unwrap();
     let mut state = unsafe { INPUT_STATE };
@@ -44,6 +54,7 @@ fn key_down(event: JsValue) {
         "ArrowLeft" | "KeyA" => state.left = true,
         "ArrowRight" | "KeyD" => state.right = true,
         "Space" | "Enter" => state.action = true,
+        "KeyP" if !event.repeat() => state.pause = !state.pause,
         _ => (),
     }
 
@@ -64,7 +75,7 @@ fn key_up(event: JsValue) {
     unsafe { INPUT_STATE = state };
 }
 
-impl Input for WebInput {
+impl Input for WebInputHandle {
     fn get_input(&self) -> InputState {
         unsafe { INPUT_STATE }
     }
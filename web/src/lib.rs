@@ -1,18 +1,26 @@
 use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
-use web_sys::{window, UrlSearchParams, Window};
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{window, ImageData, Window};
 
-use engine::Executor;
+use engine::input::CompositeInput;
+use engine::{EngineOptions, Executor, Input};
 
+#[cfg(feature = "fetch-data")]
+mod fetch;
 mod gfx;
 mod gl;
 mod input;
+#[cfg(not(feature = "fetch-data"))]
 mod resources;
 mod shaders;
 
 use gfx::WebGlGfx;
 use input::WebInput;
-use resources::EmbeddedResources;
+
+#[cfg(feature = "fetch-data")]
+type GameIo = fetch::FetchedResources;
+#[cfg(not(feature = "fetch-data"))]
+type GameIo = resources::EmbeddedResources;
 
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
@@ -24,71 +32,129 @@ pub fn main() {
     console_error_panic_hook::set_once();
     ConsoleLogger::initialize();
 
+    #[cfg(not(feature = "fetch-data"))]
+    start(resources::EmbeddedResources);
+
+    #[cfg(feature = "fetch-data")]
+    wasm_bindgen_futures::spawn_local(async {
+        let overlay = fetch::LoadingOverlay::new();
+        let policy = engine::retry::RetryPolicy::default();
+        match fetch::FetchedResources::fetch(policy, &overlay).await {
+            Ok(io) => {
+                overlay.remove();
+                start(io);
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                overlay.show(&err.to_string());
+            }
+        }
+    });
+}
+
+fn start(io: GameIo) {
     unsafe {
-        RUNNER = Some(Runner::new());
-        RUNNER.as_ref().unwrap().schedule(0);
+        RUNNER = Some(Runner::new(io));
+        RUNNER.as_ref().unwrap().schedule();
     };
 }
 
+/// Returns the current frame as `ImageData`, for screenshots or for compositing the game
+/// into another canvas. The frame is redrawn offscreen and read back with `readPixels`,
+/// which waits for the GPU to finish all queued drawing, typically a millisecond or more,
+/// so grab frames on demand rather than every frame.
+#[wasm_bindgen(js_name = captureFrame)]
+pub fn capture_frame() -> Option<ImageData> {
+    let runner = unsafe { RUNNER.as_mut()? };
+    let (mut pixels, width, height) = runner.executor.gfx_mut().capture_frame();
+
+    ImageData::new_with_u8_clamped_array_and_sh(Clamped(&mut pixels), width, height).ok()
+}
+
+/// Executor runs allowed in one animation frame, bounding the work done when a run
+/// returns no sleep to keep loading, or when catching up after a slow frame.
+const MAX_RUNS_PER_FRAME: u32 = 16;
+/// How far the game may fall behind before the missed time is dropped instead of
+/// being caught up.
+const MAX_LAG_MS: f64 = 250.0;
+
+/// Drives the executor from `requestAnimationFrame`. Each executor run returns how long
+/// the frame it produced should stay on screen, and those durations are added to a
+/// deadline for the next run, so the game keeps its own timing however the browser
+/// spaces animation frames. An animation frame runs the executor until the deadline
+/// passes its timestamp, which may be zero runs on a high refresh display or several on
+/// a slow one.
+///
+/// Browsers stop animation frames in background tabs. When the tab returns the deadline
+/// is more than `MAX_LAG_MS` behind, and rather than racing through the missed time the
+/// game resumes from where it paused.
 struct Runner {
-    closure: Closure<dyn Fn()>,
-    executor: Executor<EmbeddedResources, WebGlGfx, WebInput>,
+    closure: Closure<dyn Fn(f64)>,
+    executor: Executor<GameIo, WebGlGfx, CompositeInput>,
+    input: WebInput,
     window: Window,
-    time_remainder: f64,
+    next_run: Option<f64>,
 }
 
 impl Runner {
-    fn new() -> Self {
+    fn new(io: GameIo) -> Self {
         let window = window().unwrap();
-        let url_params = window.location().search().unwrap();
-        let params = UrlSearchParams::new_with_str(url_params.as_str());
-        let scale = params
-            .unwrap()
-            .get("scale")
-            .and_then(|s| s.parse::<u32>().ok())
-            .unwrap_or(1);
-
-        let io = EmbeddedResources;
-        let gfx = WebGlGfx::new(320 * scale, 200 * scale);
+        let query = window.location().search().unwrap();
+        let options = EngineOptions::from_query(query.as_str());
+        let scale = options.scale.unwrap_or(1);
+
+        let mut gfx = WebGlGfx::new(320 * scale, 200 * scale);
+        gfx.set_palette_mode(options.palette_mode);
+        gfx.set_debug_clear(options.debug_clear);
         let input = WebInput::new();
 
-        let executor = Executor::new(io, gfx, input, true);
+        let sources = CompositeInput::new().with(input.handle());
+        let executor =
+            Executor::with_options(io, gfx, sources, &options).expect("unable to load game data");
 
         Self {
             executor,
-            closure: Closure::wrap(Box::new(run) as Box<dyn Fn()>),
+            input,
+            closure: Closure::wrap(Box::new(run) as Box<dyn Fn(f64)>),
             window,
-            time_remainder: 0.0,
+            next_run: None,
         }
     }
 
-    fn schedule(&self, sleep_ms: i32) {
+    fn schedule(&self) {
         let _ = self
             .window
-            .set_timeout_with_callback_and_timeout_and_arguments_0(
-                self.closure.as_ref().unchecked_ref(),
-                sleep_ms as i32,
-            );
+            .request_animation_frame(self.closure.as_ref().unchecked_ref());
     }
 
-    fn run(&mut self) {
-        let now = self.window.performance().unwrap().now();
-        let sleep_ms = self.executor.run() as f64;
-        let next = self.window.performance().unwrap().now();
-        let sleep_ms = sleep_ms - (next - now) + self.time_remainder;
-        if sleep_ms > 0.0 {
-            self.time_remainder += sleep_ms.fract();
-            self.schedule(sleep_ms.floor() as i32);
-        } else {
-            self.time_remainder = 0.0;
-            self.schedule(0);
+    fn run(&mut self, timestamp: f64) {
+        // a paused game picks its timing back up from whenever it is unpaused
+        if self.input.handle().get_input().pause {
+            self.next_run = None;
+            self.schedule();
+            return;
+        }
+
+        let mut next_run = self.next_run.unwrap_or(timestamp);
+        if timestamp - next_run > MAX_LAG_MS {
+            log::debug!("dropping {}ms of missed frames", timestamp - next_run);
+            next_run = timestamp;
         }
+
+        let mut runs = 0;
+        while next_run <= timestamp && runs < MAX_RUNS_PER_FRAME {
+            next_run += self.executor.run() as f64;
+            runs += 1;
+        }
+
+        self.next_run = Some(next_run);
+        self.schedule();
     }
 }
 
-fn run() {
+fn run(timestamp: f64) {
     let runner = unsafe { RUNNER.as_mut().expect("runner init") };
-    runner.run()
+    runner.run(timestamp)
 }
 
 struct ConsoleLogger;
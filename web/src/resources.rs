@@ -1,27 +1,66 @@
 use engine::error::Error;
 use engine::Io;
 
-const MEMLIST: &'static [u8] = include_bytes!("../../games/ootw_2/MEMLIST.BIN");
-const BANK01: &'static [u8] = include_bytes!("../../games/ootw_2/BANK01");
-const BANK02: &'static [u8] = include_bytes!("../../games/ootw_2/BANK02");
-const BANK03: &'static [u8] = include_bytes!("../../games/ootw_2/BANK03");
-const BANK04: &'static [u8] = include_bytes!("../../games/ootw_2/BANK04");
-const BANK05: &'static [u8] = include_bytes!("../../games/ootw_2/BANK05");
-const BANK06: &'static [u8] = include_bytes!("../../games/ootw_2/BANK06");
-const BANK07: &'static [u8] = include_bytes!("../../games/ootw_2/BANK07");
-const BANK08: &'static [u8] = include_bytes!("../../games/ootw_2/BANK08");
-const BANK09: &'static [u8] = include_bytes!("../../games/ootw_2/BANK09");
-const BANK0A: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0A");
-const BANK0B: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0B");
-const BANK0C: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0C");
-const BANK0D: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0D");
-
 pub struct EmbeddedResources;
 
 impl Io for EmbeddedResources {
     type Reader = std::io::Cursor<&'static [u8]>;
     fn load<S: AsRef<str>>(&self, file: S) -> Result<Self::Reader, Error> {
-        let bytes = match file.as_ref() {
+        match data::find(file.as_ref()) {
+            Some(bytes) => Ok(std::io::Cursor::new(bytes)),
+            None => Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                file.as_ref(),
+            ))),
+        }
+    }
+}
+
+#[cfg(not(feature = "per-file-data"))]
+mod data {
+    use byteorder::{LittleEndian, ReadBytesExt};
+
+    /// Every file in the data directory, packed by `build.rs`.
+    const PACK: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/game_data.pack"));
+
+    pub fn find(name: &str) -> Option<&'static [u8]> {
+        let mut index = PACK;
+        let count = index.read_u32::<LittleEndian>().ok()?;
+        for _ in 0..count {
+            let name_len = index.read_u16::<LittleEndian>().ok()? as usize;
+            let entry_name = index.get(..name_len)?;
+            index = &index[name_len..];
+            let offset = index.read_u32::<LittleEndian>().ok()? as usize;
+            let len = index.read_u32::<LittleEndian>().ok()? as usize;
+
+            if entry_name.eq_ignore_ascii_case(name.as_bytes()) {
+                return PACK.get(offset..offset + len);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(feature = "per-file-data")]
+mod data {
+    const MEMLIST: &'static [u8] = include_bytes!("../../games/ootw_2/MEMLIST.BIN");
+    const BANK01: &'static [u8] = include_bytes!("../../games/ootw_2/BANK01");
+    const BANK02: &'static [u8] = include_bytes!("../../games/ootw_2/BANK02");
+    const BANK03: &'static [u8] = include_bytes!("../../games/ootw_2/BANK03");
+    const BANK04: &'static [u8] = include_bytes!("../../games/ootw_2/BANK04");
+    const BANK05: &'static [u8] = include_bytes!("../../games/ootw_2/BANK05");
+    const BANK06: &'static [u8] = include_bytes!("../../games/ootw_2/BANK06");
+    const BANK07: &'static [u8] = include_bytes!("../../games/ootw_2/BANK07");
+    const BANK08: &'static [u8] = include_bytes!("../../games/ootw_2/BANK08");
+    const BANK09: &'static [u8] = include_bytes!("../../games/ootw_2/BANK09");
+    const BANK0A: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0A");
+    const BANK0B: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0B");
+    const BANK0C: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0C");
+    const BANK0D: &'static [u8] = include_bytes!("../../games/ootw_2/BANK0D");
+
+    pub fn find(name: &str) -> Option<&'static [u8]> {
+        let bytes = match name {
             "MEMLIST.BIN" => MEMLIST,
             "BANK01" => BANK01,
             "BANK02" => BANK02,
@@ -36,9 +75,9 @@ impl Io for EmbeddedResources {
             "BANK0B" => BANK0B,
             "BANK0C" => BANK0C,
             "BANK0D" => BANK0D,
-            _ => panic!(),
+            _ => return None,
         };
 
-        Ok(std::io::Cursor::new(bytes))
+        Some(bytes)
     }
 }
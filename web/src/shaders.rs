@@ -59,10 +59,17 @@ varying vec2 v_position;
 
 uniform sampler2D u_palette;
 uniform sampler2D u_page;
+uniform mat3 u_color_transform;
 
 void main () {
   float color_index = texture2D(u_page, v_position).r * 255.0;
-  gl_FragColor = vec4(texture2D(u_palette, vec2(color_index / 15.0, 0.0)).rgb, 1.0);
+  if (color_index > 254.5) {
+    // debug clear, a page read before it was ever filled
+    gl_FragColor = vec4(1.0, 0.0, 1.0, 1.0);
+    return;
+  }
+  vec3 color = texture2D(u_palette, vec2(color_index / 15.0, 0.0)).rgb;
+  gl_FragColor = vec4(clamp(u_color_transform * color, 0.0, 1.0), 1.0);
 }
 ";
 